@@ -0,0 +1,208 @@
+use std::hint::black_box;
+
+use comprs::{
+    bits::{BitReader, BitWriter},
+    decode_bytes, encode_bytes,
+    huffman::{self, Decoder, HuffmanTable},
+    Algorithm, Container, Options,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Lines of a made-up server log, `len` bytes in all.
+fn log_text(len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 100);
+    let mut i = 0u64;
+    while out.len() < len {
+        let line = format!(
+            "2024-06-{:02} 12:{:02}:{:02} INFO request id={} path=/api/v1/items/{} status={} took={}ms\n",
+            i % 28 + 1,
+            i % 60,
+            i * 7 % 60,
+            i.wrapping_mul(2_654_435_761) % 1_000_000,
+            i % 977,
+            [200, 200, 200, 404, 500][i as usize % 5],
+            i * 13 % 250
+        );
+        out.extend_from_slice(line.as_bytes());
+        i += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// Pseudo-random bytes, which Huffman coding cannot shrink.
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    (0..len)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed >> 24) as u8
+        })
+        .collect()
+}
+
+/// Bytes with frequencies falling off so steeply that the rarest need codes
+/// of the maximum length, which the decoder looks up in two steps.
+fn skewed_bytes(len: usize) -> Vec<u8> {
+    random_bytes(len)
+        .into_iter()
+        .map(|b| b.trailing_ones() as u8 * 31 + (b >> 7))
+        .collect()
+}
+
+const LEN: usize = 4 << 20;
+
+fn inputs() -> [(&'static str, Vec<u8>); 4] {
+    [
+        ("log", log_text(LEN)),
+        ("random", random_bytes(LEN)),
+        ("skewed", skewed_bytes(LEN)),
+        ("zeros", vec![0; LEN]),
+    ]
+}
+
+fn bench_huffman(c: &mut Criterion) {
+    let mut group = c.benchmark_group("huffman");
+    group.sample_size(20);
+    group.throughput(Throughput::Bytes(LEN as u64));
+    for (name, input) in inputs() {
+        let serial = Options {
+            jobs: 1,
+            ..Options::default()
+        };
+        group.bench_with_input(BenchmarkId::new("encode", name), &input, |b, input| {
+            b.iter(|| encode_bytes(input, &serial).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("encode parallel", name),
+            &input,
+            |b, input| b.iter(|| encode_bytes(input, &Options::default()).unwrap()),
+        );
+        let encoded = encode_bytes(&input, &Options::default()).unwrap();
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, encoded| {
+            b.iter(|| decode_bytes(encoded).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_lzh(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lzh");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(LEN as u64));
+    for (name, input) in inputs() {
+        for level in [1, 6, 9] {
+            let options = Options {
+                algorithm: Algorithm::Lzh,
+                level,
+                jobs: 1,
+                ..Options::default()
+            };
+            group.bench_with_input(
+                BenchmarkId::new(format!("encode level {}", level), name),
+                &input,
+                |b, input| b.iter(|| encode_bytes(input, &options).unwrap()),
+            );
+        }
+        let options = Options {
+            algorithm: Algorithm::Lzh,
+            ..Options::default()
+        };
+        let encoded = encode_bytes(&input, &options).unwrap();
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, encoded| {
+            b.iter(|| decode_bytes(encoded).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_gzip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gzip");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(LEN as u64));
+    for (name, input) in inputs() {
+        let options = Options {
+            container: Container::Gzip,
+            ..Options::default()
+        };
+        group.bench_with_input(BenchmarkId::new("encode", name), &input, |b, input| {
+            b.iter(|| encode_bytes(input, &options).unwrap())
+        });
+        let encoded = encode_bytes(&input, &options).unwrap();
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, encoded| {
+            b.iter(|| decode_bytes(encoded).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_tables(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tables");
+    for (name, input) in inputs() {
+        let counts = huffman::byte_counts(&input);
+        group.bench_with_input(
+            BenchmarkId::new("build code", name),
+            &counts,
+            |b, counts| {
+                b.iter(|| HuffmanTable::from_frequencies(counts, huffman::DEFAULT_MAX_CODE_LEN))
+            },
+        );
+        let table = HuffmanTable::from_frequencies(&counts, huffman::DEFAULT_MAX_CODE_LEN);
+        group.bench_with_input(
+            BenchmarkId::new("build decoder", name),
+            &table,
+            |b, table| b.iter(|| Decoder::new(table)),
+        );
+    }
+    group.finish();
+}
+
+fn bench_bits(c: &mut Criterion) {
+    // code lengths typical of text
+    let lens: Vec<u32> = random_bytes(LEN)
+        .iter()
+        .map(|b| b % 12 + 1)
+        .map(u32::from)
+        .collect();
+    let mut writer = BitWriter::new();
+    for &len in &lens {
+        writer.write(0, len);
+    }
+    let total: usize = lens.iter().map(|&len| len as usize).sum();
+    let (bytes, _) = writer.finish();
+
+    let mut group = c.benchmark_group("bits");
+    group.throughput(Throughput::Bytes(total.div_ceil(8) as u64));
+    group.bench_function("write", |b| {
+        b.iter(|| {
+            let mut writer = BitWriter::new();
+            for &len in &lens {
+                writer.write(1 << (len - 1), len);
+            }
+            writer.finish()
+        })
+    });
+    group.bench_function("read", |b| {
+        b.iter(|| {
+            let mut reader = BitReader::new(&bytes);
+            let mut sum = 0;
+            for &len in &lens {
+                sum += reader.read(len).unwrap();
+            }
+            black_box(sum)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_huffman,
+    bench_lzh,
+    bench_gzip,
+    bench_tables,
+    bench_bits
+);
+criterion_main!(benches);
@@ -0,0 +1,221 @@
+//! Reading and writing streams of bits.
+//!
+//! Bits are packed into bytes starting from the least significant bit, and a
+//! value of several bits is written lowest bit first. Both sides keep up to
+//! 64 bits in an accumulator so that bytes move in and out in bulk rather
+//! than a bit at a time.
+
+/// Most bits that [`BitWriter::write`] and [`BitReader::peek`] handle at once.
+pub const MAX_BITS: u32 = 56;
+
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    out: Vec<u8>,
+    /// Pending bits, the oldest in the lowest position.
+    acc: u64,
+    /// Number of pending bits, always below 64.
+    count: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the low `len` bits of `bits`, which must be zero above them.
+    pub fn write(&mut self, bits: u64, len: u32) {
+        debug_assert!(len <= MAX_BITS, "cannot write {} bits at once", len);
+        debug_assert!(bits >> len == 0, "bits above len are set");
+        if self.count + len >= 64 {
+            self.flush();
+        }
+        self.acc |= bits << self.count;
+        self.count += len;
+    }
+
+    /// Move the whole bytes of the accumulator to the output, leaving fewer
+    /// than 8 bits pending.
+    fn flush(&mut self) {
+        let bytes = self.count / 8;
+        self.out
+            .extend_from_slice(&self.acc.to_le_bytes()[..bytes as usize]);
+        self.acc >>= bytes * 8;
+        self.count %= 8;
+    }
+
+    /// Remove the whole bytes written so far and return them, leaving the
+    /// bits of a byte not yet full pending.
+    pub fn take_bytes(&mut self) -> Vec<u8> {
+        self.flush();
+        std::mem::take(&mut self.out)
+    }
+
+    /// Number of bits written so far, less those taken by
+    /// [`take_bytes`](BitWriter::take_bytes).
+    pub fn bit_len(&self) -> usize {
+        self.out.len() * 8 + self.count as usize
+    }
+
+    /// The bytes written, with the last one padded with zero bits, and the
+    /// number of bits in use in that last byte (8 if it is full or there are
+    /// no bytes at all).
+    pub fn finish(mut self) -> (Vec<u8>, u8) {
+        self.flush();
+        let bits_filled = match self.count {
+            0 => 8,
+            count => {
+                self.out.push(self.acc as u8);
+                count as u8
+            }
+        };
+        (self.out, bits_filled)
+    }
+}
+
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    /// Next byte of `data` to load into the accumulator.
+    pos: usize,
+    /// Loaded bits, the next one in the lowest position.
+    acc: u64,
+    /// Number of loaded bits.
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            pos: 0,
+            acc: 0,
+            count: 0,
+        }
+    }
+
+    /// Load bytes until at least [`MAX_BITS`] bits are available or the data
+    /// runs out.
+    fn refill(&mut self) {
+        if let Some(chunk) = self.data.get(self.pos..self.pos + 8) {
+            let word = u64::from_le_bytes(chunk.try_into().expect("chunk has 8 bytes"));
+            self.acc |= word << self.count;
+            // whole bytes only, so the top of `word` is loaded again next time
+            let bytes = (63 - self.count) / 8;
+            self.pos += bytes as usize;
+            self.count += bytes * 8;
+        } else {
+            while self.count <= MAX_BITS {
+                let Some(&byte) = self.data.get(self.pos) else {
+                    break;
+                };
+                self.acc |= (byte as u64) << self.count;
+                self.pos += 1;
+                self.count += 8;
+            }
+        }
+    }
+
+    /// The next `len` bits without consuming them. Bits past the end of the
+    /// data read as zero.
+    pub fn peek(&mut self, len: u32) -> u64 {
+        debug_assert!(len <= MAX_BITS, "cannot peek {} bits at once", len);
+        if self.count < len {
+            self.refill();
+        }
+        self.acc & ((1 << len) - 1)
+    }
+
+    /// Skip `len` bits, which must have been peeked.
+    pub fn consume(&mut self, len: u32) {
+        debug_assert!(len <= self.count, "consumed bits that were not peeked");
+        self.acc >>= len;
+        self.count -= len;
+    }
+
+    /// Read the next `len` bits, or `None` if fewer remain.
+    pub fn read(&mut self, len: u32) -> Option<u64> {
+        let bits = self.peek(len);
+        if self.count < len {
+            return None;
+        }
+        self.consume(len);
+        Some(bits)
+    }
+
+    /// Number of bits consumed so far.
+    pub fn position(&self) -> usize {
+        self.pos * 8 - self.count as usize
+    }
+
+    /// Number of bits left to consume.
+    pub fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_packs_low_bits_first() {
+        let test_cases = vec![
+            (vec![(0b0110_1001, 8)], vec![0b0110_1001], 8),
+            (
+                vec![(0b0110_1001, 8), (0b010, 3)],
+                vec![0b0110_1001, 0b0000_0010],
+                3,
+            ),
+            (vec![(0b1, 1), (0b11, 2), (0b0, 1)], vec![0b0000_0111], 4),
+            (vec![(0xabcd, 16)], vec![0xcd, 0xab], 8),
+            (vec![], vec![], 8),
+        ];
+
+        for (writes, expected_vec, expected_bits) in test_cases {
+            let mut writer = BitWriter::new();
+            for (bits, len) in writes {
+                writer.write(bits, len);
+            }
+            assert_eq!(writer.finish(), (expected_vec, expected_bits));
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let mut reader = BitReader::new(&[0b0110_1001, 0b0000_0010]);
+        assert_eq!(reader.read(1), Some(1));
+        assert_eq!(reader.read(3), Some(0b100));
+        assert_eq!(reader.peek(6), 0b10_0110);
+        assert_eq!(reader.read(6), Some(0b10_0110));
+        assert_eq!(reader.position(), 10);
+        assert_eq!(reader.remaining(), 6);
+        assert_eq!(reader.read(7), None);
+        assert_eq!(reader.read(6), Some(0));
+        assert_eq!(reader.read(1), None);
+    }
+
+    #[test]
+    fn test_round_trip_mixed_widths() {
+        let values: Vec<(u64, u32)> = (0..10_000u64)
+            .map(|i| {
+                let len = (i * 7 % MAX_BITS as u64) as u32 + 1;
+                let bits = i.wrapping_mul(0x9e37_79b9_7f4a_7c15) & ((1 << len) - 1);
+                (bits, len)
+            })
+            .collect();
+
+        let mut writer = BitWriter::new();
+        for &(bits, len) in &values {
+            writer.write(bits, len);
+        }
+        let total = writer.bit_len();
+        let (bytes, bits_filled) = writer.finish();
+        assert_eq!(bytes.len() * 8 - (8 - bits_filled as usize), total);
+
+        let mut reader = BitReader::new(&bytes);
+        for &(bits, len) in &values {
+            assert_eq!(reader.read(len), Some(bits));
+        }
+        assert_eq!(reader.position(), total);
+    }
+}
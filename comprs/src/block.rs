@@ -0,0 +1,150 @@
+//! Compressing one block of data on its own, with its own Huffman code.
+
+use anyhow::{ensure, Context, Result};
+
+use crate::{
+    bits::{BitReader, BitWriter},
+    format::{Algorithm, BlockHeader},
+    huffman::{self, Decoder, HuffmanTable},
+    lzh, Options,
+};
+
+/// Append `block`, which must not be empty, to `out` as a block with its
+/// header, compressed as `options` say.
+pub fn encode_block(block: &[u8], options: &Options, out: &mut Vec<u8>) {
+    debug_assert!(!block.is_empty(), "an empty block marks the end");
+    let mut payload = Vec::with_capacity(64);
+    match options.algorithm {
+        Algorithm::Huffman => {
+            let counts = huffman::byte_counts(block);
+            let table = HuffmanTable::from_frequencies(&counts, options.max_code_len);
+            huffman::write_lengths(table.lengths(), &mut payload);
+            payload.extend_from_slice(&compress(block, &table));
+        }
+        Algorithm::Lzh => lzh::compress(block, options, &mut payload),
+    }
+
+    BlockHeader {
+        size: block.len() as u32,
+        payload: payload.len() as u32,
+    }
+    .write(out);
+    out.extend_from_slice(&payload);
+}
+
+/// Decode the payload of a block of `size` bytes compressed with
+/// `algorithm`, appending them to `out`.
+pub fn decode_block(
+    mut payload: &[u8],
+    size: usize,
+    algorithm: Algorithm,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    if algorithm == Algorithm::Lzh {
+        return lzh::decompress(payload, size, out);
+    }
+    let lengths =
+        huffman::read_lengths(&mut payload, 256).context("could not read huffman table")?;
+    let table = HuffmanTable::from_lengths(lengths).context("invalid huffman table")?;
+    decompress(payload, size, &table, out)
+}
+
+/// Pack the codes of `contents` into bytes.
+fn compress(contents: &[u8], table: &HuffmanTable) -> Vec<u8> {
+    let mut codes = [None; 256];
+    for (byte, code) in codes.iter_mut().enumerate() {
+        *code = table.get(byte);
+    }
+
+    let mut writer = BitWriter::new();
+    for &byte in contents {
+        let code = codes[byte as usize].expect("the table covers every byte of the contents");
+        writer.write(code.bits, code.len as u32);
+    }
+    writer.finish().0
+}
+
+/// Decode `count` codes packed into `bytes`, appending them to `out`.
+fn decompress(bytes: &[u8], count: usize, table: &HuffmanTable, out: &mut Vec<u8>) -> Result<()> {
+    let decoder = Decoder::new(table);
+    let mut reader = BitReader::new(bytes);
+    out.reserve(count);
+    for _ in 0..count {
+        out.push(decoder.decode(&mut reader)? as u8);
+    }
+    ensure!(
+        reader.remaining() < 8,
+        "unexpected data after the codes of a block"
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::FormatError;
+
+    /// The table giving each byte the code length paired with it.
+    fn table_for(lengths: &[(u8, u8)]) -> HuffmanTable {
+        let mut all = vec![0; 256];
+        for &(byte, len) in lengths {
+            all[byte as usize] = len;
+        }
+        HuffmanTable::from_lengths(all).unwrap()
+    }
+
+    #[test]
+    fn test_compress() {
+        let table = table_for(&[(b'a', 1), (b'b', 2), (b'c', 2)]);
+        let test_cases = vec![
+            (&b"abca"[..], vec![0b0001_1010]),
+            (&b"ccbb"[..], vec![0b0101_1111]),
+            (&b"cccca"[..], vec![0b1111_1111, 0b0000_0000]),
+            (&b""[..], vec![]),
+        ];
+
+        for (input, expected_vec) in test_cases {
+            let result = compress(input, &table);
+            assert_eq!(result, expected_vec);
+            let mut out = Vec::new();
+            decompress(&result, input.len(), &table, &mut out).unwrap();
+            assert_eq!(out, input);
+        }
+    }
+
+    #[test]
+    fn test_decompress_checks_length() {
+        let table = table_for(&[(b'a', 1), (b'b', 2)]);
+        let mut out = Vec::new();
+        decompress(&[0b0000_0010], 2, &table, &mut out).unwrap();
+        assert_eq!(out, b"ab");
+        // the padding decodes as "a"s, but not as many as this
+        let error = decompress(&[0b0000_0010], 9, &table, &mut out).unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&FormatError::Truncated));
+        assert!(decompress(&[0b0000_0010, 0], 2, &table, &mut out).is_err());
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let block = b"a block of text, coded on its own, a block of text";
+        for algorithm in [Algorithm::Huffman, Algorithm::Lzh] {
+            let options = Options {
+                algorithm,
+                ..Options::default()
+            };
+            let mut out = vec![0xaa];
+            encode_block(block, &options, &mut out);
+
+            let size = u32::from_le_bytes(out[1..5].try_into().unwrap()) as usize;
+            let payload = u32::from_le_bytes(out[5..9].try_into().unwrap()) as usize;
+            assert_eq!(size, block.len());
+            assert_eq!(out.len(), 9 + payload);
+            assert!(payload <= BlockHeader::max_payload(size));
+
+            let mut decoded = vec![0xbb];
+            decode_block(&out[9..], size, algorithm, &mut decoded).unwrap();
+            assert_eq!(decoded[1..], block[..], "{:?}", algorithm);
+        }
+    }
+}
@@ -0,0 +1,765 @@
+//! DEFLATE, as specified in RFC 1951.
+//!
+//! Data is matched with [`lz77::tokenize`] over a 32 KiB window and cut into
+//! blocks of up to [`BLOCK_TOKENS`] tokens. Each block is written in
+//! whichever of the three block types comes out smallest: stored, coded with
+//! the fixed Huffman codes of the RFC, or coded with codes of its own that
+//! are sent at its start. Matches may reach back into earlier blocks.
+//!
+//! The bit order is the one [`BitWriter`] and [`BitReader`] use, and the
+//! codes are canonical, so the Huffman codes are those of [`huffman`] limited
+//! to 15 bits, or 7 for the code that the code lengths are sent in.
+//!
+//! Both directions stream: an [`Encoder`] matches its input a [`CHUNK`] at a
+//! time, and [`decompress`] writes its output as it goes, keeping only the
+//! window that matches may reach back into.
+
+use std::io::{self, Write};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    bits::{BitReader, BitWriter},
+    format::FormatError,
+    huffman::{self, Decoder, HuffmanTable},
+    lz77::{self, Token},
+};
+
+/// Level that only stores the data, without looking for matches.
+pub const STORED_LEVEL: u8 = 0;
+
+/// Tokens per block, as many as zlib puts in one.
+pub const BLOCK_TOKENS: usize = 1 << 14;
+
+/// Bytes of input an [`Encoder`] matches at once. Matches reach back into
+/// the chunk before, so this bounds the memory needed rather than changing
+/// the output much.
+pub const CHUNK: usize = 1 << 20;
+
+const WINDOW_BITS: u8 = 15;
+/// Farthest a match may reach back.
+const WINDOW: usize = 1 << WINDOW_BITS;
+/// Bytes of output [`decompress`] holds before writing out all but the
+/// window.
+const SPILL: usize = 4 * WINDOW;
+const END_OF_BLOCK: usize = 256;
+/// Literal/length symbols, of which a block may use 286.
+const LITERAL_LENGTH_SYMBOLS: usize = 288;
+const MAX_LITERAL_LENGTHS: usize = 286;
+/// Distance symbols, of which a block may use 30.
+const DISTANCE_SYMBOLS: usize = 32;
+const MAX_DISTANCES: usize = 30;
+const MAX_CODE_LEN: u8 = 15;
+const MAX_CODE_LENGTH_LEN: u8 = 7;
+/// The order in which the lengths of the code length code are sent.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+/// Most bytes in a stored block.
+const MAX_STORED: usize = 0xffff;
+
+const STORED: u64 = 0b00;
+const FIXED: u64 = 0b01;
+const DYNAMIC: u64 = 0b10;
+
+/// The code lengths of the fixed codes, for literals/lengths and distances.
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+    let mut literals = vec![8; LITERAL_LENGTH_SYMBOLS];
+    literals[144..256].fill(9);
+    literals[256..280].fill(7);
+    (literals, vec![5; DISTANCE_SYMBOLS])
+}
+
+/// Compress `data` into a DEFLATE stream, searching for matches as hard as
+/// `level` says, from [`STORED_LEVEL`] to [`lz77::MAX_LEVEL`].
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    let mut encoder = Encoder::new(Vec::with_capacity(data.len() / 2 + 8), level);
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+/// Compresses the data written to it into a DEFLATE stream in `W`.
+///
+/// [`finish`](Encoder::finish) must be called at the end, or the output is
+/// incomplete.
+pub struct Encoder<W: Write> {
+    inner: W,
+    level: u8,
+    /// The last [`WINDOW`] bytes compressed, followed by at most a
+    /// [`CHUNK`] not compressed yet.
+    data: Vec<u8>,
+    /// Where the data not compressed yet starts in `data`.
+    start: usize,
+    tokens: Vec<Token>,
+    writer: BitWriter,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Start a DEFLATE stream in `inner`, searching for matches as hard as
+    /// `level` says, from [`STORED_LEVEL`] to [`lz77::MAX_LEVEL`].
+    pub fn new(inner: W, level: u8) -> Self {
+        debug_assert!(level <= lz77::MAX_LEVEL, "no level {}", level);
+        Encoder {
+            inner,
+            level,
+            data: Vec::new(),
+            start: 0,
+            tokens: Vec::new(),
+            writer: BitWriter::new(),
+        }
+    }
+
+    /// Compress the pending data as one or more blocks, writing out the
+    /// whole bytes of them.
+    fn write_chunk(&mut self, last: bool) -> io::Result<()> {
+        let pending = &self.data[self.start..];
+        if self.level == STORED_LEVEL {
+            write_stored(&mut self.writer, pending, last);
+        } else {
+            self.tokens.clear();
+            lz77::tokenize_from(
+                &self.data,
+                self.start,
+                self.level,
+                WINDOW_BITS,
+                &mut self.tokens,
+            );
+            write_blocks(&mut self.writer, &self.tokens, pending, last);
+        }
+        self.inner.write_all(&self.writer.take_bytes())?;
+        self.data.drain(..self.data.len().saturating_sub(WINDOW));
+        self.start = self.data.len();
+        Ok(())
+    }
+
+    /// Compress the rest of the data, ending the stream, and return the
+    /// writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_chunk(true)?;
+        let (bytes, _) = self.writer.finish();
+        self.inner.write_all(&bytes)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() - self.start == CHUNK {
+            self.write_chunk(false)?;
+        }
+        let n = buf.len().min(CHUNK - (self.data.len() - self.start));
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write `tokens`, which stand for `bytes`, as blocks of up to
+/// [`BLOCK_TOKENS`] tokens.
+fn write_blocks(writer: &mut BitWriter, tokens: &[Token], bytes: &[u8], last: bool) {
+    if tokens.is_empty() {
+        write_block(writer, &[], &[], last);
+    }
+    let blocks = tokens.len().div_ceil(BLOCK_TOKENS);
+    let mut start = 0;
+    for (i, block) in tokens.chunks(BLOCK_TOKENS).enumerate() {
+        let len: usize = block
+            .iter()
+            .map(|token| match *token {
+                Token::Literal(_) => 1,
+                Token::Match { length, .. } => length as usize,
+            })
+            .sum();
+        write_block(
+            writer,
+            block,
+            &bytes[start..start + len],
+            last && i + 1 == blocks,
+        );
+        start += len;
+    }
+}
+
+/// Write `bytes` as stored blocks.
+fn write_stored(writer: &mut BitWriter, bytes: &[u8], last: bool) {
+    let chunks = bytes.len().div_ceil(MAX_STORED).max(1);
+    for i in 0..chunks {
+        let chunk = &bytes[i * MAX_STORED..bytes.len().min((i + 1) * MAX_STORED)];
+        writer.write((last && i + 1 == chunks) as u64, 1);
+        writer.write(STORED, 2);
+        // the length starts on a byte boundary
+        writer.write(0, ((8 - writer.bit_len() % 8) % 8) as u32);
+        let len = chunk.len() as u64;
+        writer.write(len | (!len & 0xffff) << 16, 32);
+        for &byte in chunk {
+            writer.write(byte as u64, 8);
+        }
+    }
+}
+
+/// Code lengths for symbols occurring `counts` times, giving at least two
+/// symbols a code. Decoders such as zlib's reject a code with a single
+/// symbol, or none, for some alphabets.
+fn lengths_for(counts: &[u32], max_len: u8) -> Vec<u8> {
+    let mut counts = counts.to_vec();
+    for symbol in 0..2 {
+        if counts.iter().filter(|&&count| count > 0).count() < 2 && counts[symbol] == 0 {
+            counts[symbol] = 1;
+        }
+    }
+    huffman::code_lengths(&counts, max_len)
+}
+
+/// A code length code symbol with its extra bits, as `(symbol, bits, number
+/// of bits)`.
+type LengthSymbol = (usize, u64, u32);
+
+/// `lengths` as code length code symbols, with runs shortened by symbols 16
+/// to 18.
+fn length_symbols(lengths: &[u8]) -> Vec<LengthSymbol> {
+    let mut symbols = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let mut run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        i += run;
+        if len == 0 {
+            while run >= 11 {
+                let n = run.min(138);
+                symbols.push((18, (n - 11) as u64, 7));
+                run -= n;
+            }
+            if run >= 3 {
+                symbols.push((17, (run - 3) as u64, 3));
+                run = 0;
+            }
+        } else {
+            symbols.push((len as usize, 0, 0));
+            run -= 1;
+            while run >= 3 {
+                let n = run.min(6);
+                symbols.push((16, (n - 3) as u64, 2));
+                run -= n;
+            }
+        }
+        symbols.extend(std::iter::repeat_n((len as usize, 0, 0), run));
+    }
+    symbols
+}
+
+/// Bits `counts` take with codes of `lengths`.
+fn coded_bits(counts: &[u32], lengths: &[u8]) -> u64 {
+    counts
+        .iter()
+        .zip(lengths)
+        .map(|(&count, &len)| count as u64 * len as u64)
+        .sum()
+}
+
+/// Write `tokens`, which stand for `bytes`, as one block of whichever type
+/// is smallest.
+fn write_block(writer: &mut BitWriter, tokens: &[Token], bytes: &[u8], last: bool) {
+    let mut literal_counts = [0u32; LITERAL_LENGTH_SYMBOLS];
+    let mut distance_counts = [0u32; DISTANCE_SYMBOLS];
+    let mut extra_bits = 0;
+    literal_counts[END_OF_BLOCK] = 1;
+    for &token in tokens {
+        match token {
+            Token::Literal(byte) => literal_counts[byte as usize] += 1,
+            Token::Match { length, distance } => {
+                let (code, _, length_extra) = lz77::length_code(length);
+                let (distance, _, distance_extra) = lz77::distance_code(distance);
+                literal_counts[257 + code] += 1;
+                distance_counts[distance] += 1;
+                extra_bits += length_extra as u64 + distance_extra as u64;
+            }
+        }
+    }
+
+    let literals = lengths_for(&literal_counts[..MAX_LITERAL_LENGTHS], MAX_CODE_LEN);
+    let distances = lengths_for(&distance_counts[..MAX_DISTANCES], MAX_CODE_LEN);
+    // trailing unused symbols need not be sent
+    let literal_len = literals.iter().rposition(|&len| len > 0).unwrap_or(0) + 1;
+    let distance_len = distances.iter().rposition(|&len| len > 0).unwrap_or(0) + 1;
+    let literal_len = literal_len.max(257);
+    let mut all = literals[..literal_len].to_vec();
+    all.extend_from_slice(&distances[..distance_len]);
+    let symbols = length_symbols(&all);
+    let mut length_counts = [0u32; 19];
+    for &(symbol, _, _) in &symbols {
+        length_counts[symbol] += 1;
+    }
+    let length_lengths = lengths_for(&length_counts, MAX_CODE_LENGTH_LEN);
+    let length_len = CODE_LENGTH_ORDER
+        .iter()
+        .rposition(|&symbol| length_lengths[symbol] > 0)
+        .map_or(4, |i| (i + 1).max(4));
+
+    let (fixed_literals, fixed_distances) = fixed_lengths();
+    let header_bits = 5
+        + 5
+        + 4
+        + 3 * length_len as u64
+        + coded_bits(&length_counts, &length_lengths)
+        + symbols.iter().map(|&(_, _, len)| len as u64).sum::<u64>();
+    let dynamic_bits = header_bits
+        + coded_bits(&literal_counts, &literals)
+        + coded_bits(&distance_counts, &distances)
+        + extra_bits;
+    let fixed_bits = coded_bits(&literal_counts, &fixed_literals)
+        + coded_bits(&distance_counts, &fixed_distances)
+        + extra_bits;
+    // a header and length for each stored block, and at most 7 bits to pad
+    let stored_bits = (bytes.len() + 5 * bytes.len().div_ceil(MAX_STORED).max(1)) as u64 * 8 + 7;
+
+    if stored_bits <= dynamic_bits.min(fixed_bits) {
+        write_stored(writer, bytes, last);
+        return;
+    }
+    writer.write(last as u64, 1);
+    let (literals, distances) = if dynamic_bits < fixed_bits {
+        writer.write(DYNAMIC, 2);
+        writer.write((literal_len - 257) as u64, 5);
+        writer.write((distance_len - 1) as u64, 5);
+        writer.write((length_len - 4) as u64, 4);
+        for &symbol in &CODE_LENGTH_ORDER[..length_len] {
+            writer.write(length_lengths[symbol] as u64, 3);
+        }
+        let table =
+            HuffmanTable::from_lengths(length_lengths).expect("built code lengths are valid");
+        for (symbol, bits, len) in symbols {
+            let code = table.get(symbol).expect("the code covers every symbol");
+            writer.write(code.bits, code.len as u32);
+            writer.write(bits, len);
+        }
+        (literals, distances)
+    } else {
+        writer.write(FIXED, 2);
+        (fixed_literals, fixed_distances)
+    };
+    let literals = HuffmanTable::from_lengths(literals).expect("built code lengths are valid");
+    let distances = HuffmanTable::from_lengths(distances).expect("built code lengths are valid");
+    write_tokens(writer, tokens, &literals, &distances);
+}
+
+fn write_tokens(
+    writer: &mut BitWriter,
+    tokens: &[Token],
+    literals: &HuffmanTable,
+    distances: &HuffmanTable,
+) {
+    fn write(writer: &mut BitWriter, table: &HuffmanTable, symbol: usize) {
+        let code = table
+            .get(symbol)
+            .expect("the codes cover every symbol of the block");
+        writer.write(code.bits, code.len as u32);
+    }
+
+    for &token in tokens {
+        match token {
+            Token::Literal(byte) => write(writer, literals, byte as usize),
+            Token::Match { length, distance } => {
+                let (code, length_bits, length_extra) = lz77::length_code(length);
+                let (distance, distance_bits, distance_extra) = lz77::distance_code(distance);
+                write(writer, literals, 257 + code);
+                writer.write(length_bits, length_extra as u32);
+                write(writer, distances, distance);
+                writer.write(distance_bits, distance_extra as u32);
+            }
+        }
+    }
+    write(writer, literals, END_OF_BLOCK);
+}
+
+/// Decompress the DEFLATE stream at the start of `input`, writing the data
+/// to `out` as it goes, and return the number of bytes of `input` it took
+/// up. Matches may only reach back into the data of this stream, and only
+/// the last 32 KiB of it are kept in memory.
+pub fn decompress(input: &[u8], out: &mut impl Write) -> Result<usize> {
+    let mut reader = BitReader::new(input);
+    let mut window = Window::new(out);
+    let mut fixed = None;
+    loop {
+        let last = read(&mut reader, 1)? == 1;
+        match read(&mut reader, 2)? {
+            STORED => read_stored(&mut reader, &mut window)?,
+            FIXED => {
+                let (literals, distances) = fixed.get_or_insert_with(|| {
+                    let (literals, distances) = fixed_lengths();
+                    let decoder = |lengths| {
+                        Decoder::new(
+                            &HuffmanTable::from_lengths(lengths).expect("fixed codes are valid"),
+                        )
+                    };
+                    (decoder(literals), decoder(distances))
+                });
+                read_tokens(&mut reader, literals, distances, &mut window)?;
+            }
+            DYNAMIC => {
+                let (literals, distances) = read_codes(&mut reader)?;
+                read_tokens(&mut reader, &literals, &distances, &mut window)?;
+            }
+            _ => bail!("invalid block type at bit {}", reader.position() - 2),
+        }
+        if last {
+            window.finish()?;
+            return Ok(reader.position().div_ceil(8));
+        }
+    }
+}
+
+/// The data decompressed so far, of which all but the [`WINDOW`] that
+/// matches may reach back into is written out once it grows past
+/// [`SPILL`].
+struct Window<'a, W: Write> {
+    out: &'a mut W,
+    data: Vec<u8>,
+    /// Bytes written out and dropped from `data`.
+    written: usize,
+}
+
+impl<'a, W: Write> Window<'a, W> {
+    fn new(out: &'a mut W) -> Self {
+        Window {
+            out,
+            data: Vec::with_capacity(SPILL + MAX_STORED),
+            written: 0,
+        }
+    }
+
+    /// Number of bytes decompressed so far.
+    fn len(&self) -> usize {
+        self.written + self.data.len()
+    }
+
+    /// Write out all but the window if the data has grown past [`SPILL`].
+    fn spill(&mut self) -> Result<()> {
+        if self.data.len() > SPILL {
+            let end = self.data.len() - WINDOW;
+            self.out.write_all(&self.data[..end])?;
+            self.data.drain(..end);
+            self.written += end;
+        }
+        Ok(())
+    }
+
+    /// Write out the rest of the data.
+    fn finish(self) -> Result<()> {
+        self.out.write_all(&self.data)?;
+        Ok(())
+    }
+}
+
+fn read(reader: &mut BitReader, len: u32) -> Result<u64> {
+    Ok(reader.read(len).ok_or(FormatError::Truncated)?)
+}
+
+fn read_stored(reader: &mut BitReader, window: &mut Window<impl Write>) -> Result<()> {
+    read(reader, ((8 - reader.position() % 8) % 8) as u32)?;
+    let len = read(reader, 16)?;
+    let complement = read(reader, 16)?;
+    ensure!(
+        len == !complement & 0xffff,
+        "stored block length {:#06x} does not match its complement {:#06x}",
+        len,
+        complement
+    );
+    for _ in 0..len {
+        window.data.push(read(reader, 8)? as u8);
+    }
+    window.spill()
+}
+
+/// Read the codes at the start of a dynamic block.
+fn read_codes(reader: &mut BitReader) -> Result<(Decoder, Decoder)> {
+    let literal_len = read(reader, 5)? as usize + 257;
+    let distance_len = read(reader, 5)? as usize + 1;
+    let length_len = read(reader, 4)? as usize + 4;
+    ensure!(
+        literal_len <= MAX_LITERAL_LENGTHS && distance_len <= MAX_DISTANCES,
+        "too many codes: {} literal/length and {} distance",
+        literal_len,
+        distance_len
+    );
+
+    let mut length_lengths = vec![0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..length_len] {
+        length_lengths[symbol] = read(reader, 3)? as u8;
+    }
+    let table = HuffmanTable::from_lengths(length_lengths).context("invalid code length code")?;
+    let decoder = Decoder::new(&table);
+
+    let count = literal_len + distance_len;
+    let mut lengths = Vec::with_capacity(count);
+    while lengths.len() < count {
+        let (len, run) = match decoder.decode(reader)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let &previous = lengths.last().context("code lengths repeat nothing")?;
+                (previous, 3 + read(reader, 2)?)
+            }
+            17 => (0, 3 + read(reader, 3)?),
+            _ => (0, 11 + read(reader, 7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, run as usize));
+    }
+    ensure!(
+        lengths.len() == count,
+        "code lengths run past {} symbols",
+        count
+    );
+    ensure!(
+        lengths[END_OF_BLOCK] > 0,
+        "the literal/length code has no end of block"
+    );
+
+    let distances = lengths.split_off(literal_len);
+    let literals = HuffmanTable::from_lengths(lengths).context("invalid literal/length code")?;
+    let distances = HuffmanTable::from_lengths(distances).context("invalid distance code")?;
+    Ok((Decoder::new(&literals), Decoder::new(&distances)))
+}
+
+/// Read the tokens of a block up to its end into `window`.
+fn read_tokens(
+    reader: &mut BitReader,
+    literals: &Decoder,
+    distances: &Decoder,
+    window: &mut Window<impl Write>,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        match symbol {
+            0..=255 => {
+                window.data.push(symbol as u8);
+                window.spill()?;
+            }
+            END_OF_BLOCK => return Ok(()),
+            257..=285 => {
+                let (base, extra) = lz77::length_base(symbol - 257).expect("length codes");
+                let length = base as usize + read(reader, extra as u32)? as usize;
+                let symbol = distances.decode(reader)?;
+                ensure!(symbol < MAX_DISTANCES, "invalid distance code {}", symbol);
+                let (base, extra) = lz77::distance_base(symbol).expect("distance codes");
+                let distance = base as usize + read(reader, extra as u32)? as usize;
+                ensure!(
+                    distance <= window.data.len(),
+                    "match at byte {} reaches back {} bytes",
+                    window.len(),
+                    distance
+                );
+                lz77::copy_match(&mut window.data, distance, length);
+                window.spill()?;
+            }
+            _ => bail!("invalid literal/length code {}", symbol),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inflate(input: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let used = decompress(input, &mut out)?;
+        ensure!(
+            used == input.len(),
+            "{} bytes left over",
+            input.len() - used
+        );
+        Ok(out)
+    }
+
+    fn text(len: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0u64;
+        while out.len() < len {
+            let line = format!(
+                "line {} of {}: {}\n",
+                i,
+                i % 7,
+                i.wrapping_mul(2_654_435_761) % 1000
+            );
+            out.extend_from_slice(line.as_bytes());
+            i += 1;
+        }
+        out.truncate(len);
+        out
+    }
+
+    #[test]
+    fn test_fixed_and_stored_blocks() {
+        // from RFC 1951 examples as written by zlib: "a" in a fixed block,
+        // and the empty input
+        assert_eq!(inflate(&[0x4b, 0x04, 0x00]).unwrap(), b"a");
+        assert_eq!(inflate(&[0x03, 0x00]).unwrap(), b"");
+        // a stored block holding "hi"
+        assert_eq!(
+            inflate(&[0x01, 0x02, 0x00, 0xfd, 0xff, b'h', b'i']).unwrap(),
+            b"hi"
+        );
+        assert_eq!(
+            compress(b"hi", STORED_LEVEL),
+            [0x01, 0x02, 0x00, 0xfd, 0xff, b'h', b'i']
+        );
+        assert_eq!(compress(b"", 6), [0x03, 0x00]);
+        assert_eq!(compress(b"a", 6), [0x4b, 0x04, 0x00]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let random: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        for data in [text(200_000), random, vec![0; 70_000], b"abc".to_vec()] {
+            for level in [0, 1, 6, 9] {
+                let compressed = compress(&data, level);
+                assert_eq!(inflate(&compressed).unwrap(), data, "level {}", level);
+                // no worse than storing, give or take the block headers
+                assert!(compressed.len() <= data.len() + data.len() / 1000 + 16);
+            }
+        }
+        assert!(compress(&text(200_000), 6).len() < 200_000 / 3);
+    }
+
+    #[test]
+    fn test_encoder_matches_across_chunks() {
+        let data = text(CHUNK + CHUNK / 2);
+        let compressed = compress(&data, 6);
+        assert_eq!(inflate(&compressed).unwrap(), data);
+        // the second chunk starts with matches into the first
+        let mut separately = compress(&data[..CHUNK], 6);
+        separately.extend(compress(&data[CHUNK..], 6));
+        assert!(compressed.len() < separately.len());
+
+        // written in pieces, the output is the same
+        let mut encoder = Encoder::new(Vec::new(), 6);
+        for piece in data.chunks(100_003) {
+            encoder.write_all(piece).unwrap();
+        }
+        assert!(encoder.finish().unwrap() == compressed);
+
+        for level in [0, 1] {
+            let data = text(2 * CHUNK + 1);
+            assert_eq!(inflate(&compress(&data, level)).unwrap(), data);
+        }
+    }
+
+    /// Counts the bytes written to it, and the most written at once.
+    #[derive(Default)]
+    struct Counter {
+        len: usize,
+        largest: usize,
+    }
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.len += buf.len();
+            self.largest = self.largest.max(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_decompress_streams_its_output() {
+        let mut encoder = Encoder::new(Vec::new(), 1);
+        let zeros = vec![0; 1 << 16];
+        for _ in 0..160 {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < 20_000, "{} bytes", bomb.len());
+
+        let mut counter = Counter::default();
+        assert_eq!(decompress(&bomb, &mut counter).unwrap(), bomb.len());
+        assert_eq!(counter.len, 160 << 16);
+        assert!(
+            counter.largest <= SPILL,
+            "{} bytes at once",
+            counter.largest
+        );
+    }
+
+    #[test]
+    fn test_length_symbols() {
+        let lengths = [[0; 140].as_slice(), &[5; 8], &[0; 4], &[3, 3]].concat();
+        assert_eq!(
+            length_symbols(&lengths),
+            [
+                (18, 127, 7),
+                (0, 0, 0),
+                (0, 0, 0),
+                (5, 0, 0),
+                (16, 3, 2),
+                (5, 0, 0),
+                (17, 1, 3),
+                (3, 0, 0),
+                (3, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailing_data_is_left() {
+        let mut compressed = compress(b"hello, hello", 6);
+        let len = compressed.len();
+        compressed.extend_from_slice(b"rest");
+        let mut out = Vec::new();
+        assert_eq!(decompress(&compressed, &mut out).unwrap(), len);
+        assert_eq!(out, b"hello, hello");
+    }
+
+    #[test]
+    fn test_rejects_bad_data() {
+        // block type 3
+        assert!(inflate(&[0x07]).is_err());
+        // a stored block with the wrong complement
+        assert!(inflate(&[0x01, 0x02, 0x00, 0xfd, 0xfe, b'h', b'i']).is_err());
+        // a fixed block with a match before the start
+        let mut writer = BitWriter::new();
+        writer.write(1, 1);
+        writer.write(FIXED, 2);
+        let (literals, distances) = fixed_lengths();
+        let literals = HuffmanTable::from_lengths(literals).unwrap();
+        let distances = HuffmanTable::from_lengths(distances).unwrap();
+        write_tokens(
+            &mut writer,
+            &[Token::Match {
+                length: 3,
+                distance: 1,
+            }],
+            &literals,
+            &distances,
+        );
+        let error = inflate(&writer.finish().0).unwrap_err();
+        assert!(error.to_string().contains("reaches back"), "{}", error);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data = text(5_000);
+        for level in [0, 6] {
+            let compressed = compress(&data, level);
+            for len in 0..compressed.len() {
+                let error = inflate(&compressed[..len]).unwrap_err();
+                assert_eq!(
+                    error.downcast_ref(),
+                    Some(&FormatError::Truncated),
+                    "level {} cut to {}: {:#}",
+                    level,
+                    len,
+                    error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! The compressed file format.
+//!
+//! Integers are little endian. A compressed file is a 6-byte header:
+//!
+//! | offset | size | field                       |
+//! |-------:|-----:|-----------------------------|
+//! |      0 |    4 | magic number, `CPRS`        |
+//! |      4 |    1 | format version, currently 2 |
+//! |      5 |    1 | flags                       |
+//!
+//! The only flag is [`FLAG_LZH`], bit 0, set when the blocks use the
+//! [`Algorithm::Lzh`] payload instead of the [`Algorithm::Huffman`] one.
+//!
+//! then any number of blocks, each compressing up to [`MAX_BLOCK_SIZE`] bytes
+//! of the original data on its own:
+//!
+//! | offset | size | field                                  |
+//! |-------:|-----:|----------------------------------------|
+//! |      0 |    4 | size of the original block, at least 1 |
+//! |      4 |    4 | size `n` of the payload                |
+//! |      8 |  `n` | payload                                |
+//!
+//! A Huffman payload is the code length of each of the 256 byte values, run
+//! length coded as described at [`write_lengths`], then the code of each byte
+//! of the block, packed from the lowest bit of each byte up and padded with
+//! zero bits to a whole byte. An lzh payload is described in [`crate::lzh`].
+//!
+//! After the last block come four zero bytes, where the size of another
+//! block would be, and a 12-byte trailer:
+//!
+//! | offset | size | field                                   |
+//! |-------:|-----:|-----------------------------------------|
+//! |      0 |    8 | size of the original data in bytes      |
+//! |      8 |    4 | CRC-32 of the original data, as in gzip |
+//!
+//! Nothing may follow the trailer. Keeping the totals at the end lets data be
+//! compressed as it arrives, without knowing its size in advance.
+//!
+//! Decoders reject versions and flags they do not know, so that later
+//! versions can change anything after the version byte. Version 1, which
+//! stored the totals in the header and the data in a single block, is no
+//! longer read.
+//!
+//! [`write_lengths`]: crate::huffman::write_lengths
+
+use std::fmt;
+
+use anyhow::{ensure, Result};
+
+use crate::huffman::MAX_CODE_LEN;
+
+pub const MAGIC: [u8; 4] = *b"CPRS";
+pub const VERSION: u8 = 2;
+/// Set when blocks hold LZ77 tokens.
+pub const FLAG_LZH: u8 = 0x01;
+/// Flags this version understands.
+const KNOWN_FLAGS: u8 = FLAG_LZH;
+pub const HEADER_LEN: usize = 6;
+pub const BLOCK_HEADER_LEN: usize = 8;
+pub const TRAILER_LEN: usize = 12;
+/// Largest block a decoder accepts, which bounds the memory it needs.
+pub const MAX_BLOCK_SIZE: usize = 64 << 20;
+
+/// Why compressed data could not be read, for failures a caller may want to
+/// tell apart. Other problems with the payload are reported as plain errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// The data does not start with the magic number, so it was not written
+    /// by comprs.
+    WrongFormat,
+    UnsupportedVersion(u8),
+    UnsupportedFlags(u8),
+    /// The data ends before the header or payload does.
+    Truncated,
+    /// The data decoded, but not to what was compressed.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// The data decoded to as many bytes as the checksum covers, but not as
+    /// many as were compressed.
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::WrongFormat => write!(f, "not a comprs file (wrong magic number)"),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            FormatError::UnsupportedFlags(flags) => write!(f, "unsupported flags {:#04x}", flags),
+            FormatError::Truncated => write!(f, "compressed data is truncated"),
+            FormatError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:08x}, got {:08x}",
+                expected, actual
+            ),
+            FormatError::SizeMismatch { expected, actual } => write!(
+                f,
+                "size mismatch: expected {} bytes, got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// How the blocks of a file are compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Algorithm {
+    /// Each byte Huffman coded on its own.
+    #[default]
+    Huffman,
+    /// Repeated strings replaced by LZ77 matches, then Huffman coded.
+    Lzh,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub flags: u8,
+}
+
+impl Header {
+    pub fn new(algorithm: Algorithm) -> Self {
+        let flags = match algorithm {
+            Algorithm::Huffman => 0,
+            Algorithm::Lzh => FLAG_LZH,
+        };
+        Header { flags }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        if self.flags & FLAG_LZH != 0 {
+            Algorithm::Lzh
+        } else {
+            Algorithm::Huffman
+        }
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.push(self.flags);
+    }
+
+    /// Read a header from the front of `input`, advancing it past the header.
+    /// `input` may be shorter than a header only if it is all there is.
+    pub fn read(input: &mut &[u8]) -> Result<Self> {
+        let magic = &input[..input.len().min(MAGIC.len())];
+        if !MAGIC.starts_with(magic) {
+            return Err(FormatError::WrongFormat.into());
+        }
+        let Some((header, rest)) = input.split_first_chunk::<HEADER_LEN>() else {
+            return Err(FormatError::Truncated.into());
+        };
+        if header[4] != VERSION {
+            return Err(FormatError::UnsupportedVersion(header[4]).into());
+        }
+        if header[5] & !KNOWN_FLAGS != 0 {
+            return Err(FormatError::UnsupportedFlags(header[5]).into());
+        }
+        *input = rest;
+        Ok(Header { flags: header[5] })
+    }
+}
+
+/// The sizes at the start of a block, or the end marker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Size of the original block, or 0 for the end marker.
+    pub size: u32,
+    /// Size of the payload.
+    pub payload: u32,
+}
+
+impl BlockHeader {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&self.payload.to_le_bytes());
+    }
+
+    /// The most payload a block of `size` bytes can need: the longest
+    /// possible code lengths and a code of the longest possible length for
+    /// every byte. Matches only ever take fewer bits per byte than that.
+    pub fn max_payload(size: usize) -> usize {
+        2 * 256 + (size * MAX_CODE_LEN as usize).div_ceil(8)
+    }
+
+    /// Parse the first 4 bytes of a block, which are all of an end marker.
+    /// Returns `None` for the end marker.
+    pub fn read_size(bytes: [u8; 4]) -> Result<Option<usize>> {
+        let size = u32::from_le_bytes(bytes) as usize;
+        ensure!(
+            size <= MAX_BLOCK_SIZE,
+            "block of {} bytes exceeds the maximum of {}",
+            size,
+            MAX_BLOCK_SIZE
+        );
+        Ok(Some(size).filter(|&size| size > 0))
+    }
+
+    /// Parse the payload size that follows the size of a block of `size`
+    /// bytes.
+    pub fn read_payload(size: usize, bytes: [u8; 4]) -> Result<usize> {
+        let payload = u32::from_le_bytes(bytes) as usize;
+        ensure!(
+            payload <= Self::max_payload(size),
+            "payload of {} bytes is too large for a block of {}",
+            payload,
+            size
+        );
+        Ok(payload)
+    }
+}
+
+/// The totals after the last block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trailer {
+    /// Size of the original data in bytes.
+    pub size: u64,
+    /// CRC-32 of the original data.
+    pub crc: u32,
+}
+
+impl Trailer {
+    /// The trailer for `contents`.
+    pub fn for_contents(contents: &[u8]) -> Self {
+        Trailer {
+            size: contents.len() as u64,
+            crc: crc32fast::hash(contents),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; TRAILER_LEN] {
+        let mut out = [0; TRAILER_LEN];
+        out[..8].copy_from_slice(&self.size.to_le_bytes());
+        out[8..].copy_from_slice(&self.crc.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: [u8; TRAILER_LEN]) -> Self {
+        Trailer {
+            size: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            crc: u32::from_le_bytes(bytes[8..].try_into().unwrap()),
+        }
+    }
+
+    /// Check that the data decoded matches this trailer.
+    pub fn verify(&self, decoded: &Trailer) -> Result<()> {
+        if decoded.crc != self.crc {
+            return Err(FormatError::ChecksumMismatch {
+                expected: self.crc,
+                actual: decoded.crc,
+            }
+            .into());
+        }
+        if decoded.size != self.size {
+            return Err(FormatError::SizeMismatch {
+                expected: self.size,
+                actual: decoded.size,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_error(result: Result<Header>) -> FormatError {
+        result
+            .unwrap_err()
+            .downcast::<FormatError>()
+            .expect("a format error")
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let mut out = Vec::new();
+        Header::default().write(&mut out);
+        assert_eq!(out, b"CPRS\x02\x00");
+
+        out.push(0xaa);
+        let mut input = &out[..];
+        assert_eq!(Header::read(&mut input).unwrap(), Header::default());
+        assert_eq!(input, [0xaa]);
+
+        let mut out = Vec::new();
+        Header::new(Algorithm::Lzh).write(&mut out);
+        assert_eq!(out, b"CPRS\x02\x01");
+        let header = Header::read(&mut &out[..]).unwrap();
+        assert_eq!(header.algorithm(), Algorithm::Lzh);
+    }
+
+    #[test]
+    fn test_header_errors() {
+        let mut out = Vec::new();
+        Header::default().write(&mut out);
+
+        assert_eq!(
+            format_error(Header::read(&mut &b"PK\x03\x04 not ours"[..])),
+            FormatError::WrongFormat
+        );
+        for len in 0..HEADER_LEN {
+            assert_eq!(
+                format_error(Header::read(&mut &out[..len])),
+                FormatError::Truncated
+            );
+        }
+
+        let mut old = out.clone();
+        old[4] = 1;
+        assert_eq!(
+            format_error(Header::read(&mut &old[..])),
+            FormatError::UnsupportedVersion(1)
+        );
+        let mut flagged = out.clone();
+        flagged[5] = 0x10;
+        assert_eq!(
+            format_error(Header::read(&mut &flagged[..])),
+            FormatError::UnsupportedFlags(0x10)
+        );
+    }
+
+    #[test]
+    fn test_block_header_limits() {
+        assert_eq!(BlockHeader::read_size([0; 4]).unwrap(), None);
+        assert_eq!(BlockHeader::read_size(5u32.to_le_bytes()).unwrap(), Some(5));
+        let too_big = (MAX_BLOCK_SIZE as u32 + 1).to_le_bytes();
+        assert!(BlockHeader::read_size(too_big).is_err());
+
+        let max = BlockHeader::max_payload(100) as u32;
+        assert!(BlockHeader::read_payload(100, max.to_le_bytes()).is_ok());
+        assert!(BlockHeader::read_payload(100, (max + 1).to_le_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_trailer() {
+        let trailer = Trailer::for_contents(b"hello");
+        // the CRC-32 check value of "hello"
+        assert_eq!(trailer.crc, 0x3610_a686);
+        assert_eq!(Trailer::from_bytes(trailer.to_bytes()), trailer);
+
+        assert!(trailer.verify(&Trailer::for_contents(b"hello")).is_ok());
+        let error = trailer
+            .verify(&Trailer::for_contents(b"hellO"))
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<FormatError>(),
+            Some(FormatError::ChecksumMismatch {
+                expected: 0x3610_a686,
+                ..
+            })
+        ));
+
+        let longer = Trailer {
+            size: 6,
+            ..trailer.clone()
+        };
+        let error = longer.verify(&trailer).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<FormatError>(),
+            Some(&FormatError::SizeMismatch {
+                expected: 6,
+                actual: 5
+            })
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! gzip files, as specified in RFC 1952: DEFLATE data between a header and a
+//! CRC-32 of the original data.
+//!
+//! A file may hold several members one after the other, as `cat a.gz b.gz`
+//! makes, which decompress to their contents one after the other.
+
+use std::io::{self, Write};
+
+use anyhow::{bail, ensure, Result};
+
+use crate::{deflate, format::FormatError};
+
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// The compression method, which is always DEFLATE.
+const DEFLATE: u8 = 8;
+
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+/// Flags that must not be set.
+const RESERVED: u8 = 0xe0;
+
+/// The operating system field for "unknown", as the output does not depend
+/// on it.
+const OS_UNKNOWN: u8 = 255;
+
+/// Whether `bytes` starts like a gzip file.
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Compress `data` into a gzip file with one member, with no file name or
+/// modification time.
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    let out = Vec::with_capacity(data.len() / 2 + 18);
+    let mut encoder = Encoder::new(out, level).expect("writing to a Vec cannot fail");
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+/// Compresses the data written to it into a gzip file with one member in
+/// `W`, with no file name or modification time.
+///
+/// [`finish`](Encoder::finish) must be called at the end, or the output is
+/// incomplete.
+pub struct Encoder<W: Write> {
+    deflate: deflate::Encoder<W>,
+    crc: crc32fast::Hasher,
+    size: u64,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Start a gzip file in `inner`, writing the header.
+    pub fn new(mut inner: W, level: u8) -> io::Result<Self> {
+        // the extra flags tell of the slowest and fastest levels
+        let extra_flags = match level {
+            9 => 2,
+            1 => 4,
+            _ => 0,
+        };
+        inner.write_all(&MAGIC)?;
+        inner.write_all(&[DEFLATE, 0, 0, 0, 0, 0, extra_flags, OS_UNKNOWN])?;
+        Ok(Encoder {
+            deflate: deflate::Encoder::new(inner, level),
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+        })
+    }
+
+    /// Compress the rest of the data, write the trailer and return the
+    /// writer.
+    pub fn finish(self) -> io::Result<W> {
+        let mut inner = self.deflate.finish()?;
+        inner.write_all(&self.crc.finalize().to_le_bytes())?;
+        // the size is only kept modulo 2^32
+        inner.write_all(&(self.size as u32).to_le_bytes())?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.deflate.write(buf)?;
+        self.crc.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.deflate.flush()
+    }
+}
+
+/// Decompress every member of the gzip file `input`.
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    decompress_into(input, &mut out)?;
+    Ok(out)
+}
+
+/// Decompress every member of the gzip file `input`, writing the data to
+/// `out` as it goes.
+pub fn decompress_into(mut input: &[u8], out: &mut impl Write) -> Result<()> {
+    loop {
+        read_member(&mut input, out)?;
+        if input.is_empty() {
+            return Ok(());
+        }
+        ensure!(
+            is_gzip(input),
+            "unexpected data after the end of the compressed data"
+        );
+    }
+}
+
+/// Passes the data written to it on to `W`, keeping its CRC-32 and size.
+struct Checked<'a, W: Write> {
+    inner: &'a mut W,
+    crc: crc32fast::Hasher,
+    size: u64,
+}
+
+impl<W: Write> Write for Checked<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The first `len` bytes of `input`, advancing it past them.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    ensure!(input.len() >= len, FormatError::Truncated);
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+/// Skip a zero-terminated string at the start of `input`.
+fn skip_string(input: &mut &[u8]) -> Result<()> {
+    let Some(end) = input.iter().position(|&byte| byte == 0) else {
+        bail!(FormatError::Truncated);
+    };
+    *input = &input[end + 1..];
+    Ok(())
+}
+
+/// Decompress the member at the start of `input`, writing its data to
+/// `out`, and advance `input` past it.
+fn read_member(input: &mut &[u8], out: &mut impl Write) -> Result<()> {
+    let member = *input;
+    let magic = &input[..input.len().min(MAGIC.len())];
+    ensure!(MAGIC.starts_with(magic), "not a gzip file");
+    let header = take(input, 10)?;
+    ensure!(
+        header[2] == DEFLATE,
+        "unsupported compression method {}",
+        header[2]
+    );
+    let flags = header[3];
+    ensure!(flags & RESERVED == 0, "unsupported flags {:#04x}", flags);
+    if flags & FEXTRA != 0 {
+        let len = take(input, 2)?;
+        take(input, u16::from_le_bytes([len[0], len[1]]) as usize)?;
+    }
+    if flags & FNAME != 0 {
+        skip_string(input)?;
+    }
+    if flags & FCOMMENT != 0 {
+        skip_string(input)?;
+    }
+    if flags & FHCRC != 0 {
+        let header_len = member.len() - input.len();
+        let crc = take(input, 2)?;
+        let expected = u16::from_le_bytes([crc[0], crc[1]]);
+        let actual = crc32fast::hash(&member[..header_len]) as u16;
+        ensure!(
+            expected == actual,
+            "header checksum mismatch: expected {:04x}, got {:04x}",
+            expected,
+            actual
+        );
+    }
+
+    let mut checked = Checked {
+        inner: out,
+        crc: crc32fast::Hasher::new(),
+        size: 0,
+    };
+    let used = deflate::decompress(input, &mut checked)?;
+    *input = &input[used..];
+    let trailer = take(input, 8)?;
+    let expected = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+    let size = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+    let actual = checked.crc.finalize();
+    if expected != actual {
+        return Err(FormatError::ChecksumMismatch { expected, actual }.into());
+    }
+    // the size is only kept modulo 2^32
+    let actual = checked.size as u32;
+    if size != actual {
+        return Err(FormatError::SizeMismatch {
+            expected: size as u64,
+            actual: actual as u64,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &[u8] = include_bytes!("../fixtures/log.txt");
+    const RANDOM: &[u8] = include_bytes!("../fixtures/random.bin");
+
+    #[test]
+    fn test_decompress_gzip_fixtures() {
+        let cases: [(&[u8], &[u8]); 6] = [
+            (include_bytes!("../fixtures/log.txt.gz"), LOG),
+            (include_bytes!("../fixtures/log.txt.1.gz"), LOG),
+            (include_bytes!("../fixtures/log.txt.9.gz"), LOG),
+            (include_bytes!("../fixtures/random.bin.gz"), RANDOM),
+            (include_bytes!("../fixtures/empty.gz"), b""),
+            (
+                include_bytes!("../fixtures/multi.gz"),
+                &[LOG, RANDOM].concat(),
+            ),
+        ];
+        for (i, (gz, expected)) in cases.into_iter().enumerate() {
+            assert!(decompress(gz).unwrap() == expected, "fixture {}", i);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for data in [LOG, RANDOM, b"", b"a"] {
+            for level in [0, 1, 6, 9] {
+                let gz = compress(data, level);
+                assert_eq!(decompress(&gz).unwrap(), data, "level {}", level);
+            }
+        }
+        // about as small as gzip's own
+        let ours = compress(LOG, 6).len();
+        let theirs = include_bytes!("../fixtures/log.txt.gz").len();
+        assert!(ours < theirs + theirs / 20, "{} against {}", ours, theirs);
+    }
+
+    #[test]
+    fn test_header_fields() {
+        let mut gz = vec![0x1f, 0x8b, DEFLATE, FEXTRA | FNAME | FCOMMENT | FHCRC];
+        gz.extend_from_slice(&[0; 6]);
+        gz.extend_from_slice(&[3, 0, b'x', b'y', b'z']);
+        gz.extend_from_slice(b"name.txt\0a comment\0");
+        let crc = crc32fast::hash(&gz) as u16;
+        gz.extend_from_slice(&crc.to_le_bytes());
+        gz.extend_from_slice(&compress(b"data", 6)[10..]);
+        assert_eq!(decompress(&gz).unwrap(), b"data");
+
+        let header_end = gz.len() - compress(b"data", 6).len() + 10;
+        gz[header_end - 1] ^= 1;
+        assert!(decompress(&gz).is_err());
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        assert!(decompress(b"CPRS\x02\x00").is_err());
+        let gz = compress(b"hello", 6);
+
+        let mut wrong_crc = gz.clone();
+        let crc = wrong_crc.len() - 8;
+        wrong_crc[crc] ^= 1;
+        let error = decompress(&wrong_crc).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(FormatError::ChecksumMismatch { .. })
+        ));
+
+        let mut wrong_size = gz.clone();
+        let size = wrong_size.len() - 4;
+        wrong_size[size] ^= 1;
+        assert_eq!(
+            decompress(&wrong_size).unwrap_err().downcast_ref(),
+            Some(&FormatError::SizeMismatch {
+                expected: 4,
+                actual: 5
+            })
+        );
+
+        let mut trailing = gz.clone();
+        trailing.push(0);
+        assert!(decompress(&trailing).is_err());
+
+        for len in 0..gz.len() {
+            let error = decompress(&gz[..len]).unwrap_err();
+            assert_eq!(
+                error.downcast_ref(),
+                Some(&FormatError::Truncated),
+                "cut to {}: {:#}",
+                len,
+                error
+            );
+        }
+    }
+}
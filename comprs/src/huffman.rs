@@ -0,0 +1,770 @@
+//! Canonical Huffman codes.
+//!
+//! In a canonical code, codes of the same length are consecutive binary
+//! numbers in symbol order and shorter codes sort before longer ones, so the
+//! length of each symbol's code is all it takes to rebuild the whole code.
+//! That is what a compressed stream carries, which keeps the header small and
+//! makes the output depend on nothing but the input.
+
+use std::collections::BinaryHeap;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    bits::{BitReader, MAX_BITS},
+    format::FormatError,
+};
+
+/// Longest code a table may hold. A [`Decoder`] needs up to
+/// `2^(MAX_CODE_LEN - PRIMARY_BITS)` entries for each code longer than
+/// [`PRIMARY_BITS`], so this bounds what a file can make it allocate.
+pub const MAX_CODE_LEN: u8 = 24;
+// any code must fit in one bit write and one peek
+const _: () = assert!(MAX_CODE_LEN as u32 <= MAX_BITS);
+
+/// Default limit on code lengths when building a code.
+pub const DEFAULT_MAX_CODE_LEN: u8 = 15;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Node {
+    symbol: Option<usize>,
+    freq: u32,
+    left: Option<Box<Node>>,
+    right: Option<Box<Node>>,
+}
+
+impl Node {
+    fn new(symbol: Option<usize>, freq: u32) -> Self {
+        Node {
+            symbol,
+            freq,
+            left: None,
+            right: None,
+        }
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other
+            .freq
+            .cmp(&self.freq)
+            .then_with(|| other.symbol.cmp(&self.symbol))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<&mut BinaryHeap<Node>> for Node {
+    /// Construct a huffman tree from a non-empty priority queue
+    fn from(value: &mut BinaryHeap<Node>) -> Self {
+        while value.len() > 1 {
+            let a = value.pop().unwrap();
+            let b = value.pop().unwrap();
+
+            let mut c = Node::new(None, a.freq + b.freq);
+
+            c.left = Some(Box::new(a));
+            c.right = Some(Box::new(b));
+
+            value.push(c);
+        }
+
+        value.pop().unwrap()
+    }
+}
+
+/// A code of `len` bits, held in the order they are written: the first bit
+/// is the lowest.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Code {
+    pub bits: u64,
+    pub len: u8,
+}
+
+/// The length of each symbol's code in an optimal prefix code for `freqs`
+/// with no code longer than `max_len`, or 0 for symbols that do not occur.
+///
+/// # Panics
+///
+/// If more than `2^max_len` symbols occur, as they cannot all have codes.
+pub fn code_lengths(freqs: &[u32], max_len: u8) -> Vec<u8> {
+    let used = freqs.iter().filter(|&&freq| freq > 0).count();
+    assert!(
+        max_len > 0 && max_len <= MAX_CODE_LEN && used <= 1 << max_len,
+        "{} symbols do not fit in codes of up to {} bits",
+        used,
+        max_len
+    );
+    let lengths = huffman_lengths(freqs);
+    if lengths.iter().all(|&len| len <= max_len) {
+        lengths
+    } else {
+        package_merge(freqs, max_len)
+    }
+}
+
+/// The code lengths of a Huffman tree for `freqs`, however long.
+fn huffman_lengths(freqs: &[u32]) -> Vec<u8> {
+    fn traverse(lengths: &mut [u8], node: &Node, depth: u8) {
+        if let Some(symbol) = node.symbol {
+            lengths[symbol] = depth;
+        } else {
+            if let Some(left) = &node.left {
+                traverse(lengths, left, depth + 1);
+            }
+            if let Some(right) = &node.right {
+                traverse(lengths, right, depth + 1);
+            }
+        }
+    }
+
+    let mut heap: BinaryHeap<_> = freqs
+        .iter()
+        .enumerate()
+        .filter(|&(_, &freq)| freq > 0)
+        .map(|(symbol, &freq)| Node::new(Some(symbol), freq))
+        .collect();
+
+    let mut lengths = vec![0; freqs.len()];
+    match heap.len() {
+        0 => {}
+        // a lone symbol still needs one bit per occurrence
+        1 => lengths[heap.pop().unwrap().symbol.unwrap()] = 1,
+        _ => traverse(&mut lengths, &Node::from(&mut heap), 0),
+    }
+    lengths
+}
+
+/// The code lengths found by the package-merge algorithm, which is optimal
+/// for codes no longer than `max_len`.
+///
+/// Each symbol is a coin worth 2^-len for every length `len` up to the limit,
+/// whose price is the symbol's frequency. The cheapest set of coins worth
+/// `n - 1` in total gives each symbol a code as long as the number of its
+/// coins in the set. Starting with the smallest coins, pairs are packaged
+/// into coins of the next size and merged with the symbols' own coins of
+/// that size, cheapest first; the first `2n - 2` coins of size 1/2 are the
+/// answer.
+fn package_merge(freqs: &[u32], max_len: u8) -> Vec<u8> {
+    enum Coin {
+        Symbol(usize),
+        /// Two coins of the previous size, as indices into the coins.
+        Package(usize, usize),
+    }
+
+    let mut symbols: Vec<usize> = (0..freqs.len()).filter(|&s| freqs[s] > 0).collect();
+    // stable, so that ties go to the lower symbol and the result is
+    // deterministic
+    symbols.sort_by_key(|&s| freqs[s]);
+
+    let mut lengths = vec![0; freqs.len()];
+    if symbols.len() < 2 {
+        for &symbol in &symbols {
+            lengths[symbol] = 1;
+        }
+        return lengths;
+    }
+
+    let mut coins: Vec<(u64, Coin)> = symbols
+        .iter()
+        .map(|&s| (freqs[s] as u64, Coin::Symbol(s)))
+        .collect();
+    let leaves: Vec<usize> = (0..symbols.len()).collect();
+    let mut row = leaves.clone();
+    for _ in 1..max_len {
+        let packages: Vec<usize> = row
+            .chunks_exact(2)
+            .map(|pair| {
+                let price = coins[pair[0]].0 + coins[pair[1]].0;
+                coins.push((price, Coin::Package(pair[0], pair[1])));
+                coins.len() - 1
+            })
+            .collect();
+
+        // merge the two sorted lists, symbols first among equal prices
+        row = Vec::with_capacity(leaves.len() + packages.len());
+        let (mut i, mut j) = (0, 0);
+        while i < leaves.len() || j < packages.len() {
+            let take_leaf = j == packages.len()
+                || (i < leaves.len() && coins[leaves[i]].0 <= coins[packages[j]].0);
+            if take_leaf {
+                row.push(leaves[i]);
+                i += 1;
+            } else {
+                row.push(packages[j]);
+                j += 1;
+            }
+        }
+    }
+
+    let mut stack = row[..2 * symbols.len() - 2].to_vec();
+    while let Some(coin) = stack.pop() {
+        match coins[coin].1 {
+            Coin::Symbol(symbol) => lengths[symbol] += 1,
+            Coin::Package(a, b) => stack.extend([a, b]),
+        }
+    }
+    lengths
+}
+
+/// The canonical code for each symbol of an alphabet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HuffmanTable {
+    lengths: Vec<u8>,
+    codes: Vec<Code>,
+}
+
+impl HuffmanTable {
+    /// The optimal code for symbols occurring `freqs` times each, with no
+    /// code longer than `max_len`. Panics like [`code_lengths`].
+    pub fn from_frequencies(freqs: &[u32], max_len: u8) -> Self {
+        Self::from_lengths(code_lengths(freqs, max_len)).expect("built code lengths are valid")
+    }
+
+    /// The canonical code with the given code lengths, where 0 marks an
+    /// unused symbol. Fails if no prefix code has these lengths.
+    pub fn from_lengths(lengths: Vec<u8>) -> Result<Self> {
+        let counts = length_counts(&lengths)?;
+
+        // the first code of each length, as a number whose top bit is sent
+        // first
+        let mut next = vec![0u64; counts.len()];
+        let mut code = 0;
+        for len in 1..counts.len() {
+            code = (code + counts[len - 1] as u64) << 1;
+            next[len] = code;
+        }
+
+        let codes = lengths
+            .iter()
+            .map(|&len| {
+                let code = next[len as usize];
+                next[len as usize] += 1;
+                Code {
+                    bits: reverse(code, len),
+                    len,
+                }
+            })
+            .collect();
+        Ok(HuffmanTable { lengths, codes })
+    }
+
+    /// Number of symbols in the alphabet, used or not.
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    pub fn lengths(&self) -> &[u8] {
+        &self.lengths
+    }
+
+    /// The code of `symbol`, or `None` if it has none.
+    pub fn get(&self, symbol: usize) -> Option<Code> {
+        self.codes.get(symbol).copied().filter(|code| code.len > 0)
+    }
+}
+
+impl From<&[u8]> for HuffmanTable {
+    /// The code for the bytes of `value`, limited to the default length.
+    fn from(value: &[u8]) -> Self {
+        HuffmanTable::from_frequencies(&byte_counts(value), DEFAULT_MAX_CODE_LEN)
+    }
+}
+
+/// How often each byte value occurs in `bytes`.
+pub fn byte_counts(bytes: &[u8]) -> [u32; 256] {
+    let mut counts = [0u32; 256];
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+    counts
+}
+
+/// How many codes there are of each length, indexed by length, after checking
+/// that a prefix code can have these lengths. Index 0 is always 0.
+fn length_counts(lengths: &[u8]) -> Result<Vec<u32>> {
+    let max = lengths.iter().copied().max().unwrap_or(0);
+    ensure!(
+        max <= MAX_CODE_LEN,
+        "code length {} exceeds the maximum of {}",
+        max,
+        MAX_CODE_LEN
+    );
+    let mut counts = vec![0u32; max as usize + 1];
+    for &len in lengths.iter().filter(|&&len| len > 0) {
+        counts[len as usize] += 1;
+    }
+
+    // the number of codes of the current length still unassigned
+    let mut left = 1i64;
+    for &count in &counts[1..] {
+        left = left * 2 - count as i64;
+        ensure!(left >= 0, "code lengths describe more codes than fit");
+    }
+    Ok(counts)
+}
+
+/// The low `len` bits of `bits` in reverse order.
+fn reverse(bits: u64, len: u8) -> u64 {
+    match len {
+        0 => 0,
+        len => bits.reverse_bits() >> (64 - len as u32),
+    }
+}
+
+/// Bits the first lookup of [`Decoder::decode`] resolves. Longer codes take
+/// a second lookup.
+const PRIMARY_BITS: u32 = 10;
+
+/// What the next bits of the input decode to.
+#[derive(Clone, Copy, Debug, Default)]
+struct Entry {
+    /// The symbol, or for a link the offset of its secondary table.
+    value: u32,
+    /// Length of the symbol's code, or 0 if the bits start no code.
+    len: u8,
+    /// For a link, the number of bits after the primary ones that index its
+    /// secondary table; 0 otherwise.
+    sub_bits: u8,
+}
+
+/// Reads symbols coded with a [`HuffmanTable`] by table lookup.
+///
+/// The primary table is indexed by the next [`PRIMARY_BITS`] bits of input
+/// and gives the symbol and length of any code that short directly, with
+/// every entry whose index starts with a code holding that code. An index
+/// that starts a longer code instead links to a secondary table covering all
+/// codes beginning with it, indexed by the bits that follow.
+#[derive(Clone, Debug)]
+pub struct Decoder {
+    primary_bits: u32,
+    primary: Vec<Entry>,
+    secondary: Vec<Entry>,
+    /// Length of the longest code.
+    max_len: u32,
+}
+
+impl Decoder {
+    pub fn new(table: &HuffmanTable) -> Self {
+        let max_len = table.lengths().iter().copied().max().unwrap_or(0) as u32;
+        let primary_bits = max_len.clamp(1, PRIMARY_BITS);
+        let mut primary = vec![Entry::default(); 1 << primary_bits];
+        let mut secondary = Vec::new();
+        let codes = || (0..table.len()).filter_map(|s| Some((s, table.get(s)?)));
+
+        // size each secondary table for the longest code it holds
+        for (_, code) in codes().filter(|(_, code)| code.len as u32 > primary_bits) {
+            let link = &mut primary[(code.bits & ((1 << primary_bits) - 1)) as usize];
+            link.sub_bits = link.sub_bits.max(code.len - primary_bits as u8);
+        }
+        for link in primary.iter_mut().filter(|link| link.sub_bits > 0) {
+            link.value = secondary.len() as u32;
+            secondary.resize(secondary.len() + (1 << link.sub_bits), Entry::default());
+        }
+
+        for (symbol, code) in codes() {
+            let entry = Entry {
+                value: symbol as u32,
+                len: code.len,
+                sub_bits: 0,
+            };
+            let len = code.len as u32;
+            if len <= primary_bits {
+                for index in (code.bits as usize..primary.len()).step_by(1 << len) {
+                    primary[index] = entry;
+                }
+            } else {
+                let link = primary[(code.bits & ((1 << primary_bits) - 1)) as usize];
+                let rest = (code.bits >> primary_bits) as usize;
+                let start = link.value as usize;
+                for index in (rest..1 << link.sub_bits).step_by(1 << (len - primary_bits)) {
+                    secondary[start + index] = entry;
+                }
+            }
+        }
+
+        Decoder {
+            primary_bits,
+            primary,
+            secondary,
+            max_len,
+        }
+    }
+
+    /// Read one code from `reader` and return its symbol.
+    pub fn decode(&self, reader: &mut BitReader) -> Result<usize> {
+        let bits = reader.peek(self.max_len.max(self.primary_bits));
+        let mut entry = self.primary[(bits & ((1 << self.primary_bits) - 1)) as usize];
+        if entry.sub_bits > 0 {
+            let index = (bits >> self.primary_bits) & ((1 << entry.sub_bits) - 1);
+            entry = self.secondary[entry.value as usize + index as usize];
+        }
+        let remaining = reader.remaining();
+        // past the end the bits read as zeros, which need not form a code
+        if entry.len == 0 && remaining >= self.max_len as usize {
+            bail!("invalid code at bit {}", reader.position());
+        }
+        if entry.len == 0 || entry.len as usize > remaining {
+            return Err(FormatError::Truncated.into());
+        }
+        reader.consume(entry.len as u32);
+        Ok(entry.value as usize)
+    }
+}
+
+/// Smallest byte of a run of repeated lengths.
+const REPEAT: u8 = 0x40;
+/// Smallest byte of a run of zero lengths.
+const ZEROS: u8 = 0x80;
+
+/// Append `lengths` to `out`, run-length coded. Each byte is one of
+///
+/// - `0..=0x3f`: one code length,
+/// - `0x40 | n`: the previous length again, `n + 1` times,
+/// - `0x80 | n`: `n + 1` unused symbols.
+pub fn write_lengths(lengths: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        i += run;
+        if len == 0 {
+            for chunk in chunks(run, 0x80) {
+                out.push(ZEROS | (chunk - 1) as u8);
+            }
+        } else {
+            out.push(len);
+            for chunk in chunks(run - 1, 0x40) {
+                out.push(REPEAT | (chunk - 1) as u8);
+            }
+        }
+    }
+}
+
+/// `n` split into parts of at most `max`.
+fn chunks(n: usize, max: usize) -> impl Iterator<Item = usize> {
+    (0..n.div_ceil(max)).map(move |i| (n - i * max).min(max))
+}
+
+/// Read `count` code lengths written by [`write_lengths`] from the front of
+/// `input`, advancing it past them.
+pub fn read_lengths(input: &mut &[u8], count: usize) -> Result<Vec<u8>> {
+    let mut lengths = Vec::with_capacity(count);
+    while lengths.len() < count {
+        let (&byte, rest) = input.split_first().ok_or(FormatError::Truncated)?;
+        *input = rest;
+        match byte {
+            len if len < REPEAT => {
+                ensure!(len <= MAX_CODE_LEN, "invalid code length {}", len);
+                lengths.push(len);
+            }
+            run if run < ZEROS => {
+                let &previous = lengths
+                    .last()
+                    .filter(|&&len| len > 0)
+                    .context("code lengths repeat nothing")?;
+                let run = (run - REPEAT) as usize + 1;
+                lengths.extend(std::iter::repeat_n(previous, run));
+            }
+            run => {
+                let run = (run - ZEROS) as usize + 1;
+                lengths.extend(std::iter::repeat_n(0, run));
+            }
+        }
+    }
+    ensure!(
+        lengths.len() == count,
+        "code lengths run past {} symbols",
+        count
+    );
+    Ok(lengths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::bits::BitWriter;
+
+    /// A code from its bits in the order they are written.
+    fn code(bits: &str) -> Code {
+        bits.bytes()
+            .fold(Code { bits: 0, len: 0 }, |code, bit| Code {
+                bits: code.bits | ((bit - b'0') as u64) << code.len,
+                len: code.len + 1,
+            })
+    }
+
+    #[test]
+    fn test_encoding_table() {
+        // Arrange
+        let counts = HashMap::from([
+            (b'C', 32),
+            (b'D', 42),
+            (b'E', 120),
+            (b'K', 7),
+            (b'L', 42),
+            (b'M', 24),
+            (b'U', 37),
+            (b'Z', 2),
+        ]);
+        let contents = counts
+            .iter()
+            .flat_map(|(k, v)| std::iter::repeat_n(*k, *v))
+            .collect::<Vec<u8>>();
+
+        // Act
+        let table = HuffmanTable::from(&contents[..]);
+
+        // Assert
+        assert_eq!(table.get(b'C' as usize), Some(code("1110")));
+        assert_eq!(table.get(b'D' as usize), Some(code("100")));
+        assert_eq!(table.get(b'E' as usize), Some(code("0")));
+        assert_eq!(table.get(b'K' as usize), Some(code("111110")));
+        assert_eq!(table.get(b'L' as usize), Some(code("101")));
+        assert_eq!(table.get(b'M' as usize), Some(code("11110")));
+        assert_eq!(table.get(b'U' as usize), Some(code("110")));
+        assert_eq!(table.get(b'Z' as usize), Some(code("111111")));
+        assert_eq!(table.get(b'A' as usize), None);
+    }
+
+    #[test]
+    fn test_canonical_codes() {
+        // the example from RFC 1951, section 3.2.2
+        let table = HuffmanTable::from_lengths(vec![3, 3, 3, 3, 3, 2, 4, 4]).unwrap();
+        let expected = ["010", "011", "100", "101", "110", "00", "1110", "1111"];
+        for (symbol, expected) in expected.into_iter().enumerate() {
+            assert_eq!(table.get(symbol), Some(code(expected)));
+        }
+    }
+
+    #[test]
+    fn test_from_lengths_rejects_oversubscribed() {
+        assert!(HuffmanTable::from_lengths(vec![1, 1, 1]).is_err());
+        assert!(HuffmanTable::from_lengths(vec![2, 2, 2, 2, 3]).is_err());
+        assert!(HuffmanTable::from_lengths(vec![MAX_CODE_LEN + 1, 1]).is_err());
+        // incomplete codes are allowed
+        assert!(HuffmanTable::from_lengths(vec![0, 1, 0]).is_ok());
+        assert!(HuffmanTable::from_lengths(vec![2, 2, 2]).is_ok());
+    }
+
+    #[test]
+    fn test_decoder() {
+        let freqs = [10, 1, 0, 3, 7, 7, 2, 0, 0, 1];
+        let table = HuffmanTable::from_frequencies(&freqs, DEFAULT_MAX_CODE_LEN);
+        let symbols = [0, 1, 3, 4, 5, 6, 9, 0, 0, 4];
+
+        let mut writer = BitWriter::new();
+        for &symbol in &symbols {
+            let code = table.get(symbol).unwrap();
+            writer.write(code.bits, code.len as u32);
+        }
+        let (bytes, _) = writer.finish();
+
+        let decoder = Decoder::new(&table);
+        let mut reader = BitReader::new(&bytes);
+        for &symbol in &symbols {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), symbol);
+        }
+    }
+
+    /// The first `n` Fibonacci numbers, the frequencies giving the deepest
+    /// Huffman tree.
+    fn fibonacci(n: usize) -> Vec<u32> {
+        let mut out = vec![1, 1];
+        while out.len() < n {
+            out.push(out[out.len() - 1] + out[out.len() - 2]);
+        }
+        out.truncate(n);
+        out
+    }
+
+    fn cost(freqs: &[u32], lengths: &[u8]) -> u64 {
+        freqs
+            .iter()
+            .zip(lengths)
+            .map(|(&freq, &len)| freq as u64 * len as u64)
+            .sum()
+    }
+
+    #[test]
+    fn test_fibonacci_lengths_are_limited() {
+        let freqs = fibonacci(30);
+        assert_eq!(huffman_lengths(&freqs).iter().max(), Some(&29));
+
+        for max_len in [5, 8, 12, DEFAULT_MAX_CODE_LEN, 20] {
+            let lengths = code_lengths(&freqs, max_len);
+            assert_eq!(lengths.iter().max(), Some(&max_len), "limit {}", max_len);
+            // a complete code, so no bits are wasted
+            let kraft: f64 = lengths.iter().map(|&len| 0.5f64.powi(len as i32)).sum();
+            assert_eq!(kraft, 1.0);
+            // rarer symbols never get shorter codes
+            assert!(lengths.windows(2).skip(1).all(|w| w[0] >= w[1]));
+            assert!(HuffmanTable::from_lengths(lengths).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_package_merge_is_optimal() {
+        // the best lengths within 3 bits, found by hand
+        let freqs = [1, 1, 2, 4, 8];
+        assert_eq!(huffman_lengths(&freqs), [4, 4, 3, 2, 1]);
+        assert_eq!(code_lengths(&freqs, 3), [3, 3, 3, 3, 1]);
+
+        // with room to spare it costs the same as the Huffman code
+        let freqs = [5, 0, 9, 12, 13, 16, 45, 1, 0, 3];
+        let unlimited = package_merge(&freqs, MAX_CODE_LEN);
+        assert_eq!(
+            cost(&freqs, &unlimited),
+            cost(&freqs, &huffman_lengths(&freqs))
+        );
+        assert_eq!(unlimited[1], 0);
+        assert_eq!(unlimited[8], 0);
+    }
+
+    #[test]
+    fn test_full_alphabet_at_minimum_length() {
+        let freqs = fibonacci(40)
+            .iter()
+            .cycle()
+            .take(256)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(code_lengths(&freqs, 8), vec![8; 256]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_limit_too_small_for_alphabet() {
+        code_lengths(&[1; 9], 3);
+    }
+
+    #[test]
+    fn test_decoder_long_codes() {
+        // codes up to 20 bits, so that most need a secondary table, over an
+        // alphabet larger than a byte
+        let mut freqs = fibonacci(30);
+        freqs.extend([0, 7, 7, 7].iter().cycle().take(270));
+        let table = HuffmanTable::from_frequencies(&freqs, 20);
+        let max = *table.lengths().iter().max().unwrap();
+        assert!(max as u32 > PRIMARY_BITS + 5 && max <= 20);
+
+        let symbols: Vec<usize> = (0..freqs.len()).filter(|&s| freqs[s] > 0).collect();
+        let mut writer = BitWriter::new();
+        for &symbol in symbols.iter().chain(symbols.iter().rev()) {
+            let code = table.get(symbol).unwrap();
+            writer.write(code.bits, code.len as u32);
+        }
+        let (bytes, _) = writer.finish();
+
+        let decoder = Decoder::new(&table);
+        let mut reader = BitReader::new(&bytes);
+        for &symbol in symbols.iter().chain(symbols.iter().rev()) {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), symbol);
+        }
+    }
+
+    #[test]
+    fn test_decoder_longest_codes() {
+        // every length up to the limit, with all the codes longer than the
+        // primary ones sharing one secondary table of the largest size
+        let freqs = fibonacci(MAX_CODE_LEN as usize + 1);
+        let table = HuffmanTable::from_frequencies(&freqs, MAX_CODE_LEN);
+        assert_eq!(table.lengths().iter().max(), Some(&MAX_CODE_LEN));
+
+        let decoder = Decoder::new(&table);
+        assert_eq!(
+            decoder.secondary.len(),
+            1 << (MAX_CODE_LEN as u32 - PRIMARY_BITS)
+        );
+
+        let mut writer = BitWriter::new();
+        for symbol in 0..freqs.len() {
+            let code = table.get(symbol).unwrap();
+            writer.write(code.bits, code.len as u32);
+        }
+        let (bytes, _) = writer.finish();
+        let mut reader = BitReader::new(&bytes);
+        for symbol in 0..freqs.len() {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), symbol);
+        }
+    }
+
+    #[test]
+    fn test_decoder_rejects_truncated_code() {
+        let table = HuffmanTable::from_lengths(vec![1, 2, 3, 3]).unwrap();
+        let decoder = Decoder::new(&table);
+        // six times "0", then the first two bits of "110" or "111"
+        let mut reader = BitReader::new(&[0b1100_0000]);
+        for _ in 0..6 {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), 0);
+        }
+        assert!(decoder.decode(&mut reader).is_err());
+        assert_eq!(reader.remaining(), 2);
+    }
+
+    #[test]
+    fn test_decoder_rejects_unassigned_code() {
+        // only "0" is assigned
+        let table = HuffmanTable::from_lengths(vec![1]).unwrap();
+        let decoder = Decoder::new(&table);
+        assert_eq!(decoder.decode(&mut BitReader::new(&[0b10])).unwrap(), 0);
+        assert!(decoder.decode(&mut BitReader::new(&[0b01])).is_err());
+        assert!(decoder.decode(&mut BitReader::new(&[])).is_err());
+    }
+
+    #[test]
+    fn test_lengths_round_trip() {
+        let mut long_runs = vec![0; 300];
+        long_runs[150..290].fill(9);
+        long_runs[299] = 1;
+        let test_cases = vec![
+            vec![0; 256],
+            vec![8; 256],
+            vec![1, 2, 3, 3, 0, 0, 4, 4, 4, 0],
+            long_runs,
+            vec![],
+        ];
+
+        for lengths in test_cases {
+            let mut out = Vec::new();
+            write_lengths(&lengths, &mut out);
+            out.push(0xaa);
+            let mut input = &out[..];
+            assert_eq!(read_lengths(&mut input, lengths.len()).unwrap(), lengths);
+            assert_eq!(input, [0xaa]);
+        }
+    }
+
+    #[test]
+    fn test_lengths_are_compact() {
+        let text = b"The quick brown fox jumps over the lazy dog, again and again.";
+        let table = HuffmanTable::from(&text[..]);
+        let mut out = Vec::new();
+        write_lengths(table.lengths(), &mut out);
+        assert!(out.len() < 64, "{} bytes", out.len());
+
+        let mut out = Vec::new();
+        write_lengths(&[0; 256], &mut out);
+        assert_eq!(out.len(), 2);
+    }
+
+    #[test]
+    fn test_read_lengths_rejects_bad_input() {
+        assert!(read_lengths(&mut &[1, 2][..], 3).is_err());
+        assert!(read_lengths(&mut &[0x41][..], 2).is_err());
+        assert!(read_lengths(&mut &[0x80, 0x41][..], 3).is_err());
+        assert!(read_lengths(&mut &[0x83][..], 3).is_err());
+        assert!(read_lengths(&mut &[0x3f][..], 1).is_err());
+    }
+}
@@ -1,338 +1,737 @@
 use std::{
-    char,
-    collections::{BinaryHeap, HashMap},
     fs::File,
-    io::{Read, Write},
-    mem::size_of,
-    path::PathBuf,
-    str::{self},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    thread,
 };
 
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Eq, PartialEq, Debug)]
-pub struct Node {
-    ch: Option<char>,
-    freq: u32,
-    left: Option<Box<Node>>,
-    right: Option<Box<Node>>,
+use anyhow::{ensure, Context, Result};
+
+use crate::stream::{Decoder, Encoder};
+
+pub use crate::format::{Algorithm, FormatError};
+
+pub mod bits;
+pub mod block;
+pub mod deflate;
+pub mod format;
+pub mod gzip;
+pub mod huffman;
+pub mod lz77;
+pub mod lzh;
+pub mod stream;
+pub mod zlib;
+
+/// The kind of file to write or read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Container {
+    /// comprs's own format, described in [`format`]
+    #[default]
+    Comprs,
+    /// A gzip file, as `gzip` writes
+    Gzip,
+    /// A zlib stream
+    Zlib,
+    /// Raw DEFLATE data, with no header or checksum
+    Deflate,
 }
 
-impl Node {
-    fn new(ch: Option<char>, freq: u32) -> Self {
-        Node {
-            ch,
-            freq,
-            left: None,
-            right: None,
+impl Container {
+    /// The container `bytes` appear to be in, going by their first bytes.
+    /// Only gzip files are told apart from comprs's, which everything else
+    /// is taken to be. A zlib header is only two bytes, which about one in
+    /// 500 other files starts with, and raw DEFLATE data has none, so both
+    /// must be asked for.
+    pub fn detect(bytes: &[u8]) -> Container {
+        if gzip::is_gzip(bytes) {
+            Container::Gzip
+        } else {
+            Container::Comprs
         }
     }
 }
 
-impl Ord for Node {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other
-            .freq
-            .cmp(&self.freq)
-            .then_with(|| other.ch.cmp(&self.ch))
-    }
+/// Settings for encoding. Decoding needs none, as the encoded data describes
+/// itself.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub container: Container,
+    /// How [`Container::Comprs`] compresses. The other containers always use
+    /// DEFLATE.
+    pub algorithm: Algorithm,
+    /// How hard [`Algorithm::Lzh`] and DEFLATE look for matches, from 1
+    /// (fastest) to [`lz77::MAX_LEVEL`] (smallest output). DEFLATE also
+    /// takes 0, which stores the data uncompressed.
+    pub level: u8,
+    /// [`Algorithm::Lzh`] matches reach back less than `2^window_bits` bytes.
+    pub window_bits: u8,
+    /// Longest code the Huffman tables may use, at least 8 so that all byte
+    /// values fit, or 9 with [`Algorithm::Lzh`] for its length codes.
+    pub max_code_len: u8,
+    /// Bytes of input compressed together with one Huffman code, from 1 to
+    /// [`format::MAX_BLOCK_SIZE`]. Smaller blocks follow changes in the data
+    /// more closely but each carries its own code lengths.
+    pub block_size: usize,
+    /// Number of threads compressing blocks. Does not change the output.
+    pub jobs: usize,
 }
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            container: Container::default(),
+            algorithm: Algorithm::default(),
+            level: lz77::DEFAULT_LEVEL,
+            window_bits: lz77::DEFAULT_WINDOW_BITS,
+            max_code_len: huffman::DEFAULT_MAX_CODE_LEN,
+            block_size: stream::DEFAULT_BLOCK_SIZE,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
     }
 }
 
-impl From<&mut BinaryHeap<Node>> for Node {
-    /// Construct a huffman tree from a priority queue
-    fn from(value: &mut BinaryHeap<Node>) -> Self {
-        while value.len() > 1 {
-            let a = value.pop().unwrap();
-            let b = value.pop().unwrap();
-
-            let mut c = Node::new(None, a.freq + b.freq);
-
-            c.left = Some(Box::new(a));
-            c.right = Some(Box::new(b));
-
-            value.push(c);
+impl Options {
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            (8..=huffman::MAX_CODE_LEN).contains(&self.max_code_len),
+            "maximum code length must be between 8 and {}",
+            huffman::MAX_CODE_LEN
+        );
+        ensure!(
+            (1..=format::MAX_BLOCK_SIZE).contains(&self.block_size),
+            "block size must be between 1 and {} bytes",
+            format::MAX_BLOCK_SIZE
+        );
+        ensure!(self.jobs > 0, "at least one job is needed");
+        if self.container != Container::Comprs {
+            ensure!(
+                self.level <= lz77::MAX_LEVEL,
+                "level must be between 0 and {}",
+                lz77::MAX_LEVEL
+            );
+        } else if self.algorithm == Algorithm::Lzh {
+            ensure!(
+                self.max_code_len >= 9,
+                "maximum code length must be at least 9 for lzh"
+            );
+            ensure!(
+                (1..=lz77::MAX_LEVEL).contains(&self.level),
+                "level must be between 1 and {}",
+                lz77::MAX_LEVEL
+            );
+            ensure!(
+                (lz77::MIN_WINDOW_BITS..=lz77::MAX_WINDOW_BITS).contains(&self.window_bits),
+                "window must be between {} and {} bits",
+                lz77::MIN_WINDOW_BITS,
+                lz77::MAX_WINDOW_BITS
+            );
         }
-
-        value.pop().unwrap()
+        Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct HuffmanTable(HashMap<char, String>);
-
-impl HuffmanTable {
-    fn new() -> Self {
-        HuffmanTable(HashMap::new())
-    }
-
-    fn insert(&mut self, ch: char, code: String) {
-        self.0.insert(ch, code);
-    }
+/// Whether `path` stands for standard input or output.
+fn is_std(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
 
-    fn get(&self, ch: &char) -> Option<&String> {
-        self.0.get(ch)
+fn open_source(source: &Path) -> Result<Box<dyn Read>> {
+    if is_std(source) {
+        return Ok(Box::new(io::stdin().lock()));
     }
+    let file = File::open(source).context(format!("failed to open file `{:?}`", source))?;
+    Ok(Box::new(file))
+}
 
-    fn invert(&self) -> HashMap<&String, &char> {
-        self.0.iter().map(|(k, v)| (v, k)).collect()
+fn create_destination(destination: &Path) -> Result<Box<dyn Write>> {
+    if is_std(destination) {
+        return Ok(Box::new(io::stdout().lock()));
     }
+    let file =
+        File::create(destination).context(format!("could not create file `{:?}`", destination))?;
+    Ok(Box::new(file))
 }
 
-impl From<&String> for HuffmanTable {
-    // Constructs a mapping of characters to their huffman encoding
-    fn from(value: &String) -> Self {
-        fn traverse(table: &mut HuffmanTable, node: &Node, code: String) {
-            if let Some(ch) = node.ch {
-                table.insert(ch, code);
-            } else {
-                if let Some(left) = &node.left {
-                    traverse(table, left, code.clone() + "0");
-                }
-                if let Some(right) = &node.right {
-                    traverse(table, right, code.clone() + "1");
-                }
-            }
+/// Compress the file `source` into `destination`. Either may be `-` for
+/// standard input or output. The data is compressed as it is read, so the
+/// memory needed does not depend on its size.
+pub fn encode(source: &PathBuf, destination: &PathBuf, options: &Options) -> Result<()> {
+    let mut input = open_source(source)?;
+    let output = create_destination(destination)?;
+    let context = || format!("could not compress `{:?}` into `{:?}`", source, destination);
+    if options.container != Container::Comprs {
+        options.validate()?;
+        let level = options.level;
+        return match options.container {
+            Container::Gzip => gzip::Encoder::new(output, level)
+                .and_then(|encoder| compress_into(&mut input, encoder, gzip::Encoder::finish)),
+            Container::Zlib => zlib::Encoder::new(output, level)
+                .and_then(|encoder| compress_into(&mut input, encoder, zlib::Encoder::finish)),
+            _ => compress_into(
+                &mut input,
+                deflate::Encoder::new(output, level),
+                deflate::Encoder::finish,
+            ),
         }
+        .with_context(context);
+    }
 
-        let counts = value.chars().fold(HashMap::new(), |mut counts, c| {
-            *counts.entry(c).or_insert(0) += 1;
-            counts
-        });
-
-        let mut heap: BinaryHeap<_> = counts
-            .into_iter()
-            .map(|(c, count)| Node::new(Some(c), count))
-            .collect();
+    let mut encoder = Encoder::new(output, options)?;
+    io::copy(&mut input, &mut encoder).with_context(context)?;
+    encoder.finish().context(format!(
+        "could not write contents to file `{:?}`",
+        destination
+    ))?;
 
-        let root = Node::from(&mut heap);
+    Ok(())
+}
 
-        let mut table = HuffmanTable::new();
-        let code = String::from("");
+/// Copy all of `input` into `encoder`, then end its output with `finish`.
+fn compress_into<E: Write, W: Write>(
+    input: &mut impl Read,
+    mut encoder: E,
+    finish: impl FnOnce(E) -> io::Result<W>,
+) -> io::Result<()> {
+    io::copy(input, &mut encoder)?;
+    finish(encoder)?.flush()
+}
 
-        traverse(&mut table, &root, code);
-        table
+/// Compress `contents`, which may hold any bytes at all, into
+/// `options.container`.
+pub fn encode_bytes(contents: &[u8], options: &Options) -> Result<Vec<u8>> {
+    let level = options.level;
+    match options.container {
+        Container::Comprs => {
+            let mut encoder = Encoder::new(Vec::with_capacity(contents.len() / 2), options)?;
+            encoder.write_all(contents)?;
+            encoder.finish()
+        }
+        container => {
+            options.validate()?;
+            Ok(match container {
+                Container::Gzip => gzip::compress(contents, level),
+                Container::Zlib => zlib::compress(contents, level),
+                _ => deflate::compress(contents, level),
+            })
+        }
     }
 }
 
-pub fn encode(source: &PathBuf, destination: &PathBuf) -> Result<()> {
-    let contents =
-        std::fs::read_to_string(source).context(format!("failed to open file `{:?}`", source))?;
-    let table = HuffmanTable::from(&contents);
-
-    let encoded = to_bitstring(&contents, &table)?;
-    let (compressed, bits_filled) = compress(&encoded);
-    let encoded_tbl = bincode::serialize(&table).context("failed to serialize table")?;
-
-    write_encoded(destination, encoded_tbl, compressed, bits_filled)?;
-
-    Ok(())
-}
+/// Decompress the file `source` into `destination`. Either may be `-` for
+/// standard input or output. `container` is detected from the first bytes
+/// of `source` if not given, which raw DEFLATE data always needs.
+///
+/// The output is written as it is decoded. gzip, zlib and DEFLATE input is
+/// read whole first, but the comprs format is read a block at a time.
+pub fn decode(source: &PathBuf, destination: &PathBuf, container: Option<Container>) -> Result<()> {
+    let mut input = open_source(source)?;
+    let mut output = create_destination(destination)?;
+    let mut head = Vec::with_capacity(format::MAGIC.len());
+    input
+        .by_ref()
+        .take(format::MAGIC.len() as u64)
+        .read_to_end(&mut head)
+        .context(format!("could not read file `{:?}`", source))?;
+    let container = container.unwrap_or_else(|| Container::detect(&head));
+    let mut input = io::Cursor::new(head).chain(input);
+
+    if container != Container::Comprs {
+        let mut encoded = Vec::new();
+        input
+            .read_to_end(&mut encoded)
+            .context(format!("could not read file `{:?}`", source))?;
+        decode_into(&encoded, container, &mut output)
+            .and_then(|()| Ok(output.flush()?))
+            .context(format!(
+                "could not decode `{:?}` into `{:?}`",
+                source, destination
+            ))?;
+        return Ok(());
+    }
 
-fn write_encoded(
-    destination: &PathBuf,
-    encoded_tbl: Vec<u8>,
-    encoded: Vec<u8>,
-    bits_filled: u8,
-) -> Result<(), anyhow::Error> {
-    let mut file =
-        File::create(destination).context(format!("failed to create file `{:?}`", destination))?;
-
-    file.write_all(&(encoded_tbl.len() as u64).to_le_bytes())?;
-    file.write_all(&encoded_tbl).context(format!(
+    let mut decoder = Decoder::new(input).context(format!("could not decode `{:?}`", source))?;
+    while let Some(block) = decoder
+        .read_block()
+        .context(format!("could not decode `{:?}`", source))?
+    {
+        output.write_all(block).context(format!(
+            "could not write contents to file `{:?}`",
+            destination
+        ))?;
+    }
+    output.flush().context(format!(
         "could not write contents to file `{:?}`",
         destination
     ))?;
-    file.write_all(&encoded)
-        .context("could not write encoded contents to file")?;
-    file.write_all(&[bits_filled])
-        .context("could not write number of bits filled")?;
 
     Ok(())
 }
 
-pub fn to_bitstring(contents: &str, table: &HuffmanTable) -> Result<String> {
-    Ok(contents.chars().fold(String::new(), |mut acc, c| {
-        acc.push_str(
-            table
-                .get(&c)
-                .context(format!("no encoding for {}", c))
-                .unwrap(),
-        );
-        acc
-    }))
+/// Decompress the output of [`encode_bytes`] in the comprs format, or any
+/// gzip file, going by [`Container::detect`]. Failures that callers may want to
+/// tell apart, such as input that was not compressed at all, are
+/// [`FormatError`]s.
+pub fn decode_bytes(encoded: &[u8]) -> Result<Vec<u8>> {
+    decode_container(encoded, Container::detect(encoded))
 }
 
-fn compress(encoded: &str) -> (Vec<u8>, u8) {
-    let mut bytes: Vec<u8> = Vec::new();
-    let mut current_byte = 0u8;
-    let mut bits_filled = 0;
+/// Decompress `encoded`, which is in `container`.
+pub fn decode_container(encoded: &[u8], container: Container) -> Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    decode_into(encoded, container, &mut contents)?;
+    Ok(contents)
+}
 
-    for bit in encoded.chars() {
-        if bit == '1' {
-            current_byte |= 1 << (7 - bits_filled);
+/// Decompress `encoded`, which is in `container`, writing the data to `out`
+/// as it goes.
+pub fn decode_into(encoded: &[u8], container: Container, out: &mut impl Write) -> Result<()> {
+    match container {
+        Container::Comprs => {
+            let mut decoder = Decoder::new(encoded)?;
+            while let Some(block) = decoder.read_block()? {
+                out.write_all(block)?;
+            }
+            Ok(())
         }
-
-        bits_filled += 1;
-
-        if bits_filled == 8 {
-            bytes.push(current_byte);
-            current_byte = 0;
-            bits_filled = 0;
+        Container::Gzip => gzip::decompress_into(encoded, out),
+        Container::Zlib => zlib::decompress_into(encoded, out),
+        Container::Deflate => {
+            let used = deflate::decompress(encoded, out)?;
+            ensure!(
+                used == encoded.len(),
+                "unexpected data after the end of the compressed data"
+            );
+            Ok(())
         }
     }
+}
 
-    if bits_filled > 0 {
-        bytes.push(current_byte);
-    } else {
-        bits_filled = 8;
-    }
+#[cfg(test)]
+mod tests {
+    use std::fs;
 
-    (bytes, bits_filled as u8)
-}
+    use super::*;
 
-pub fn decode(source: &PathBuf, destination: &PathBuf) -> Result<()> {
-    let (table, contents, bits_filled) = read_encoded(source)?;
-    let table = table.invert();
-    let bit_string = bitstring_from_bytes(&contents, bits_filled);
-    let contents = bitstring_to_text(&bit_string, &table)?;
+    /// Deterministic pseudo-random bytes (xorshift64).
+    fn random_bytes(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                (seed >> 24) as u8
+            })
+            .collect()
+    }
 
-    let mut file =
-        File::create(destination).context(format!("failed to create file `{:?}`", destination))?;
-    file.write_all(contents.as_bytes()).context(format!(
-        "could not write contents to file `{:?}`",
-        destination
-    ))?;
+    fn assert_round_trip(contents: &[u8]) {
+        for algorithm in [Algorithm::Huffman, Algorithm::Lzh] {
+            let options = Options {
+                algorithm,
+                ..Options::default()
+            };
+            let encoded = encode_bytes(contents, &options).unwrap();
+            assert_eq!(decode_bytes(&encoded).unwrap(), contents, "{:?}", algorithm);
+        }
+    }
 
-    Ok(())
-}
+    #[test]
+    fn test_round_trip_random_binary() {
+        for (len, seed) in [(1, 1), (255, 2), (4096, 3), (100_000, 4)] {
+            assert_round_trip(&random_bytes(len, seed));
+        }
+    }
 
-fn read_encoded(source: &PathBuf) -> Result<(HuffmanTable, Vec<u8>, u8), anyhow::Error> {
-    let mut file = File::open(source).context(format!("could not open file `{:?}`", source))?;
+    #[test]
+    fn test_round_trip_skewed_binary() {
+        // mostly zeros with every other byte value sprinkled in, like an
+        // executable
+        let mut contents = vec![0u8; 20_000];
+        for (i, byte) in random_bytes(2_000, 5).into_iter().enumerate() {
+            contents[i * 10] = byte;
+        }
+        contents.extend(0..=255);
+        assert_round_trip(&contents);
+    }
 
-    let mut buffer = [0u8; size_of::<u64>()];
-    file.read_exact(&mut buffer)
-        .context("could not read length of encoded huffman tree")?;
+    #[test]
+    fn test_round_trip_invalid_utf8() {
+        let latin1 = b"caf\xe9 \xff\xfe\xc3\x28 \x80 na\xefve".to_vec();
+        assert!(String::from_utf8(latin1.clone()).is_err());
+        assert_round_trip(&latin1);
+        assert_round_trip("h\u{e9}llo w\u{f6}rld \u{1f600}".as_bytes());
+    }
 
-    let tree_len = u64::from_le_bytes(buffer) as usize;
-    let mut tree = vec![0u8; tree_len];
-    file.read_exact(&mut tree)
-        .context("could not read huffman tree")?;
-    let table: HuffmanTable =
-        bincode::deserialize(&tree[..]).context("could not deserialize huffman tree")?;
+    #[test]
+    fn test_round_trip_degenerate() {
+        assert_round_trip(b"");
+        assert_round_trip(b"a");
+        assert_round_trip(&[0xff; 1000]);
+    }
 
-    let mut contents = Vec::new();
-    file.read_to_end(&mut contents)
-        .context("could not read encoded contents from file")?;
+    #[test]
+    fn test_output_is_deterministic() {
+        let contents = random_bytes(5_000, 7);
+        let first = encode_bytes(&contents, &Options::default()).unwrap();
+        for _ in 0..5 {
+            assert_eq!(encode_bytes(&contents, &Options::default()).unwrap(), first);
+        }
+    }
 
-    let valid_bits = contents.pop().unwrap();
+    /// Bytes of a single block file that are not payload.
+    const OVERHEAD: usize = format::HEADER_LEN + format::BLOCK_HEADER_LEN + 4 + format::TRAILER_LEN;
 
-    Ok((table, contents, valid_bits))
-}
+    #[test]
+    fn test_small_input_has_small_header() {
+        let contents = b"hello, world";
+        let encoded = encode_bytes(contents, &Options::default()).unwrap();
+        assert!(encoded.len() < OVERHEAD + 40, "{} bytes", encoded.len());
+    }
 
-fn bitstring_from_bytes(bytes: &[u8], valid_bits: u8) -> String {
-    let mut bit_string = String::new();
+    #[test]
+    fn test_round_trip_fibonacci_distribution() {
+        // byte i occurs fib(i) times, which gives a Huffman tree 24 deep
+        let (mut a, mut b) = (1, 1);
+        let mut contents = Vec::new();
+        for byte in 0..25 {
+            contents.extend(std::iter::repeat_n(byte, a));
+            (a, b) = (b, a + b);
+        }
+        let counts = huffman::byte_counts(&contents);
+        assert_eq!(
+            huffman::code_lengths(&counts, huffman::MAX_CODE_LEN)
+                .iter()
+                .max(),
+            Some(&24)
+        );
 
-    for (i, &byte) in bytes.iter().enumerate() {
-        let bits_to_read = if i == bytes.len() - 1 { valid_bits } else { 8 };
-        for j in (8 - bits_to_read..8).rev() {
-            let bit = (byte >> j) & 1;
-            bit_string.push(if bit == 1 { '1' } else { '0' });
+        for max_code_len in [8, 12, 15, huffman::MAX_CODE_LEN] {
+            let options = Options {
+                max_code_len,
+                ..Options::default()
+            };
+            let encoded = encode_bytes(&contents, &options).unwrap();
+            let lengths = huffman::read_lengths(
+                &mut &encoded[format::HEADER_LEN + format::BLOCK_HEADER_LEN..],
+                256,
+            )
+            .unwrap();
+            assert_eq!(lengths.iter().max(), Some(&max_code_len));
+            assert_eq!(decode_bytes(&encoded).unwrap(), contents);
+        }
+        for max_code_len in [7, huffman::MAX_CODE_LEN + 1] {
+            let options = Options {
+                max_code_len,
+                ..Options::default()
+            };
+            assert!(encode_bytes(&contents, &options).is_err());
         }
     }
 
-    bit_string
-}
+    #[test]
+    fn test_block_sizes() {
+        let contents = random_bytes(5_000, 10);
+        for block_size in [1, 1000, 4096, format::MAX_BLOCK_SIZE] {
+            let options = Options {
+                block_size,
+                ..Options::default()
+            };
+            let encoded = encode_bytes(&contents, &options).unwrap();
+            assert_eq!(decode_bytes(&encoded).unwrap(), contents);
+        }
 
-pub fn bitstring_to_text(contents: &str, table: &HashMap<&String, &char>) -> Result<String> {
-    let mut out = String::new();
+        for block_size in [0, format::MAX_BLOCK_SIZE + 1] {
+            let options = Options {
+                block_size,
+                ..Options::default()
+            };
+            assert!(encode_bytes(b"abc", &options).is_err());
+        }
+    }
 
-    let mut start = 0;
-    let mut end = 1;
+    #[test]
+    fn test_lzh_shrinks_repetitive_text() {
+        let mut log = Vec::new();
+        for i in 0..2000 {
+            let line = format!(
+                "2024-06-01 12:00:{:02} INFO request path=/api/items/{} status=200\n",
+                i % 60,
+                i % 37
+            );
+            log.extend_from_slice(line.as_bytes());
+        }
+        let huffman = encode_bytes(&log, &Options::default()).unwrap();
+        let sizes: Vec<usize> = [1, 6, 9]
+            .into_iter()
+            .map(|level| {
+                let options = Options {
+                    algorithm: Algorithm::Lzh,
+                    level,
+                    ..Options::default()
+                };
+                let encoded = encode_bytes(&log, &options).unwrap();
+                assert_eq!(decode_bytes(&encoded).unwrap(), log);
+                encoded.len()
+            })
+            .collect();
+        assert!(
+            sizes[0] * 4 < huffman.len(),
+            "{:?} {}",
+            sizes,
+            huffman.len()
+        );
+        assert!(sizes[2] <= sizes[1] && sizes[1] <= sizes[0], "{:?}", sizes);
+    }
 
-    while end <= contents.len() {
-        if let Some(ch) = table.get(&contents[start..end].to_string()) {
-            out.push(**ch);
-            start = end;
+    #[test]
+    fn test_lzh_options() {
+        let lzh = Options {
+            algorithm: Algorithm::Lzh,
+            ..Options::default()
+        };
+        for options in [
+            Options {
+                level: 0,
+                ..lzh.clone()
+            },
+            Options {
+                level: 10,
+                ..lzh.clone()
+            },
+            Options {
+                window_bits: 9,
+                ..lzh.clone()
+            },
+            Options {
+                window_bits: 21,
+                ..lzh.clone()
+            },
+            Options {
+                max_code_len: 8,
+                ..lzh.clone()
+            },
+        ] {
+            assert!(encode_bytes(b"abc", &options).is_err(), "{:?}", options);
         }
 
-        end += 1;
+        let contents = random_bytes(3000, 11).repeat(3);
+        for window_bits in [10, 20] {
+            let options = Options {
+                window_bits,
+                ..lzh.clone()
+            };
+            let encoded = encode_bytes(&contents, &options).unwrap();
+            assert_eq!(decode_bytes(&encoded).unwrap(), contents);
+        }
     }
 
-    Ok(out)
-}
+    #[test]
+    fn test_containers() {
+        let contents = random_bytes(2000, 12).repeat(2);
+        for container in [
+            Container::Comprs,
+            Container::Gzip,
+            Container::Zlib,
+            Container::Deflate,
+        ] {
+            for level in [0, 6] {
+                let options = Options {
+                    container,
+                    level,
+                    ..Options::default()
+                };
+                let encoded = encode_bytes(&contents, &options).unwrap();
+                assert_eq!(
+                    decode_container(&encoded, container).unwrap(),
+                    contents,
+                    "{:?}",
+                    container
+                );
+                if matches!(container, Container::Comprs | Container::Gzip) {
+                    assert_eq!(Container::detect(&encoded), container);
+                    assert_eq!(decode_bytes(&encoded).unwrap(), contents);
+                }
+            }
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let gzip = Options {
+            container: Container::Gzip,
+            level: 10,
+            ..Options::default()
+        };
+        assert!(encode_bytes(b"abc", &gzip).is_err());
+        let log = include_bytes!("../fixtures/log.txt");
+        let gz = include_bytes!("../fixtures/log.txt.gz");
+        assert_eq!(decode_bytes(gz).unwrap(), log);
+    }
 
-    #[test]
-    fn test_encoding_table() {
-        // Arrange
-        let counts = HashMap::from([
-            ('C', 32),
-            ('D', 42),
-            ('E', 120),
-            ('K', 7),
-            ('L', 42),
-            ('M', 24),
-            ('U', 37),
-            ('Z', 2),
-        ]);
-        let contents = counts
-            .iter()
-            .map(|(k, v)| k.to_string().repeat(*v as usize))
-            .collect::<String>();
-
-        // Act
-        let table = HuffmanTable::from(&contents);
-
-        // Assert
-        assert_eq!(table.get(&'C').unwrap(), "1110");
-        assert_eq!(table.get(&'D').unwrap(), "101");
-        assert_eq!(table.get(&'E').unwrap(), "0");
-        assert_eq!(table.get(&'K').unwrap(), "111101");
-        assert_eq!(table.get(&'L').unwrap(), "110");
-        assert_eq!(table.get(&'M').unwrap(), "11111");
-        assert_eq!(table.get(&'U').unwrap(), "100");
-        assert_eq!(table.get(&'Z').unwrap(), "111100");
+    fn format_error(encoded: &[u8]) -> FormatError {
+        decode_bytes(encoded)
+            .unwrap_err()
+            .downcast()
+            .expect("a format error")
     }
 
     #[test]
-    fn test_byte_from_str() {
-        let test_cases = vec![
-            (String::from("10010110"), vec![0b10010110], 8),
-            (String::from("10010110010"), vec![0b10010110, 0b01000000], 3),
-            (
-                String::from("1001011010010110"),
-                vec![0b10010110, 0b10010110],
-                8,
-            ),
-        ];
+    fn test_decode_rejects_other_formats() {
+        assert_eq!(
+            format_error(&random_bytes(1000, 8)),
+            FormatError::WrongFormat
+        );
+        assert_eq!(format_error(b"\x1f\x8b\x08\x00"), FormatError::Truncated);
+
+        let mut encoded = encode_bytes(b"hello", &Options::default()).unwrap();
+        encoded[4] = 9;
+        assert_eq!(format_error(&encoded), FormatError::UnsupportedVersion(9));
+
+        // files that happen to start like a zlib stream are not taken for one
+        let mut zlib_like = random_bytes(1000, 13);
+        zlib_like[..2].copy_from_slice(&[0x08, 0x1d]);
+        assert!(zlib::is_zlib(&zlib_like));
+        assert_eq!(format_error(&zlib_like), FormatError::WrongFormat);
+        let zlib = Options {
+            container: Container::Zlib,
+            ..Options::default()
+        };
+        let encoded = encode_bytes(b"hello", &zlib).unwrap();
+        assert_eq!(Container::detect(&encoded), Container::Comprs);
+        assert_eq!(format_error(&encoded), FormatError::WrongFormat);
+        assert_eq!(
+            decode_container(&encoded, Container::Zlib).unwrap(),
+            b"hello"
+        );
+    }
 
-        for (input, expected_vec, expected_bits) in test_cases {
-            let (result, valid_bits) = compress(&input);
-            assert_eq!(result, expected_vec);
-            assert_eq!(valid_bits, expected_bits);
+    #[test]
+    fn test_decode_rejects_truncated() {
+        let random = random_bytes(300, 9);
+        for contents in [&b""[..], b"a", b"hello, hello world", &random] {
+            for algorithm in [Algorithm::Huffman, Algorithm::Lzh] {
+                let options = Options {
+                    algorithm,
+                    ..Options::default()
+                };
+                let encoded = encode_bytes(contents, &options).unwrap();
+                for len in 0..encoded.len() {
+                    assert_eq!(
+                        format_error(&encoded[..len]),
+                        FormatError::Truncated,
+                        "{:?} with {:?} cut to {} bytes",
+                        contents,
+                        algorithm,
+                        len
+                    );
+                }
+            }
         }
     }
 
     #[test]
-    fn test_decompress() {
-        let test_cases = vec![
-            (vec![0b10010110, 0b01000000], 3, "10010110010"),
-            (vec![0b10010110], 8, "10010110"),
-        ];
-
-        for (input, valid_bits, expected) in test_cases {
-            let result = bitstring_from_bytes(&input, valid_bits);
-            assert_eq!(&result, expected);
+    fn test_decode_rejects_overlong_codes() {
+        // code lengths 1 to 55 and 55 again form a complete code, whose
+        // decoding tables would need 2^45 entries; then 200 unused byte
+        // values and a single byte of data
+        let mut payload: Vec<u8> = (1..=55).collect();
+        payload.extend_from_slice(&[55, 0xff, 0xc7, 0]);
+        let mut encoded = Vec::new();
+        format::Header::new(Algorithm::Huffman).write(&mut encoded);
+        format::BlockHeader {
+            size: 1,
+            payload: payload.len() as u32,
         }
+        .write(&mut encoded);
+        encoded.extend_from_slice(&payload);
+        encoded.extend_from_slice(&[0; 4]);
+        encoded.extend_from_slice(&format::Trailer::for_contents(&[0]).to_bytes());
+        assert_eq!(encoded.len(), 89);
+
+        let error = decode_bytes(&encoded).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("invalid code length"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_corruption() {
+        // "a" and "b" get the codes 0 and 1, so flipping the second bit of
+        // the last byte of the payload turns "ab" into "aa"
+        let encoded = encode_bytes(b"ab", &Options::default()).unwrap();
+        let last_payload = encoded.len() - 4 - format::TRAILER_LEN - 1;
+        let mut flipped = encoded.clone();
+        flipped[last_payload] ^= 0b10;
+        assert_eq!(
+            format_error(&flipped),
+            FormatError::ChecksumMismatch {
+                expected: crc32fast::hash(b"ab"),
+                actual: crc32fast::hash(b"aa"),
+            }
+        );
+
+        let mut wrong_crc = encoded.clone();
+        let last = wrong_crc.len() - 1;
+        wrong_crc[last] ^= 1;
+        assert!(matches!(
+            format_error(&wrong_crc),
+            FormatError::ChecksumMismatch { .. }
+        ));
+
+        let mut wrong_size = encoded.clone();
+        let size = wrong_size.len() - format::TRAILER_LEN;
+        wrong_size[size] ^= 1;
+        assert_eq!(
+            format_error(&wrong_size),
+            FormatError::SizeMismatch {
+                expected: 3,
+                actual: 2,
+            }
+        );
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(decode_bytes(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_file() {
+        let dir = std::env::temp_dir().join(format!("comprs-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.bin");
+        let encoded = dir.join("source.bin.huf");
+        let decoded = dir.join("decoded.bin");
+
+        let contents = random_bytes(10_000, 6);
+        fs::write(&source, &contents).unwrap();
+        encode(&source, &encoded, &Options::default()).unwrap();
+        decode(&encoded, &decoded, None).unwrap();
+        let result = fs::read(&decoded).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result, contents);
+    }
+
+    #[test]
+    fn test_round_trip_gzip_file() {
+        let dir = std::env::temp_dir().join(format!("comprs-test-gzip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("log.txt");
+        let encoded = dir.join("log.txt.gz");
+        let decoded = dir.join("decoded.txt");
+
+        let contents = include_bytes!("../fixtures/log.txt");
+        fs::write(&source, contents).unwrap();
+        let options = Options {
+            container: Container::Gzip,
+            ..Options::default()
+        };
+        encode(&source, &encoded, &options).unwrap();
+        decode(&encoded, &decoded, None).unwrap();
+        let result = fs::read(&decoded).unwrap();
+        let gz = fs::read(&encoded).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result, contents);
+        assert_eq!(gzip::decompress(&gz).unwrap(), contents);
     }
 }
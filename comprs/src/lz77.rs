@@ -0,0 +1,474 @@
+//! Finding repeated strings, as in LZ77.
+//!
+//! Data is turned into [`Token`]s: literal bytes, and matches that repeat
+//! `length` bytes from `distance` bytes back. Earlier positions are found
+//! through hash chains: the last position at which each hash of three bytes
+//! occurred, and for every position the one before it with the same hash.
+//! The compression level sets how far along a chain to look and whether to
+//! try the next byte before taking a match, much as in zlib.
+//!
+//! Lengths and distances are coded in two parts as in DEFLATE: a code, which
+//! is Huffman coded, and extra bits read as they are. Distance codes continue
+//! the DEFLATE pattern past 32 KiB to cover windows of up to
+//! 2^[`MAX_WINDOW_BITS`] bytes.
+
+pub const MIN_MATCH: usize = 3;
+pub const MAX_MATCH: usize = 258;
+
+pub const MIN_WINDOW_BITS: u8 = 10;
+pub const MAX_WINDOW_BITS: u8 = 20;
+pub const DEFAULT_WINDOW_BITS: u8 = 15;
+
+pub const MAX_LEVEL: u8 = 9;
+pub const DEFAULT_LEVEL: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u32 },
+}
+
+/// How hard a level looks for matches.
+struct Config {
+    /// Most earlier positions to try for each match.
+    chain: usize,
+    /// Length of a match good enough to stop looking for a longer one.
+    nice: usize,
+    /// Matches shorter than this are put off by a byte, in case the next
+    /// position has a longer one. 0 takes every match at once.
+    lazy: usize,
+}
+
+impl Config {
+    const fn new(chain: usize, nice: usize, lazy: usize) -> Self {
+        Config { chain, nice, lazy }
+    }
+}
+
+/// The [`Config`] of each level from 1 to [`MAX_LEVEL`].
+const CONFIGS: [Config; MAX_LEVEL as usize] = [
+    Config::new(4, 8, 0),
+    Config::new(8, 16, 0),
+    Config::new(32, 32, 0),
+    Config::new(16, 16, 4),
+    Config::new(32, 32, 16),
+    Config::new(128, 128, 16),
+    Config::new(256, 128, 32),
+    Config::new(1024, MAX_MATCH, 128),
+    Config::new(4096, MAX_MATCH, MAX_MATCH),
+];
+
+const HASH_BITS: u32 = 15;
+/// Marks the end of a hash chain.
+const NONE: u32 = u32::MAX;
+
+/// The hash chains over some data.
+struct Chains {
+    /// The last position with each hash.
+    head: Vec<u32>,
+    /// For each position in the window, the one before it with the same
+    /// hash, indexed by position modulo the window size.
+    prev: Vec<u32>,
+    mask: usize,
+}
+
+impl Chains {
+    fn new(window_bits: u8) -> Self {
+        Chains {
+            head: vec![NONE; 1 << HASH_BITS],
+            prev: vec![NONE; 1 << window_bits],
+            mask: (1 << window_bits) - 1,
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> usize {
+        let key = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0]);
+        (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    /// Add `pos` to the chain of its hash.
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH > data.len() {
+            return;
+        }
+        let hash = Self::hash(data, pos);
+        self.prev[pos & self.mask] = self.head[hash];
+        self.head[hash] = pos as u32;
+    }
+
+    /// The longest match for `pos` that is longer than `longer_than`, as
+    /// `(length, distance)`, or a length of 0 if there is none. `pos` must
+    /// not be inserted yet.
+    fn longest_match(
+        &self,
+        data: &[u8],
+        pos: usize,
+        longer_than: usize,
+        config: &Config,
+    ) -> (usize, usize) {
+        let max_len = MAX_MATCH.min(data.len() - pos);
+        if max_len < MIN_MATCH || longer_than >= max_len {
+            return (0, 0);
+        }
+        let mut best = (longer_than.max(MIN_MATCH - 1), 0);
+        let mut candidate = self.head[Self::hash(data, pos)];
+        for _ in 0..config.chain {
+            let start = candidate as usize;
+            // further back, the chain runs into positions overwritten since
+            if candidate == NONE || pos - start > self.mask {
+                break;
+            }
+            // a longer match must at least agree with the best one at its end
+            if data[start + best.0] == data[pos + best.0] {
+                let len = data[start..start + max_len]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, pos - start);
+                    if len >= config.nice || len == max_len {
+                        break;
+                    }
+                }
+            }
+            let next = self.prev[start & self.mask];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        if best.1 == 0 {
+            (0, 0)
+        } else {
+            best
+        }
+    }
+}
+
+/// Append the tokens of `data` to `tokens`, with matches reaching back less
+/// than `2^window_bits` bytes, searching as hard as `level` says.
+pub fn tokenize(data: &[u8], level: u8, window_bits: u8, tokens: &mut Vec<Token>) {
+    tokenize_from(data, 0, level, window_bits, tokens);
+}
+
+/// Like [`tokenize`], but only for the data from `start` on. Matches may
+/// also reach back into the data before `start`, which was tokenized
+/// earlier.
+pub fn tokenize_from(
+    data: &[u8],
+    start: usize,
+    level: u8,
+    window_bits: u8,
+    tokens: &mut Vec<Token>,
+) {
+    debug_assert!((1..=MAX_LEVEL).contains(&level), "no level {}", level);
+    debug_assert!((MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&window_bits));
+    let config = &CONFIGS[level as usize - 1];
+    let mut chains = Chains::new(window_bits);
+    for pos in start.saturating_sub(1 << window_bits)..start {
+        chains.insert(data, pos);
+    }
+
+    // a match found at the previous position and not yet taken
+    let mut previous: Option<(usize, usize)> = None;
+    let mut pos = start;
+    while pos < data.len() {
+        let found = match previous {
+            Some((len, _)) if len >= config.lazy => (0, 0),
+            _ => chains.longest_match(data, pos, previous.map_or(0, |(len, _)| len), config),
+        };
+        chains.insert(data, pos);
+        match previous {
+            Some((length, distance)) if found.0 <= length => {
+                tokens.push(Token::Match {
+                    length: length as u16,
+                    distance: distance as u32,
+                });
+                // the match started at `pos - 1`, which is inserted, as is `pos`
+                let end = pos - 1 + length;
+                for p in pos + 1..end {
+                    chains.insert(data, p);
+                }
+                pos = end;
+                previous = None;
+            }
+            _ => {
+                if previous.take().is_some() {
+                    tokens.push(Token::Literal(data[pos - 1]));
+                }
+                if found.0 >= MIN_MATCH {
+                    previous = Some(found);
+                } else {
+                    tokens.push(Token::Literal(data[pos]));
+                }
+                pos += 1;
+            }
+        }
+    }
+    debug_assert!(previous.is_none(), "a match runs past the end");
+}
+
+/// Append the `length` bytes that start `distance` bytes before the end of
+/// `out`, which must hold at least that many.
+pub fn copy_match(out: &mut Vec<u8>, distance: usize, length: usize) {
+    let from = out.len() - distance;
+    if distance >= length {
+        out.extend_from_within(from..from + length);
+    } else {
+        // the match repeats bytes it is itself writing
+        for i in from..from + length {
+            out.push(out[i]);
+        }
+    }
+}
+
+/// Number of length codes.
+pub const LENGTH_CODES: usize = 29;
+
+/// The shortest length of each length code.
+const LENGTH_BASE: [u16; LENGTH_CODES] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+/// The number of extra bits after each length code.
+const LENGTH_EXTRA: [u8; LENGTH_CODES] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The code for match length `length`, and its extra bits as
+/// `(bits, number of bits)`.
+pub fn length_code(length: u16) -> (usize, u64, u8) {
+    debug_assert!((MIN_MATCH..=MAX_MATCH).contains(&(length as usize)));
+    let code = LENGTH_BASE.partition_point(|&base| base <= length) - 1;
+    (
+        code,
+        (length - LENGTH_BASE[code]) as u64,
+        LENGTH_EXTRA[code],
+    )
+}
+
+/// The shortest length of length code `code` and the number of extra bits
+/// that follow it, or `None` if there is no such code.
+pub fn length_base(code: usize) -> Option<(u16, u8)> {
+    Some((*LENGTH_BASE.get(code)?, LENGTH_EXTRA[code]))
+}
+
+/// Number of distance codes, enough for the largest window.
+pub const DISTANCE_CODES: usize = 2 * MAX_WINDOW_BITS as usize;
+
+/// The code for match distance `distance`, and its extra bits as
+/// `(bits, number of bits)`.
+pub fn distance_code(distance: u32) -> (usize, u64, u8) {
+    debug_assert!((1..=1 << MAX_WINDOW_BITS).contains(&distance));
+    let code = match distance - 1 {
+        small @ 0..=3 => small as usize,
+        d => {
+            let top = 31 - d.leading_zeros();
+            (2 * top + ((d >> (top - 1)) & 1)) as usize
+        }
+    };
+    let (base, extra) = distance_base(code).expect("distances have codes");
+    (code, (distance - base) as u64, extra)
+}
+
+/// The shortest distance of distance code `code` and the number of extra
+/// bits that follow it, or `None` if there is no such code.
+pub fn distance_base(code: usize) -> Option<(u32, u8)> {
+    match code {
+        0..=3 => Some((code as u32 + 1, 0)),
+        _ if code < DISTANCE_CODES => {
+            let extra = code as u32 / 2 - 1;
+            Some((((2 | code as u32 & 1) << extra) + 1, extra as u8))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The data `tokens` stand for.
+    fn expand(tokens: &[Token]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &token in tokens {
+            match token {
+                Token::Literal(byte) => out.push(byte),
+                Token::Match { length, distance } => {
+                    copy_match(&mut out, distance as usize, length as usize)
+                }
+            }
+        }
+        out
+    }
+
+    fn tokens(data: &[u8], level: u8, window_bits: u8) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        tokenize(data, level, window_bits, &mut tokens);
+        assert_eq!(expand(&tokens), data, "level {}", level);
+        tokens
+    }
+
+    /// Lines of text with a lot in common, `len` bytes in all.
+    fn log(len: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0u64;
+        while out.len() < len {
+            let line = format!(
+                "12:{:02} GET /items/{} {}\n",
+                i % 60,
+                i.wrapping_mul(2_654_435_761) % 10_000,
+                [200, 404][i as usize % 2]
+            );
+            out.extend_from_slice(line.as_bytes());
+            i += 1;
+        }
+        out.truncate(len);
+        out
+    }
+
+    #[test]
+    fn test_tokenize_from() {
+        let data = b"abcdefgh abcdefgh";
+        let mut tokens = Vec::new();
+        tokenize_from(data, 9, 6, 15, &mut tokens);
+        assert_eq!(
+            tokens,
+            [Token::Match {
+                length: 8,
+                distance: 9
+            }]
+        );
+        // " abcdefgh" alone has nothing to match
+        tokens.clear();
+        tokenize(&data[8..], 6, 15, &mut tokens);
+        assert_eq!(tokens.len(), 9);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        use Token::*;
+        assert_eq!(tokens(b"", 6, 15), []);
+        assert_eq!(tokens(b"ab", 6, 15), [Literal(b'a'), Literal(b'b')]);
+        assert_eq!(
+            tokens(b"abcabcabcx", 6, 15),
+            [
+                Literal(b'a'),
+                Literal(b'b'),
+                Literal(b'c'),
+                Match {
+                    length: 6,
+                    distance: 3
+                },
+                Literal(b'x'),
+            ]
+        );
+        let zeros = tokens(&[0; 1000], 1, 15);
+        assert_eq!(zeros[0], Literal(0));
+        assert_eq!(
+            zeros[1],
+            Match {
+                length: MAX_MATCH as u16,
+                distance: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_lazy_matching() {
+        use Token::*;
+        // at the second "abc" the greedy choice is a match of 3, while
+        // waiting a byte finds "bcdef"
+        let data = b"XabcYbcdefZabcdef";
+        let greedy = tokens(data, 1, 15);
+        assert_eq!(
+            greedy[greedy.len() - 2..],
+            [
+                Match {
+                    length: 3,
+                    distance: 10
+                },
+                Match {
+                    length: 3,
+                    distance: 7
+                }
+            ]
+        );
+        let lazy = tokens(data, 9, 15);
+        assert_eq!(
+            lazy[lazy.len() - 2..],
+            [
+                Literal(b'a'),
+                Match {
+                    length: 5,
+                    distance: 7
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_levels() {
+        let data = log(50_000);
+        let counts: Vec<usize> = (1..=MAX_LEVEL)
+            .map(|level| tokens(&data, level, 15).len())
+            .collect();
+        assert!(counts[8] < counts[0], "{:?}", counts);
+        assert!(counts[0] < data.len() / 3, "{:?}", counts);
+    }
+
+    #[test]
+    fn test_window() {
+        // a repeat further back than the window is not found
+        let mut data = log(3000);
+        let start = data[..100].to_vec();
+        data.extend_from_slice(&start);
+        for window_bits in [10, 11, 12] {
+            let tokens = tokens(&data, 9, window_bits);
+            for token in tokens {
+                if let Token::Match { distance, .. } = token {
+                    assert!(distance < 1 << window_bits);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_length_codes() {
+        assert_eq!(length_code(3), (0, 0, 0));
+        assert_eq!(length_code(10), (7, 0, 0));
+        assert_eq!(length_code(12), (8, 1, 1));
+        assert_eq!(length_code(257), (27, 30, 5));
+        assert_eq!(length_code(258), (28, 0, 0));
+        for length in MIN_MATCH as u16..=MAX_MATCH as u16 {
+            let (code, bits, len) = length_code(length);
+            let (base, extra) = length_base(code).unwrap();
+            assert_eq!(extra, len);
+            assert!(bits < 1 << len);
+            assert_eq!(base + bits as u16, length);
+        }
+        assert_eq!(length_base(LENGTH_CODES), None);
+    }
+
+    #[test]
+    fn test_distance_codes() {
+        // the same as DEFLATE's up to 32 KiB
+        assert_eq!(distance_code(1), (0, 0, 0));
+        assert_eq!(distance_code(4), (3, 0, 0));
+        assert_eq!(distance_code(5), (4, 0, 1));
+        assert_eq!(distance_code(8), (5, 1, 1));
+        assert_eq!(distance_code(24_577), (29, 0, 13));
+        assert_eq!(distance_code(32_768), (29, 8191, 13));
+        assert_eq!(distance_code(1 << MAX_WINDOW_BITS).0, DISTANCE_CODES - 1);
+        for distance in (1..=1 << MAX_WINDOW_BITS).step_by(97).chain([1 << 20]) {
+            let (code, bits, len) = distance_code(distance);
+            let (base, extra) = distance_base(code).unwrap();
+            assert_eq!(extra, len);
+            assert!(bits < 1 << len);
+            assert_eq!(base + bits as u32, distance);
+        }
+        assert_eq!(distance_base(DISTANCE_CODES), None);
+    }
+}
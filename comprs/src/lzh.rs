@@ -0,0 +1,206 @@
+//! The lzh algorithm: LZ77 tokens, Huffman coded.
+//!
+//! A payload starts with two codes, written as code lengths like those of a
+//! Huffman payload: one for the [`LITERAL_LENGTH_SYMBOLS`] literal/length
+//! symbols, where 0 to 255 are literal bytes and the rest length codes, then
+//! one for the [`DISTANCE_CODES`] distance codes. The tokens of the block
+//! follow. A literal is its symbol's code, and a match is the code of its
+//! length code, the length's extra bits, the code of its distance code and
+//! the distance's extra bits. Matches only reach back into their own block,
+//! so that blocks can be decoded alone.
+
+use anyhow::{ensure, Context, Result};
+
+use crate::{
+    bits::{BitReader, BitWriter},
+    format::FormatError,
+    huffman::{self, Code, Decoder, HuffmanTable},
+    lz77::{self, Token, DISTANCE_CODES, LENGTH_CODES},
+    Options,
+};
+
+pub const LITERAL_LENGTH_SYMBOLS: usize = 256 + LENGTH_CODES;
+
+/// Append the payload for `block` to `out`.
+pub fn compress(block: &[u8], options: &Options, out: &mut Vec<u8>) {
+    let mut tokens = Vec::with_capacity(block.len() / 4);
+    lz77::tokenize(block, options.level, options.window_bits, &mut tokens);
+
+    let mut literal_counts = [0u32; LITERAL_LENGTH_SYMBOLS];
+    let mut distance_counts = [0u32; DISTANCE_CODES];
+    for &token in &tokens {
+        match token {
+            Token::Literal(byte) => literal_counts[byte as usize] += 1,
+            Token::Match { length, distance } => {
+                literal_counts[256 + lz77::length_code(length).0] += 1;
+                distance_counts[lz77::distance_code(distance).0] += 1;
+            }
+        }
+    }
+    let literals = HuffmanTable::from_frequencies(&literal_counts, options.max_code_len);
+    let distances = HuffmanTable::from_frequencies(&distance_counts, options.max_code_len);
+    huffman::write_lengths(literals.lengths(), out);
+    huffman::write_lengths(distances.lengths(), out);
+
+    let code = |table: &HuffmanTable, symbol: usize| -> Code {
+        table
+            .get(symbol)
+            .expect("the tables cover every symbol of the block")
+    };
+    let mut writer = BitWriter::new();
+    for token in tokens {
+        match token {
+            Token::Literal(byte) => {
+                let literal = code(&literals, byte as usize);
+                writer.write(literal.bits, literal.len as u32);
+            }
+            Token::Match { length, distance } => {
+                let (symbol, bits, len) = lz77::length_code(length);
+                let length = code(&literals, 256 + symbol);
+                writer.write(length.bits, length.len as u32);
+                writer.write(bits, len as u32);
+
+                let (symbol, bits, len) = lz77::distance_code(distance);
+                let distance = code(&distances, symbol);
+                writer.write(distance.bits, distance.len as u32);
+                writer.write(bits, len as u32);
+            }
+        }
+    }
+    out.extend_from_slice(&writer.finish().0);
+}
+
+/// Decode the payload of a block of `size` bytes, appending them to `out`.
+pub fn decompress(mut payload: &[u8], size: usize, out: &mut Vec<u8>) -> Result<()> {
+    let lengths = huffman::read_lengths(&mut payload, LITERAL_LENGTH_SYMBOLS)
+        .context("could not read literal/length code")?;
+    let literals = HuffmanTable::from_lengths(lengths).context("invalid literal/length code")?;
+    let lengths = huffman::read_lengths(&mut payload, DISTANCE_CODES)
+        .context("could not read distance code")?;
+    let distances = HuffmanTable::from_lengths(lengths).context("invalid distance code")?;
+    let literals = Decoder::new(&literals);
+    let distances = Decoder::new(&distances);
+
+    let start = out.len();
+    let end = start + size;
+    out.reserve(size);
+    let mut reader = BitReader::new(payload);
+    while out.len() < end {
+        let symbol = literals.decode(&mut reader)?;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        let (base, extra) = lz77::length_base(symbol - 256).expect("the code has no other symbols");
+        let length = base as usize + read_bits(&mut reader, extra)? as usize;
+        let symbol = distances.decode(&mut reader)?;
+        let (base, extra) = lz77::distance_base(symbol).expect("the code has no other symbols");
+        let distance = base as usize + read_bits(&mut reader, extra)? as usize;
+
+        ensure!(
+            distance <= out.len() - start,
+            "match at byte {} of a block reaches back {} bytes",
+            out.len() - start,
+            distance
+        );
+        ensure!(
+            length <= end - out.len(),
+            "match runs past the end of its block"
+        );
+        lz77::copy_match(out, distance, length);
+    }
+    ensure!(
+        reader.remaining() < 8,
+        "unexpected data after the codes of a block"
+    );
+
+    Ok(())
+}
+
+fn read_bits(reader: &mut BitReader, len: u8) -> Result<u64> {
+    Ok(reader.read(len as u32).ok_or(FormatError::Truncated)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Algorithm;
+
+    fn options(level: u8) -> Options {
+        Options {
+            algorithm: Algorithm::Lzh,
+            level,
+            ..Options::default()
+        }
+    }
+
+    fn round_trip(block: &[u8], options: &Options) -> usize {
+        let mut payload = Vec::new();
+        compress(block, options, &mut payload);
+        let mut out = vec![0xaa];
+        decompress(&payload, block.len(), &mut out).unwrap();
+        assert_eq!(out[1..], block[..]);
+        payload.len()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = b"one two three, one two three, one two three four".repeat(20);
+        for level in [1, 4, 6, 9] {
+            let len = round_trip(&text, &options(level));
+            assert!(len < text.len() / 5, "level {}: {} bytes", level, len);
+        }
+        round_trip(b"", &options(6));
+        round_trip(b"x", &options(6));
+        round_trip(&[7; 10_000], &options(6));
+        let bytes: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        round_trip(&bytes, &options(6));
+    }
+
+    #[test]
+    fn test_rejects_matches_out_of_the_block() {
+        // literal "a", then a match of 3 from 2 back
+        let mut literals = [0u32; LITERAL_LENGTH_SYMBOLS];
+        literals[b'a' as usize] = 1;
+        literals[256] = 1;
+        let literals = HuffmanTable::from_frequencies(&literals, 15);
+        let mut distances = [0u32; DISTANCE_CODES];
+        distances[1] = 1;
+        let distances = HuffmanTable::from_frequencies(&distances, 15);
+
+        let mut payload = Vec::new();
+        huffman::write_lengths(literals.lengths(), &mut payload);
+        huffman::write_lengths(distances.lengths(), &mut payload);
+        let mut writer = BitWriter::new();
+        for code in [
+            literals.get(b'a' as usize),
+            literals.get(256),
+            distances.get(1),
+        ] {
+            let code = code.unwrap();
+            writer.write(code.bits, code.len as u32);
+        }
+        payload.extend_from_slice(&writer.finish().0);
+
+        let error = decompress(&payload, 4, &mut vec![b'a'; 10]).unwrap_err();
+        assert!(error.to_string().contains("reaches back"), "{}", error);
+        assert!(decompress(&payload, 2, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let text = b"abcabcabcabc, abcabc".repeat(10);
+        let mut payload = Vec::new();
+        compress(&text, &options(6), &mut payload);
+        for len in 0..payload.len() {
+            let error = decompress(&payload[..len], text.len(), &mut Vec::new()).unwrap_err();
+            assert_eq!(
+                error.downcast_ref(),
+                Some(&FormatError::Truncated),
+                "cut to {}: {:#}",
+                len,
+                error
+            );
+        }
+    }
+}
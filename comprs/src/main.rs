@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use comprs::{decode, encode};
+use comprs::{decode, encode, format, huffman, lz77, stream, Algorithm, Container, Options};
 
 #[derive(Parser)]
 #[command(name = "huffman")]
@@ -15,18 +15,76 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     Encode {
+        /// File to read, or `-` for standard input
         #[arg(value_name = "SOURCE")]
         source: PathBuf,
 
+        /// File to write, or `-` for standard output
         #[arg(value_name = "DESTINATION")]
         destination: PathBuf,
+
+        /// Kind of file to write
+        #[arg(long, value_enum, default_value_t = Container::Comprs)]
+        format: Container,
+
+        /// How to compress, for the comprs format
+        #[arg(long, value_enum, default_value_t = Algorithm::Huffman)]
+        algo: Algorithm,
+
+        /// How hard lzh and DEFLATE look for matches, from 1 (fastest) to 9
+        /// (smallest), or 0 to store DEFLATE data uncompressed
+        #[arg(
+            short,
+            long,
+            default_value_t = lz77::DEFAULT_LEVEL,
+            value_parser = clap::value_parser!(u8).range(0..=lz77::MAX_LEVEL as i64)
+        )]
+        level: u8,
+
+        /// Size of the lzh window, as a power of two
+        #[arg(
+            long,
+            value_name = "BITS",
+            default_value_t = lz77::DEFAULT_WINDOW_BITS,
+            value_parser = clap::value_parser!(u8)
+                .range(lz77::MIN_WINDOW_BITS as i64..=lz77::MAX_WINDOW_BITS as i64)
+        )]
+        window_bits: u8,
+
+        /// Longest Huffman code to use
+        #[arg(
+            long,
+            value_name = "BITS",
+            default_value_t = huffman::DEFAULT_MAX_CODE_LEN,
+            value_parser = clap::value_parser!(u8).range(8..=huffman::MAX_CODE_LEN as i64)
+        )]
+        max_code_length: u8,
+
+        /// Bytes compressed together with one Huffman code, in KiB
+        #[arg(
+            long,
+            value_name = "KIB",
+            default_value_t = (stream::DEFAULT_BLOCK_SIZE / 1024) as u64,
+            value_parser = clap::value_parser!(u64).range(1..=(format::MAX_BLOCK_SIZE / 1024) as u64)
+        )]
+        block_size: u64,
+
+        /// Number of worker threads [default: number of CPUs]
+        #[arg(short, long)]
+        jobs: Option<usize>,
     },
     Decode {
+        /// File to read, or `-` for standard input
         #[arg(value_name = "SOURCE")]
         source: PathBuf,
 
+        /// File to write, or `-` for standard output
         #[arg(value_name = "DESTINATION")]
         destination: PathBuf,
+
+        /// Kind of file to read [default: detected from its first bytes]
+        #[arg(long, value_enum)]
+        format: Option<Container>,
     },
 }
 
@@ -37,10 +95,30 @@ fn main() -> Result<()> {
         Commands::Encode {
             source,
             destination,
-        } => encode(&source, &destination),
+            format,
+            algo,
+            level,
+            window_bits,
+            max_code_length,
+            block_size,
+            jobs,
+        } => {
+            let defaults = Options::default();
+            let options = Options {
+                container: format,
+                algorithm: algo,
+                level,
+                window_bits,
+                max_code_len: max_code_length,
+                block_size: block_size as usize * 1024,
+                jobs: jobs.unwrap_or(defaults.jobs),
+            };
+            encode(&source, &destination, &options)
+        }
         Commands::Decode {
             source,
             destination,
-        } => decode(&source, &destination),
+            format,
+        } => decode(&source, &destination, format),
     }
 }
@@ -0,0 +1,568 @@
+//! Compressing and decompressing streams.
+//!
+//! An [`Encoder`] compresses whatever is written to it into another writer,
+//! and a [`Decoder`] decompresses what it reads from another reader. Both work
+//! a block at a time, so the memory they need depends on the block size but
+//! not on the size of the data.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind, Read, Write},
+    panic,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    block::{decode_block, encode_block},
+    format::{Algorithm, BlockHeader, FormatError, Header, Trailer, HEADER_LEN, TRAILER_LEN},
+    Options,
+};
+
+/// Bytes of input compressed together, with one Huffman code, unless
+/// [`Options::block_size`] says otherwise.
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// Compresses the data written to it into `W`.
+///
+/// With more than one job in its [`Options`], blocks are compressed on that
+/// many threads while more data is written, and written out in order. The
+/// output is the same however many jobs there are.
+///
+/// [`finish`](Encoder::finish) must be called at the end, or the output is
+/// incomplete. [`flush`](Write::flush) compresses the data written so far as
+/// a block of its own, so that a reader can decode it without waiting for
+/// more.
+pub struct Encoder<W: Write> {
+    inner: W,
+    options: Options,
+    /// Data not yet compressed, at most a block of it.
+    pending: Vec<u8>,
+    /// The last block compressed, kept to reuse its allocation.
+    out: Vec<u8>,
+    workers: Option<Workers>,
+    crc: crc32fast::Hasher,
+    size: u64,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Start compressed output in `inner`, writing the header.
+    pub fn new(mut inner: W, options: &Options) -> Result<Self> {
+        options.validate()?;
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        Header::new(options.algorithm).write(&mut header);
+        inner.write_all(&header).context("could not write header")?;
+        let workers = (options.jobs > 1).then(|| Workers::new(options));
+        Ok(Encoder {
+            inner,
+            options: options.clone(),
+            pending: Vec::new(),
+            out: Vec::new(),
+            workers,
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+        })
+    }
+
+    /// Compress the pending data as a block, or hand it to the workers.
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        match &mut self.workers {
+            None => {
+                self.out.clear();
+                encode_block(&self.pending, &self.options, &mut self.out);
+                self.inner.write_all(&self.out)?;
+                self.pending.clear();
+            }
+            Some(workers) => {
+                workers.send(std::mem::take(&mut self.pending), &mut self.inner)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Compress the pending data and write out every block handed to the
+    /// workers.
+    fn write_blocks(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        if let Some(workers) = &mut self.workers {
+            workers.wait(&mut self.inner)?;
+        }
+        Ok(())
+    }
+
+    /// Compress the rest of the data, write the end of the stream and return
+    /// the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_blocks().context("could not write block")?;
+        let trailer = Trailer {
+            size: self.size,
+            crc: self.crc.clone().finalize(),
+        };
+        self.inner
+            .write_all(&[0; 4])
+            .and_then(|()| self.inner.write_all(&trailer.to_bytes()))
+            .and_then(|()| self.inner.flush())
+            .context("could not write trailer")?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.pending.len() == self.options.block_size {
+            self.write_block()?;
+        }
+        let n = buf.len().min(self.options.block_size - self.pending.len());
+        self.pending.extend_from_slice(&buf[..n]);
+        self.crc.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_blocks()?;
+        self.inner.flush()
+    }
+}
+
+/// Threads compressing blocks for an [`Encoder`], each block tagged with its
+/// place in the output.
+struct Workers {
+    blocks: Option<mpsc::Sender<(u64, Vec<u8>)>>,
+    /// Compressed blocks, or `None` for a block whose thread panicked.
+    encoded: mpsc::Receiver<(u64, Option<Vec<u8>>)>,
+    threads: Vec<JoinHandle<()>>,
+    /// Blocks sent to the threads so far.
+    sent: u64,
+    /// Blocks written to the output so far.
+    written: u64,
+    /// Blocks that came back before their turn.
+    done: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Workers {
+    fn new(options: &Options) -> Self {
+        let (blocks, queue) = mpsc::channel::<(u64, Vec<u8>)>();
+        let (results, encoded) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let threads = (0..options.jobs)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let results = results.clone();
+                let options = options.clone();
+                thread::spawn(move || loop {
+                    // the lock is released before compressing
+                    let next = queue.lock().map(|queue| queue.recv());
+                    let Ok(Ok((i, block))) = next else {
+                        break;
+                    };
+                    // a panic is passed on rather than leaving the block
+                    // missing, which would keep the encoder waiting for it
+                    let out = panic::catch_unwind(|| {
+                        let mut out = Vec::with_capacity(block.len() / 2);
+                        encode_block(&block, &options, &mut out);
+                        out
+                    });
+                    if results.send((i, out.ok())).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        Workers {
+            blocks: Some(blocks),
+            encoded,
+            threads,
+            sent: 0,
+            written: 0,
+            done: BTreeMap::new(),
+        }
+    }
+
+    /// Queue `block`, writing finished blocks to `out` in order. Waits for
+    /// some to finish when twice as many blocks as threads are in flight,
+    /// which bounds the memory held.
+    fn send(&mut self, block: Vec<u8>, out: &mut impl Write) -> io::Result<()> {
+        let sent = self
+            .blocks
+            .as_ref()
+            .is_some_and(|blocks| blocks.send((self.sent, block)).is_ok());
+        if !sent {
+            return Err(stopped());
+        }
+        self.sent += 1;
+        while let Ok((i, encoded)) = self.encoded.try_recv() {
+            self.done.insert(i, encoded.ok_or_else(stopped)?);
+        }
+        self.write_done(out)?;
+        while self.sent - self.written >= 2 * self.threads.len() as u64 {
+            self.receive(out)?;
+        }
+        Ok(())
+    }
+
+    /// Write every block sent so far to `out`.
+    fn wait(&mut self, out: &mut impl Write) -> io::Result<()> {
+        while self.written < self.sent {
+            self.receive(out)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for a block to finish, then write those whose turn it is.
+    fn receive(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (i, encoded) = self.encoded.recv().map_err(|_| stopped())?;
+        self.done.insert(i, encoded.ok_or_else(stopped)?);
+        self.write_done(out)
+    }
+
+    fn write_done(&mut self, out: &mut impl Write) -> io::Result<()> {
+        while let Some(encoded) = self.done.remove(&self.written) {
+            out.write_all(&encoded)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        // closing the queue ends the threads once they finish their blocks
+        self.blocks = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::other("a compression thread stopped unexpectedly")
+}
+
+/// Decompresses the data read from `R`.
+///
+/// Besides implementing [`Read`], it hands out whole blocks with
+/// [`read_block`](Decoder::read_block), whose errors keep their
+/// [`FormatError`] for callers to inspect.
+pub struct Decoder<R: Read> {
+    inner: R,
+    algorithm: Algorithm,
+    /// The payload of the last block, kept to reuse its allocation.
+    payload: Vec<u8>,
+    /// The last block decoded.
+    block: Vec<u8>,
+    /// Bytes of `block` already passed on by [`Read::read`].
+    pos: usize,
+    crc: crc32fast::Hasher,
+    size: u64,
+    done: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Start reading compressed data from `inner`, checking the header.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut header = [0; HEADER_LEN];
+        let mut len = 0;
+        while len < HEADER_LEN {
+            match inner.read(&mut header[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context("could not read header"),
+            }
+        }
+        let header = Header::read(&mut &header[..len])?;
+        Ok(Decoder {
+            inner,
+            algorithm: header.algorithm(),
+            payload: Vec::new(),
+            block: Vec::new(),
+            pos: 0,
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+            done: false,
+        })
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.inner.read_exact(buf).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => FormatError::Truncated.into(),
+            _ => anyhow::Error::from(e).context("could not read compressed data"),
+        })
+    }
+
+    /// Decode the next block, or check the trailer and return `None` at the
+    /// end of the data.
+    pub fn read_block(&mut self) -> Result<Option<&[u8]>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut bytes = [0; 4];
+        self.read_exact(&mut bytes)?;
+        let Some(size) = BlockHeader::read_size(bytes)? else {
+            self.finish()?;
+            return Ok(None);
+        };
+        self.read_exact(&mut bytes)?;
+        let payload = BlockHeader::read_payload(size, bytes)?;
+
+        let mut buf = std::mem::take(&mut self.payload);
+        buf.resize(payload, 0);
+        let read = self.read_exact(&mut buf);
+        self.payload = buf;
+        read?;
+
+        self.block.clear();
+        self.pos = 0;
+        decode_block(&self.payload, size, self.algorithm, &mut self.block)?;
+        self.crc.update(&self.block);
+        self.size += size as u64;
+        Ok(Some(&self.block))
+    }
+
+    /// Check the trailer and that nothing follows it.
+    fn finish(&mut self) -> Result<()> {
+        let mut trailer = [0; TRAILER_LEN];
+        self.read_exact(&mut trailer)?;
+        let decoded = Trailer {
+            size: self.size,
+            crc: self.crc.clone().finalize(),
+        };
+        Trailer::from_bytes(trailer).verify(&decoded)?;
+
+        let mut extra = [0];
+        loop {
+            match self.inner.read(&mut extra) {
+                Ok(0) => break,
+                Ok(_) => bail!("unexpected data after the end of the compressed data"),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context("could not read compressed data"),
+            }
+        }
+        self.done = true;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.block.len() {
+            if self.read_block().map_err(into_io)?.is_none() {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.block.len() - self.pos);
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// `error` as an I/O error, keeping a [`FormatError`] where there is one.
+fn into_io(error: anyhow::Error) -> io::Error {
+    let error = match error.downcast::<io::Error>() {
+        Ok(error) => return error,
+        Err(error) => error,
+    };
+    match error.downcast::<FormatError>() {
+        Ok(error) => io::Error::new(ErrorKind::InvalidData, error),
+        Err(error) => io::Error::new(ErrorKind::InvalidData, format!("{:#}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compress `contents` with small blocks, writing it in pieces of
+    /// `chunk` bytes.
+    fn encode_in_chunks(contents: &[u8], block_size: usize, chunk: usize) -> Vec<u8> {
+        let options = Options {
+            block_size,
+            jobs: 1,
+            ..Options::default()
+        };
+        let mut encoder = Encoder::new(Vec::new(), &options).unwrap();
+        for piece in contents.chunks(chunk) {
+            encoder.write_all(piece).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn text(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| b"the quick brown fox jumps over the lazy dog "[i % 44] ^ (i / 1000) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_in_blocks() {
+        let contents = text(10_000);
+        for (block_size, chunk) in [
+            (1, 7),
+            (100, 1),
+            (999, 333),
+            (4096, 10_000),
+            (DEFAULT_BLOCK_SIZE, 5),
+        ] {
+            let encoded = encode_in_chunks(&contents, block_size, chunk);
+
+            let mut decoded = Vec::new();
+            Decoder::new(&encoded[..])
+                .unwrap()
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, contents, "blocks of {}", block_size);
+        }
+    }
+
+    #[test]
+    fn test_read_in_small_pieces() {
+        let contents = text(5_000);
+        let encoded = encode_in_chunks(&contents, 1000, 5_000);
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut decoded = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            let n = decoder.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(decoded, contents);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let contents = text(2_500);
+        let encoded = encode_in_chunks(&contents, 1000, 64);
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut sizes = Vec::new();
+        while let Some(block) = decoder.read_block().unwrap() {
+            sizes.push(block.len());
+        }
+        assert_eq!(sizes, [1000, 1000, 500]);
+    }
+
+    #[test]
+    fn test_flush_ends_block() {
+        let mut encoder = Encoder::new(Vec::new(), &Options::default()).unwrap();
+        encoder.write_all(b"first").unwrap();
+        encoder.flush().unwrap();
+        encoder.flush().unwrap();
+        encoder.write_all(b"second").unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        assert_eq!(decoder.read_block().unwrap(), Some(&b"first"[..]));
+        assert_eq!(decoder.read_block().unwrap(), Some(&b"second"[..]));
+        assert_eq!(decoder.read_block().unwrap(), None);
+    }
+
+    #[test]
+    fn test_jobs_give_the_same_output() {
+        let contents = text(100_000);
+        let serial = encode_in_chunks(&contents, 1000, 777);
+        let options = Options {
+            block_size: 1000,
+            jobs: 4,
+            ..Options::default()
+        };
+        let mut encoder = Encoder::new(Vec::new(), &options).unwrap();
+        for piece in contents.chunks(777) {
+            encoder.write_all(piece).unwrap();
+        }
+        assert_eq!(encoder.finish().unwrap(), serial);
+    }
+
+    #[test]
+    fn test_flush_with_jobs() {
+        let contents = text(100_000);
+        for jobs in [2, 3, 8] {
+            let options = Options {
+                block_size: 1000,
+                jobs,
+                ..Options::default()
+            };
+            let mut encoder = Encoder::new(Vec::new(), &options).unwrap();
+            for (i, piece) in contents.chunks(777).enumerate() {
+                encoder.write_all(piece).unwrap();
+                if i == 50 {
+                    encoder.flush().unwrap();
+                }
+            }
+            let parallel = encoder.finish().unwrap();
+
+            let mut decoded = Vec::new();
+            Decoder::new(&parallel[..])
+                .unwrap()
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, contents, "{} jobs", jobs);
+        }
+    }
+
+    #[test]
+    fn test_unfinished_encoder_stops_its_threads() {
+        let options = Options {
+            block_size: 10,
+            jobs: 4,
+            ..Options::default()
+        };
+        let mut encoder = Encoder::new(Vec::new(), &options).unwrap();
+        encoder.write_all(&text(1000)).unwrap();
+        drop(encoder);
+    }
+
+    #[test]
+    fn test_truncated_in_every_place() {
+        let encoded = encode_in_chunks(&text(300), 100, 300);
+        for len in 0..encoded.len() {
+            let result = Decoder::new(&encoded[..len]).and_then(|mut decoder| {
+                while decoder.read_block()?.is_some() {}
+                Ok(())
+            });
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.downcast_ref(),
+                Some(&FormatError::Truncated),
+                "cut to {} bytes: {:#}",
+                len,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_keeps_format_error() {
+        let mut encoded = encode_in_chunks(b"some data", 100, 100);
+        let last = encoded.len() - 1;
+        encoded[last] ^= 0xff;
+
+        let error = Decoder::new(&encoded[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let inner = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<FormatError>());
+        assert!(matches!(inner, Some(FormatError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_rejects_trailing_data() {
+        let mut encoded = encode_in_chunks(b"some data", 100, 100);
+        encoded.push(0);
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        assert!(decoder.read_block().unwrap().is_some());
+        assert!(decoder.read_block().is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! zlib streams, as specified in RFC 1950: DEFLATE data between a two byte
+//! header and an Adler-32 of the original data.
+
+use std::io::{self, Write};
+
+use anyhow::{ensure, Result};
+
+use crate::{deflate, format::FormatError};
+
+/// The compression method, which is always DEFLATE.
+const DEFLATE: u8 = 8;
+/// A 32K window, the largest DEFLATE allows.
+const CMF: u8 = 0x78;
+/// A preset dictionary, which is not supported.
+const FDICT: u8 = 0x20;
+
+/// The largest prime below 2^16.
+const ADLER_MOD: u32 = 65521;
+/// The most bytes that can be summed before the sums may overflow.
+const ADLER_NMAX: usize = 5552;
+
+/// The Adler-32 checksum of `data`.
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.finish()
+}
+
+/// An Adler-32 checksum of data that comes in pieces.
+#[derive(Clone, Copy, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+}
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `data` to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(ADLER_NMAX) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER_MOD;
+            self.b %= ADLER_MOD;
+        }
+    }
+
+    /// The checksum of the data so far.
+    pub fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+/// Whether `bytes` starts with a valid zlib header.
+pub fn is_zlib(bytes: &[u8]) -> bool {
+    match bytes {
+        [cmf, flg, ..] => {
+            cmf & 0x0f == DEFLATE
+                && cmf >> 4 <= 7
+                && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31)
+        }
+        _ => false,
+    }
+}
+
+/// Compress `data` into a zlib stream.
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    let out = Vec::with_capacity(data.len() / 2 + 6);
+    let mut encoder = Encoder::new(out, level).expect("writing to a Vec cannot fail");
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+/// Compresses the data written to it into a zlib stream in `W`.
+///
+/// [`finish`](Encoder::finish) must be called at the end, or the output is
+/// incomplete.
+pub struct Encoder<W: Write> {
+    deflate: deflate::Encoder<W>,
+    adler: Adler32,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Start a zlib stream in `inner`, writing the header.
+    pub fn new(mut inner: W, level: u8) -> io::Result<Self> {
+        let flevel = match level {
+            0 | 1 => 0,
+            2..=5 => 1,
+            6 => 2,
+            _ => 3,
+        };
+        let flg = flevel << 6;
+        let check = (31 - (CMF as u16 * 256 + flg as u16) % 31) % 31;
+        inner.write_all(&[CMF, flg | check as u8])?;
+        Ok(Encoder {
+            deflate: deflate::Encoder::new(inner, level),
+            adler: Adler32::new(),
+        })
+    }
+
+    /// Compress the rest of the data, write the checksum and return the
+    /// writer.
+    pub fn finish(self) -> io::Result<W> {
+        let mut inner = self.deflate.finish()?;
+        inner.write_all(&self.adler.finish().to_be_bytes())?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.deflate.write(buf)?;
+        self.adler.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.deflate.flush()
+    }
+}
+
+/// Decompress the zlib stream `input`.
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    decompress_into(input, &mut out)?;
+    Ok(out)
+}
+
+/// Decompress the zlib stream `input`, writing the data to `out` as it
+/// goes.
+pub fn decompress_into(input: &[u8], out: &mut impl Write) -> Result<()> {
+    ensure!(input.len() >= 2, FormatError::Truncated);
+    ensure!(is_zlib(input), "not a zlib stream");
+    ensure!(
+        input[1] & FDICT == 0,
+        "zlib streams with a preset dictionary are not supported"
+    );
+    let mut checked = Checked {
+        inner: out,
+        adler: Adler32::new(),
+    };
+    let used = deflate::decompress(&input[2..], &mut checked)?;
+    let rest = &input[2 + used..];
+    ensure!(rest.len() >= 4, FormatError::Truncated);
+    ensure!(
+        rest.len() == 4,
+        "unexpected data after the end of the compressed data"
+    );
+    let expected = u32::from_be_bytes(rest.try_into().unwrap());
+    let actual = checked.adler.finish();
+    if expected != actual {
+        return Err(FormatError::ChecksumMismatch { expected, actual }.into());
+    }
+    Ok(())
+}
+
+/// Passes the data written to it on to `W`, keeping its Adler-32.
+struct Checked<'a, W: Write> {
+    inner: &'a mut W,
+    adler: Adler32,
+}
+
+impl<W: Write> Write for Checked<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.adler.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &[u8] = include_bytes!("../fixtures/log.txt");
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // long enough for the sums to need reducing
+        let ones = vec![0xff; 100_000];
+        let (a, b) = ones.iter().fold((1u64, 0u64), |(a, b), &byte| {
+            let a = (a + byte as u64) % ADLER_MOD as u64;
+            (a, (b + a) % ADLER_MOD as u64)
+        });
+        assert_eq!(adler32(&ones), ((b << 16) | a) as u32);
+
+        let mut pieces = Adler32::new();
+        for piece in ones.chunks(7_001) {
+            pieces.update(piece);
+        }
+        assert_eq!(pieces.finish(), adler32(&ones));
+    }
+
+    #[test]
+    fn test_decompress_zlib_fixture() {
+        let zz = include_bytes!("../fixtures/log.txt.zz");
+        assert!(is_zlib(zz));
+        assert_eq!(decompress(zz).unwrap(), LOG);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for data in [LOG, b"", b"a"] {
+            for level in [0, 1, 6, 9] {
+                let zz = compress(data, level);
+                assert!(is_zlib(&zz));
+                assert_eq!(decompress(&zz).unwrap(), data, "level {}", level);
+            }
+        }
+        // the header zlib itself writes
+        assert_eq!(compress(b"a", 6)[..2], [0x78, 0x9c]);
+        assert_eq!(compress(b"a", 9)[..2], [0x78, 0xda]);
+        assert_eq!(compress(b"a", 1)[..2], [0x78, 0x01]);
+    }
+
+    #[test]
+    fn test_rejects_bad_streams() {
+        assert!(!is_zlib(b"\x1f\x8b"));
+        assert!(decompress(b"\x78\x9d\x03\x00").is_err());
+        assert!(decompress(b"\x78\xbb\x00\x00\x00\x01\x03\x00").is_err());
+
+        let zz = compress(b"hello", 6);
+        let mut wrong_adler = zz.clone();
+        let last = wrong_adler.len() - 1;
+        wrong_adler[last] ^= 1;
+        assert!(matches!(
+            decompress(&wrong_adler).unwrap_err().downcast_ref(),
+            Some(FormatError::ChecksumMismatch { .. })
+        ));
+
+        let mut trailing = zz.clone();
+        trailing.push(0);
+        assert!(decompress(&trailing).is_err());
+
+        for len in 0..zz.len() {
+            let error = decompress(&zz[..len]).unwrap_err();
+            assert_eq!(
+                error.downcast_ref(),
+                Some(&FormatError::Truncated),
+                "cut to {}: {:#}",
+                len,
+                error
+            );
+        }
+    }
+}
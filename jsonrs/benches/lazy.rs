@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use jsonrs::{lazy::get_lazy, value::Value};
+
+/// A document of `records` objects followed by a small trailer, so that the
+/// requested fields sit at both ends of the input.
+fn document(records: usize) -> String {
+    let mut out = String::from(r#"{"meta": {"version": 3, "source": "bench"}, "records": ["#);
+    for i in 0..records {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&format!(
+            r#"{{"id": {}, "name": "record {}", "tags": ["a", "b\"c"], "score": {}.5, "nested": {{"ok": true, "values": [1, 2, 3]}}}}"#,
+            i, i, i
+        ));
+    }
+    out.push_str(r#"], "summary": {"count": "#);
+    out.push_str(&records.to_string());
+    out.push_str("}}");
+    out
+}
+
+fn bench_lazy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("select two fields");
+    for records in [1_000, 20_000] {
+        let input = document(records);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("full parse", records),
+            &input,
+            |b, input| {
+                b.iter(|| {
+                    let value: Value = input.parse().unwrap();
+                    (
+                        value.pointer("/meta/version").cloned(),
+                        value.pointer("/summary/count").cloned(),
+                    )
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("get_lazy", records), &input, |b, input| {
+            b.iter(|| get_lazy(input, &["/meta/version", "/summary/count"]).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("get_lazy one field", records),
+            &input,
+            |b, input| b.iter(|| get_lazy(input, &["/meta/version"]).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lazy);
+criterion_main!(benches);
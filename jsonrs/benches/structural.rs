@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use jsonrs::{
+    lexer::Lexer,
+    structural::{self, Backend},
+    token::TokenType,
+    value::Value,
+};
+
+/// An array of `records` objects mixing strings with escapes, numbers and
+/// nested containers.
+fn document(records: usize) -> String {
+    let mut out = String::from("[");
+    for i in 0..records {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        out.push_str(&format!(
+            r#"{{"id": {}, "name": "record \"{}\"", "tags": ["alpha", "beta", "gamma\\delta"], "score": {}.25, "nested": {{"ok": false, "values": [1, 2, 3, null]}}}}"#,
+            i, i, i
+        ));
+    }
+    out.push(']');
+    out
+}
+
+fn bench_structural(c: &mut Criterion) {
+    let input = document(20_000);
+    let mut group = c.benchmark_group("structural");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    group.bench_function("lexer tokens", |b| {
+        b.iter(|| {
+            let mut l = Lexer::new(input.clone());
+            let mut count = 0usize;
+            while l.next_token().token_type != TokenType::EOF {
+                count += 1;
+            }
+            count
+        })
+    });
+    for backend in Backend::available() {
+        group.bench_with_input(
+            BenchmarkId::new("stage 1", format!("{:?}", backend)),
+            &backend,
+            |b, &backend| b.iter(|| structural::index_with(backend, &input).unwrap().len()),
+        );
+    }
+    group.bench_function("stage 1 + stage 2", |b| {
+        b.iter(|| structural::parse(&input).unwrap())
+    });
+    group.bench_function("parser", |b| b.iter(|| input.parse::<Value>().unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_structural);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#![no_main]
+
+use jsonrs::{lexer::Lexer, token::TokenType};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let mut l = Lexer::new(input.into());
+    // the lexer does not advance past an illegal token, so stop there
+    loop {
+        match l.next_token().token_type {
+            TokenType::EOF | TokenType::ILLEGAL => break,
+            _ => {}
+        }
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use jsonrs::{encoding, lexer::Lexer, parser::Parser, value::Value};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| {
+    let Ok(text) = encoding::decode(input) else {
+        return;
+    };
+    let mut l = Lexer::new(text);
+    let Ok(value) = Parser::new(&mut l).parse() else {
+        return;
+    };
+
+    // whatever parses must serialize to text that parses back the same;
+    // compare text, as overflowing numbers become `null` on the first pass
+    let json = value.to_string();
+    let reparsed: Value = json.parse().expect("serialized value should parse");
+    assert_eq!(reparsed.to_string(), json);
+});
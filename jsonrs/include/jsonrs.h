@@ -0,0 +1,79 @@
+/* Generated from src/ffi.rs by tests/ffi.rs; do not edit. */
+
+#ifndef JSONRS_H
+#define JSONRS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// A parsed document, opaque to C.
+typedef struct JsonrsDocument JsonrsDocument;
+
+// Why parsing failed. Lines and columns count from 1, columns in characters.
+typedef struct {
+    size_t line;
+    size_t column;
+    size_t offset;
+    // NUL-terminated message, freed by `jsonrs_error_free`.
+    char *message;
+} JsonrsError;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Parse `len` bytes of UTF-8 at `text`. Returns NULL on failure and, if
+// `error` is not NULL, describes the failure there.
+//
+// # Safety
+//
+// `text` must point to `len` readable bytes and `error` must be NULL or
+// point to a writable `JsonrsError`.
+JsonrsDocument *jsonrs_parse(const char *text, size_t len, JsonrsError *error);
+
+// Free a document returned by `jsonrs_parse`. NULL is ignored.
+//
+// # Safety
+//
+// `document` must be NULL or a pointer from `jsonrs_parse` that has not
+// been freed.
+void jsonrs_document_free(JsonrsDocument *document);
+
+// The value at the RFC 6901 JSON Pointer `pointer` as compact JSON, or NULL
+// if there is none. The result is freed by `jsonrs_string_free`.
+//
+// # Safety
+//
+// `document` must be a live pointer from `jsonrs_parse` and `pointer` a
+// NUL-terminated string.
+char *jsonrs_pointer(const JsonrsDocument *document, const char *pointer);
+
+// The whole document as compact JSON, freed by `jsonrs_string_free`.
+//
+// # Safety
+//
+// `document` must be a live pointer from `jsonrs_parse`.
+char *jsonrs_to_string(const JsonrsDocument *document);
+
+// Free a string returned by this library. NULL is ignored.
+//
+// # Safety
+//
+// `s` must be NULL or a string from this library that has not been freed.
+void jsonrs_string_free(char *s);
+
+// Free the message of an error filled in by `jsonrs_parse` and set it to
+// NULL, so freeing twice is harmless.
+//
+// # Safety
+//
+// `error` must be NULL or point to a `JsonrsError` filled in by
+// `jsonrs_parse`.
+void jsonrs_error_free(JsonrsError *error);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* JSONRS_H */
@@ -0,0 +1,292 @@
+//! Validation of many files at once.
+//!
+//! [`expand`] turns files, directories and glob patterns into a list of paths,
+//! and [`validate`] checks them on a pool of worker threads. Each result is
+//! passed on as soon as every result before it is known, so results stream in
+//! the order of the paths whatever the scheduling. [`Summary`], [`junit`] and
+//! [`json_report`] describe a finished run.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Write},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Context, Result};
+
+use crate::{encoding, lexer::Lexer, parser::Parser, value::Value};
+
+/// Report formats for CI systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+/// The result of validating one file.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub path: PathBuf,
+    pub duration: Duration,
+    /// The error with its causes, if the file is not valid JSON.
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Resolve each input to files: directories are searched recursively for
+/// files ending in `.{extension}`, without following symlinks to
+/// directories, inputs containing `*`, `?` or `[` are glob
+/// patterns and anything else is a file. Directory and glob matches are
+/// sorted, and a path found twice is only kept the first time.
+pub fn expand(inputs: &[String], extension: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            walk(path, extension, &mut paths)?;
+        } else if input.contains(['*', '?', '[']) {
+            let mut matches = glob::glob(input)
+                .context(format!("invalid glob pattern {:?}", input))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ensure!(!matches.is_empty(), "no files match {:?}", input);
+            matches.sort();
+            paths.extend(matches);
+        } else {
+            paths.push(path.to_path_buf());
+        }
+    }
+    let mut seen = HashSet::new();
+    paths.retain(|p| seen.insert(p.clone()));
+    Ok(paths)
+}
+
+fn walk(dir: &Path, extension: &str, out: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("failed to read directory `{:?}`", dir))?
+        .map(|entry| entry.and_then(|e| Ok((e.path(), e.file_type()?))))
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!("failed to read directory `{:?}`", dir))?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    for (path, file_type) in entries {
+        // the file type does not follow symlinks, so links to directories
+        // are skipped and cannot lead around a cycle
+        if file_type.is_dir() {
+            walk(&path, extension, out)?;
+        } else if path.extension().is_some_and(|e| e == extension) && !path.is_dir() {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Check that `path` holds a single JSON document in any Unicode encoding,
+/// parsing numbers as exact decimals if `exact_numbers` is set.
+pub fn validate_file(path: &Path, exact_numbers: bool) -> Result<()> {
+    let bytes = fs::read(path).context(format!("failed to open file `{:?}`", path))?;
+    let text = encoding::decode(&bytes).context(format!("failed to decode file `{:?}`", path))?;
+    let mut lexer = Lexer::new(text);
+    Parser::new(&mut lexer)
+        .exact_numbers(exact_numbers)
+        .parse()?;
+    Ok(())
+}
+
+/// Validate `paths` on `jobs` threads, calling `f` with each outcome in the
+/// order of `paths`, and return all outcomes in that order.
+pub fn validate(
+    paths: &[PathBuf],
+    jobs: usize,
+    exact_numbers: bool,
+    mut f: impl FnMut(&Outcome),
+) -> Vec<Outcome> {
+    let jobs = jobs.clamp(1, paths.len().max(1));
+    let next = AtomicUsize::new(0);
+    let mut done = Vec::with_capacity(paths.len());
+
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else {
+                    break;
+                };
+                let start = Instant::now();
+                let result = validate_file(path, exact_numbers);
+                let outcome = Outcome {
+                    path: path.clone(),
+                    duration: start.elapsed(),
+                    error: result.err().map(|e| format!("{:#}", e)),
+                };
+                if tx.send((i, outcome)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // hold back results that finish early until their turn
+        let mut pending = BTreeMap::new();
+        for (i, outcome) in rx {
+            pending.insert(i, outcome);
+            while let Some(outcome) = pending.remove(&done.len()) {
+                f(&outcome);
+                done.push(outcome);
+            }
+        }
+    });
+    done
+}
+
+/// Pass and fail counts of a run, with the slowest files.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    /// Time spent validating, summed over all files.
+    pub total: Duration,
+    /// The slowest files, slowest first.
+    pub slowest: Vec<(PathBuf, Duration)>,
+}
+
+impl Summary {
+    pub fn new(outcomes: &[Outcome], slowest: usize) -> Self {
+        let passed = outcomes.iter().filter(|o| o.passed()).count();
+        let mut by_time: Vec<(PathBuf, Duration)> = outcomes
+            .iter()
+            .map(|o| (o.path.clone(), o.duration))
+            .collect();
+        // stable, so ties keep the order of the paths
+        by_time.sort_by_key(|&(_, d)| std::cmp::Reverse(d));
+        by_time.truncate(slowest);
+        Summary {
+            passed,
+            failed: outcomes.len() - passed,
+            total: outcomes.iter().map(|o| o.duration).sum(),
+            slowest: by_time,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} passed, {} failed in {:.3}s",
+            self.passed,
+            self.failed,
+            self.total.as_secs_f64()
+        )?;
+        if !self.slowest.is_empty() {
+            writeln!(f, "slowest:")?;
+            for (path, duration) in &self.slowest {
+                writeln!(
+                    f,
+                    "  {:>10.3}ms  {}",
+                    duration.as_secs_f64() * 1000.0,
+                    path.display()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A JUnit XML report with one test case per file.
+pub fn junit(outcomes: &[Outcome]) -> String {
+    let mut out = String::new();
+    write_junit(&mut out, outcomes).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_junit(out: &mut String, outcomes: &[Outcome]) -> fmt::Result {
+    let summary = Summary::new(outcomes, 0);
+    let counts = format!(
+        "tests=\"{}\" failures=\"{}\" time=\"{:.6}\"",
+        outcomes.len(),
+        summary.failed,
+        summary.total.as_secs_f64()
+    );
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<testsuites {}>", counts)?;
+    writeln!(out, "  <testsuite name=\"jsonrs validate\" {}>", counts)?;
+    for outcome in outcomes {
+        write!(
+            out,
+            "    <testcase classname=\"validate\" name=\"{}\" time=\"{:.6}\"",
+            xml_escape(&outcome.path.display().to_string()),
+            outcome.duration.as_secs_f64()
+        )?;
+        match &outcome.error {
+            None => writeln!(out, "/>")?,
+            Some(error) => {
+                let error = xml_escape(error);
+                writeln!(out, ">")?;
+                writeln!(
+                    out,
+                    "      <failure message=\"{}\">{}</failure>",
+                    error, error
+                )?;
+                writeln!(out, "    </testcase>")?;
+            }
+        }
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // XML 1.0 cannot represent other control characters at all
+            c if c < ' ' && !matches!(c, '\t' | '\n' | '\r') => out.push('\u{fffd}'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A JSON report with the counts and one entry per file.
+pub fn json_report(outcomes: &[Outcome]) -> Value {
+    let summary = Summary::new(outcomes, 0);
+    let files = outcomes
+        .iter()
+        .map(|o| {
+            let mut file = BTreeMap::new();
+            file.insert("path".into(), o.path.display().to_string().into());
+            file.insert("ok".into(), o.passed().into());
+            file.insert("seconds".into(), o.duration.as_secs_f64().into());
+            if let Some(error) = &o.error {
+                file.insert("error".into(), error.as_str().into());
+            }
+            Value::Object(file)
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = BTreeMap::new();
+    report.insert("passed".into(), (summary.passed as i64).into());
+    report.insert("failed".into(), (summary.failed as i64).into());
+    report.insert("seconds".into(), summary.total.as_secs_f64().into());
+    report.insert("files".into(), files.into());
+    Value::Object(report)
+}
@@ -0,0 +1,129 @@
+//! Conversion between [`Value`] and other data formats.
+//!
+//! Each text format module exposes `from_str` and `to_string`, and the binary
+//! ones `from_slice` and `to_vec`. Writers return an error naming the offending
+//! path when a value has no representation in the target format, e.g. `null`
+//! in TOML or a nested object in CSV.
+
+use std::fmt;
+
+use anyhow::{Context, Result};
+
+use crate::value::Value;
+
+pub mod cbor;
+pub mod csv;
+mod f16;
+pub mod msgpack;
+pub mod toml;
+pub mod yaml;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Csv,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Json => "JSON",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+            Format::Csv => "CSV",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Options for the binary encoders in [`cbor`] and [`msgpack`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeOptions {
+    /// Order map entries by the bytes of their encoded keys rather than by
+    /// key, as required by RFC 8949 deterministic encoding.
+    pub canonical_keys: bool,
+    /// Store each float in the narrowest width that holds it exactly.
+    pub shortest_floats: bool,
+}
+
+impl EncodeOptions {
+    /// Options that give the same bytes for equal values, whatever their source.
+    pub fn deterministic() -> Self {
+        EncodeOptions {
+            canonical_keys: true,
+            shortest_floats: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BinaryFormat {
+    Cbor,
+    Msgpack,
+}
+
+/// Serialize `value` as `format`.
+pub fn encode(format: BinaryFormat, value: &Value, options: &EncodeOptions) -> Result<Vec<u8>> {
+    match format {
+        BinaryFormat::Cbor => Ok(cbor::to_vec(value, options)),
+        BinaryFormat::Msgpack => {
+            msgpack::to_vec(value, options).context("failed to encode MessagePack")
+        }
+    }
+}
+
+/// Parse `input` encoded as `format`.
+pub fn decode(format: BinaryFormat, input: &[u8]) -> Result<Value> {
+    match format {
+        BinaryFormat::Cbor => cbor::from_slice(input).context("failed to decode CBOR"),
+        BinaryFormat::Msgpack => msgpack::from_slice(input).context("failed to decode MessagePack"),
+    }
+}
+
+/// Parse `input` written in `format`.
+pub fn read(format: Format, input: &str) -> Result<Value> {
+    match format {
+        Format::Json => input.parse(),
+        Format::Yaml => yaml::from_str(input),
+        Format::Toml => toml::from_str(input),
+        Format::Csv => csv::from_str(input),
+    }
+    .context(format!("failed to read {} input", format))
+}
+
+/// Serialize `value` as `format`.
+pub fn write(format: Format, value: &Value) -> Result<String> {
+    match format {
+        Format::Json => Ok(value.to_pretty_string() + "\n"),
+        Format::Yaml => Ok(yaml::to_string(value)),
+        Format::Toml => toml::to_string(value),
+        Format::Csv => csv::to_string(value),
+    }
+    .context(format!("failed to write {} output", format))
+}
+
+/// Render a path such as `.servers[0].host` for error messages.
+pub(crate) fn path_to_string(path: &[PathSegment]) -> String {
+    if path.is_empty() {
+        return ".".into();
+    }
+    let mut out: String = path
+        .iter()
+        .map(|segment| match segment {
+            PathSegment::Key(k) => format!(".{}", k),
+            PathSegment::Index(i) => format!("[{}]", i),
+        })
+        .collect();
+    if out.starts_with('[') {
+        out.insert(0, '.');
+    }
+    out
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum PathSegment {
+    Key(String),
+    Index(usize),
+}
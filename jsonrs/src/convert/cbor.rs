@@ -0,0 +1,448 @@
+//! CBOR as specified by RFC 8949.
+//!
+//! Encoding always uses definite lengths and the shortest argument encoding.
+//! With [`EncodeOptions::deterministic`] the output additionally follows the
+//! core deterministic encoding requirements of section 4.2.1. Exact decimals
+//! are written as tag 4 decimal fractions and read back as exact decimals.
+//! Decoding accepts indefinite lengths and skips other tags, but rejects byte
+//! strings, non-string map keys, infinities and NaN, which have no JSON
+//! equivalent.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use super::{f16, EncodeOptions};
+use crate::{
+    decimal::BigDecimal,
+    value::{Number, Value},
+};
+
+const MAX_DEPTH: usize = 512;
+/// Longest bignum mantissa of a decimal fraction, which bounds the quadratic
+/// conversion to decimal digits.
+const MAX_BIGNUM_LEN: usize = 4096;
+
+pub fn to_vec(value: &Value, options: &EncodeOptions) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode(&mut out, value, options);
+    out
+}
+
+fn encode(out: &mut Vec<u8>, value: &Value, options: &EncodeOptions) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(Number::Int(i)) => encode_int(out, *i),
+        Value::Number(Number::Float(f)) => encode_float(out, *f, options),
+        Value::Number(Number::Decimal(d)) => encode_decimal(out, d),
+        Value::String(s) => {
+            write_head(out, 3, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(a) => {
+            write_head(out, 4, a.len() as u64);
+            for item in a {
+                encode(out, item, options);
+            }
+        }
+        Value::Object(o) => {
+            write_head(out, 5, o.len() as u64);
+            let mut entries: Vec<(Vec<u8>, &Value)> = o
+                .iter()
+                .map(|(k, v)| (to_vec(&Value::String(k.clone()), options), v))
+                .collect();
+            if options.canonical_keys {
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            for (key, v) in entries {
+                out.extend_from_slice(&key);
+                encode(out, v, options);
+            }
+        }
+    }
+}
+
+fn encode_int(out: &mut Vec<u8>, i: i64) {
+    if i >= 0 {
+        write_head(out, 0, i as u64);
+    } else {
+        // -1 - n is stored as n, which cannot overflow for any i64
+        write_head(out, 1, !(i as u64));
+    }
+}
+
+/// Write a decimal fraction (tag 4): an array of the exponent and the
+/// mantissa, which is a bignum (tag 2 or 3) when it does not fit in 64 bits.
+fn encode_decimal(out: &mut Vec<u8>, d: &BigDecimal) {
+    let (negative, digits, exponent) = d.parts();
+    write_head(out, 6, 4);
+    write_head(out, 4, 2);
+    encode_int(out, exponent);
+
+    let mut magnitude = digits_to_bytes(digits);
+    if negative {
+        // like negative integers, negative bignums store -1 - n as n
+        decrement(&mut magnitude);
+    }
+    let skip = magnitude.iter().take_while(|&&b| b == 0).count();
+    let magnitude = &magnitude[skip..];
+    if magnitude.len() <= 8 {
+        let mut n = [0; 8];
+        n[8 - magnitude.len()..].copy_from_slice(magnitude);
+        write_head(out, negative as u8, u64::from_be_bytes(n));
+    } else {
+        write_head(out, 6, 2 + negative as u64);
+        write_head(out, 2, magnitude.len() as u64);
+        out.extend_from_slice(magnitude);
+    }
+}
+
+/// The big-endian bytes of the unsigned integer written as decimal `digits`.
+fn digits_to_bytes(digits: &str) -> Vec<u8> {
+    let mut digits: Vec<u32> = digits.bytes().map(|b| (b - b'0') as u32).collect();
+    let mut bytes = Vec::new();
+    while !digits.is_empty() {
+        // divide by 256, keeping the remainder as the next byte
+        let mut remainder = 0;
+        let mut quotient = Vec::with_capacity(digits.len());
+        for d in digits {
+            let n = remainder * 10 + d;
+            if !quotient.is_empty() || n >= 256 {
+                quotient.push(n / 256);
+            }
+            remainder = n % 256;
+        }
+        bytes.push(remainder as u8);
+        digits = quotient;
+    }
+    bytes.reverse();
+    bytes
+}
+
+/// The decimal digits of the unsigned big-endian integer `bytes`.
+fn bytes_to_digits(bytes: &[u8]) -> String {
+    // least significant digit first
+    let mut digits: Vec<u32> = Vec::new();
+    for &b in bytes {
+        let mut carry = b as u32;
+        for d in digits.iter_mut() {
+            let n = *d * 256 + carry;
+            *d = n % 10;
+            carry = n / 10;
+        }
+        while carry > 0 {
+            digits.push(carry % 10);
+            carry /= 10;
+        }
+    }
+    if digits.is_empty() {
+        return "0".into();
+    }
+    digits
+        .iter()
+        .rev()
+        .map(|&d| char::from(b'0' + d as u8))
+        .collect()
+}
+
+/// Subtract one from a big-endian integer greater than zero.
+fn decrement(bytes: &mut [u8]) {
+    for b in bytes.iter_mut().rev() {
+        let (n, borrow) = b.overflowing_sub(1);
+        *b = n;
+        if !borrow {
+            return;
+        }
+    }
+}
+
+/// Add one to a big-endian integer, growing it if it carries out.
+fn increment(bytes: &mut Vec<u8>) {
+    for b in bytes.iter_mut().rev() {
+        let (n, carry) = b.overflowing_add(1);
+        *b = n;
+        if !carry {
+            return;
+        }
+    }
+    bytes.insert(0, 1);
+}
+
+fn encode_float(out: &mut Vec<u8>, f: f64, options: &EncodeOptions) {
+    if options.shortest_floats {
+        if let Some(half) = f16::from_f64(f) {
+            out.push(0xf9);
+            out.extend_from_slice(&half.to_be_bytes());
+            return;
+        }
+        if (f as f32) as f64 == f {
+            out.push(0xfa);
+            out.extend_from_slice(&(f as f32).to_be_bytes());
+            return;
+        }
+    }
+    out.push(0xfb);
+    out.extend_from_slice(&f.to_be_bytes());
+}
+
+/// Write a major type with its argument in the shortest form.
+fn write_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.extend_from_slice(&[major | 24, n as u8]);
+    } else if n <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+pub fn from_slice(input: &[u8]) -> Result<Value> {
+    let mut d = Decoder { input, position: 0 };
+    let value = d.decode(0)?;
+    ensure!(
+        d.position == input.len(),
+        "{} trailing bytes after CBOR item",
+        input.len() - d.position
+    );
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+/// The argument of a data item head, or `None` for indefinite lengths.
+type Argument = Option<u64>;
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self
+            .position
+            .checked_add(n)
+            .filter(|&end| end <= self.input.len())
+            .context(format!("unexpected end of input at byte {}", self.position))?;
+        let bytes = &self.input[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn head(&mut self) -> Result<(u8, u8, Argument)> {
+        let initial = self.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let argument = match info {
+            0..=23 => Some(info as u64),
+            24 => Some(self.byte()? as u64),
+            25 => Some(u16::from_be_bytes(self.take(2)?.try_into()?) as u64),
+            26 => Some(u32::from_be_bytes(self.take(4)?.try_into()?) as u64),
+            27 => Some(u64::from_be_bytes(self.take(8)?.try_into()?)),
+            31 if matches!(major, 2..=5 | 7) => None,
+            _ => bail!(
+                "invalid additional information {} at byte {}",
+                info,
+                self.position - 1
+            ),
+        };
+        Ok((major, info, argument))
+    }
+
+    fn length(&self, argument: u64) -> Result<usize> {
+        let len = usize::try_from(argument)?;
+        ensure!(
+            len <= self.input.len() - self.position,
+            "length {} at byte {} exceeds the remaining input",
+            len,
+            self.position
+        );
+        Ok(len)
+    }
+
+    /// Whether the next byte is the `break` stop code, consuming it if so.
+    fn at_break(&mut self) -> bool {
+        if self.peek() == Some(0xff) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn decode(&mut self, depth: usize) -> Result<Value> {
+        ensure!(
+            depth <= MAX_DEPTH,
+            "CBOR nesting exceeds {} levels",
+            MAX_DEPTH
+        );
+        let start = self.position;
+        let (major, info, argument) = self.head()?;
+
+        let value = match (major, argument) {
+            (0, Some(n)) => match i64::try_from(n) {
+                Ok(i) => Value::Number(Number::Int(i)),
+                Err(_) => Value::Number(Number::Float(n as f64)),
+            },
+            (1, Some(n)) => match i64::try_from(n) {
+                Ok(i) => Value::Number(Number::Int(-1 - i)),
+                Err(_) => Value::Number(Number::Float(-1.0 - n as f64)),
+            },
+            (2, _) => bail!(
+                "byte string at byte {} cannot be represented in JSON",
+                start
+            ),
+            (3, Some(n)) => {
+                let len = self.length(n)?;
+                Value::String(self.text(len)?)
+            }
+            (3, None) => {
+                let mut s = String::new();
+                while !self.at_break() {
+                    let (major, _, argument) = self.head()?;
+                    ensure!(major == 3, "invalid chunk in indefinite-length string");
+                    let len = self.length(argument.context("nested indefinite string")?)?;
+                    s.push_str(&self.text(len)?);
+                }
+                Value::String(s)
+            }
+            (4, Some(n)) => {
+                let len = self.length(n)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.decode(depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            (4, None) => {
+                let mut items = Vec::new();
+                while !self.at_break() {
+                    items.push(self.decode(depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            (5, Some(n)) => {
+                let len = self.length(n)?;
+                let mut object = BTreeMap::new();
+                for _ in 0..len {
+                    let (key, value) = self.entry(depth)?;
+                    object.insert(key, value);
+                }
+                Value::Object(object)
+            }
+            (5, None) => {
+                let mut object = BTreeMap::new();
+                while !self.at_break() {
+                    let (key, value) = self.entry(depth)?;
+                    object.insert(key, value);
+                }
+                Value::Object(object)
+            }
+            (6, Some(4)) => self.decimal_fraction(start)?,
+            // tags carry semantics JSON cannot express, so decode the tagged item
+            (6, Some(tag)) => {
+                ensure!(
+                    !matches!(tag, 2 | 3),
+                    "bignum at byte {} cannot be represented in JSON",
+                    start
+                );
+                self.decode(depth + 1)?
+            }
+            (7, _) => match info {
+                20 => Value::Bool(false),
+                21 => Value::Bool(true),
+                22 | 23 => Value::Null,
+                25..=27 => {
+                    let f = match info {
+                        25 => f16::to_f64(u16::from_be_bytes(
+                            self.input[start + 1..start + 3].try_into()?,
+                        )),
+                        26 => f32::from_bits(u32::from_be_bytes(
+                            self.input[start + 1..start + 5].try_into()?,
+                        )) as f64,
+                        _ => f64::from_bits(u64::from_be_bytes(
+                            self.input[start + 1..start + 9].try_into()?,
+                        )),
+                    };
+                    ensure!(
+                        f.is_finite(),
+                        "{} at byte {} cannot be represented in JSON",
+                        f,
+                        start
+                    );
+                    Value::Number(Number::Float(f))
+                }
+                31 => bail!("unexpected break at byte {}", start),
+                _ => bail!("unsupported simple value {} at byte {}", info, start),
+            },
+            _ => bail!("malformed data item at byte {}", start),
+        };
+        Ok(value)
+    }
+
+    /// Decode the content of a decimal fraction whose tag started at `start`.
+    fn decimal_fraction(&mut self, start: usize) -> Result<Value> {
+        let invalid = || format!("invalid decimal fraction at byte {}", start);
+        let (major, _, len) = self.head()?;
+        ensure!(major == 4 && len == Some(2), invalid());
+        let exponent = match self.head()? {
+            (0, _, Some(n)) => i64::try_from(n).ok(),
+            (1, _, Some(n)) => i64::try_from(n).ok().map(|n| -1 - n),
+            _ => None,
+        }
+        .with_context(invalid)?;
+        let (negative, mut magnitude) = match self.head()? {
+            (major @ (0 | 1), _, Some(n)) => (major == 1, n.to_be_bytes().to_vec()),
+            (6, _, Some(tag @ (2 | 3))) => {
+                let (major, _, len) = self.head()?;
+                ensure!(major == 2, invalid());
+                let len = self.length(len.with_context(invalid)?)?;
+                ensure!(
+                    len <= MAX_BIGNUM_LEN,
+                    "bignum at byte {} is longer than {} bytes",
+                    start,
+                    MAX_BIGNUM_LEN
+                );
+                (tag == 3, self.take(len)?.to_vec())
+            }
+            _ => bail!(invalid()),
+        };
+        if negative {
+            increment(&mut magnitude);
+        }
+        let sign = if negative { "-" } else { "" };
+        let decimal = format!("{}{}e{}", sign, bytes_to_digits(&magnitude), exponent)
+            .parse()
+            .with_context(invalid)?;
+        Ok(Value::Number(Number::Decimal(decimal)))
+    }
+
+    fn text(&mut self, len: usize) -> Result<String> {
+        let start = self.position;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .context(format!("invalid UTF-8 in string at byte {}", start))
+    }
+
+    fn entry(&mut self, depth: usize) -> Result<(String, Value)> {
+        let start = self.position;
+        let Value::String(key) = self.decode(depth + 1)? else {
+            bail!("map key at byte {} is not a text string", start);
+        };
+        Ok((key, self.decode(depth + 1)?))
+    }
+}
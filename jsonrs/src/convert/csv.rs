@@ -0,0 +1,224 @@
+//! CSV as described by RFC 4180, mapped to an array of flat objects.
+//!
+//! The first row is the header. Unquoted cells that look like JSON numbers or
+//! booleans are typed accordingly and empty unquoted cells become `null`, so a
+//! flat array of objects survives a round trip.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Result};
+
+use super::{path_to_string, PathSegment};
+use crate::value::{Number, Value};
+
+pub fn from_str(input: &str) -> Result<Value> {
+    let mut rows = parse_rows(input)?.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(Value::Array(Vec::new()));
+    };
+
+    let mut records = Vec::new();
+    for (i, row) in rows.enumerate() {
+        ensure!(
+            row.len() == header.len(),
+            "row {} has {} fields, but the header has {}",
+            i + 2,
+            row.len(),
+            header.len()
+        );
+        let record: BTreeMap<_, _> = header
+            .iter()
+            .map(|cell| cell.text.clone())
+            .zip(row.into_iter().map(infer))
+            .collect();
+        records.push(Value::Object(record));
+    }
+
+    Ok(Value::Array(records))
+}
+
+struct Cell {
+    text: String,
+    quoted: bool,
+}
+
+impl Cell {
+    fn take(&mut self) -> Cell {
+        Cell {
+            text: std::mem::take(&mut self.text),
+            quoted: std::mem::take(&mut self.quoted),
+        }
+    }
+}
+
+fn parse_rows(input: &str) -> Result<Vec<Vec<Cell>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = Cell {
+        text: String::new(),
+        quoted: false,
+    };
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.text.is_empty() => {
+                field.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.text.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            line += usize::from(c == '\n');
+                            field.text.push(c);
+                        }
+                        None => bail!("unterminated quoted field starting on line {}", line),
+                    }
+                }
+            }
+            ',' => row.push(field.take()),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                row.push(field.take());
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+            }
+            c => field.text.push(c),
+        }
+    }
+
+    if !field.text.is_empty() || field.quoted || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+fn infer(cell: Cell) -> Value {
+    if cell.quoted {
+        return Value::String(cell.text);
+    }
+    match cell.text.as_str() {
+        "" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        s if looks_numeric(s) => s
+            .parse::<Number>()
+            .map(Value::Number)
+            .unwrap_or(Value::String(cell.text)),
+        _ => Value::String(cell.text),
+    }
+}
+
+/// Only treat cells shaped like JSON numbers as numbers, so values such as
+/// `007` or `1.` stay strings.
+fn looks_numeric(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let int_end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let (int, rest) = digits.split_at(int_end);
+    if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+        return false;
+    }
+    let rest = match rest.strip_prefix('.') {
+        Some(frac) => {
+            let end = frac
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(frac.len());
+            if end == 0 {
+                return false;
+            }
+            &frac[end..]
+        }
+        None => rest,
+    };
+    match rest.strip_prefix(['e', 'E']) {
+        Some(exp) => {
+            let exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+            !exp.is_empty() && exp.chars().all(|c| c.is_ascii_digit())
+        }
+        None => rest.is_empty(),
+    }
+}
+
+pub fn to_string(value: &Value) -> Result<String> {
+    let Value::Array(records) = value else {
+        bail!(
+            "CSV output requires an array of objects, got {}",
+            value.type_name()
+        );
+    };
+
+    let mut header: Vec<&String> = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let Value::Object(record) = record else {
+            bail!(
+                "{} is {}, but CSV rows must be objects",
+                path_to_string(&[PathSegment::Index(i)]),
+                record.type_name()
+            );
+        };
+        for key in record.keys() {
+            if !header.contains(&key) {
+                header.push(key);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    write_row(&mut out, header.iter().map(|k| (k.as_str(), false)));
+
+    for (i, record) in records.iter().enumerate() {
+        let mut cells = Vec::with_capacity(header.len());
+        for key in &header {
+            let cell = match record.get(key) {
+                None | Some(Value::Null) => (String::new(), false),
+                // quote strings that would otherwise be read back as another type
+                Some(Value::String(s)) => (s.clone(), !is_string(s)),
+                Some(v @ (Value::Bool(_) | Value::Number(_))) => (v.to_string(), false),
+                Some(v) => bail!(
+                    "{} is {}, which cannot be represented in CSV",
+                    path_to_string(&[PathSegment::Index(i), PathSegment::Key(key.to_string())]),
+                    v.type_name()
+                ),
+            };
+            cells.push(cell);
+        }
+        write_row(
+            &mut out,
+            cells.iter().map(|(c, quote)| (c.as_str(), *quote)),
+        );
+    }
+
+    Ok(out)
+}
+
+fn is_string(s: &str) -> bool {
+    let cell = Cell {
+        text: s.into(),
+        quoted: false,
+    };
+    matches!(infer(cell), Value::String(_))
+}
+
+fn write_row<'a>(out: &mut String, cells: impl Iterator<Item = (&'a str, bool)>) {
+    for (i, (cell, quote)) in cells.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if quote || cell.contains([',', '"', '\n', '\r']) || cell.trim() != cell {
+            out.push('"');
+            out.push_str(&cell.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(cell);
+        }
+    }
+    out.push('\n');
+}
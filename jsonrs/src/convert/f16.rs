@@ -0,0 +1,50 @@
+//! IEEE 754 half-precision floats, as used by CBOR.
+
+/// Widen half-precision bits to an `f64`, which is always exact.
+pub fn to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        e => (1024.0 + mantissa) * 2f64.powi(e - 25),
+    };
+    sign * magnitude
+}
+
+/// Narrow `f` to half precision if that loses no information.
+pub fn from_f64(f: f64) -> Option<u16> {
+    if f.is_nan() {
+        return Some(0x7e00);
+    }
+    let sign = if f.is_sign_negative() { 0x8000 } else { 0 };
+    let a = f.abs();
+    if a.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+    if a == 0.0 {
+        return Some(sign);
+    }
+    if !(2f64.powi(-24)..=65504.0).contains(&a) {
+        return None;
+    }
+
+    let exponent = ((a.to_bits() >> 52) & 0x7ff) as i32 - 1023;
+    let bits = if exponent >= -14 {
+        let mantissa = a * 2f64.powi(10 - exponent) - 1024.0;
+        if mantissa.fract() != 0.0 {
+            return None;
+        }
+        (((exponent + 15) as u16) << 10) | mantissa as u16
+    } else {
+        let mantissa = a * 2f64.powi(24);
+        if mantissa.fract() != 0.0 {
+            return None;
+        }
+        mantissa as u16
+    };
+    Some(sign | bits)
+}
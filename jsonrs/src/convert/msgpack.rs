@@ -0,0 +1,304 @@
+//! MessagePack, following the current msgpack specification.
+//!
+//! Integers and lengths always use their smallest representation. There is no
+//! decimal type, so encoding fails on exact decimals that no float holds
+//! exactly. Decoding rejects `bin` and `ext` values, non-string map keys,
+//! infinities and NaN, which have no JSON equivalent.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use super::{path_to_string, EncodeOptions, PathSegment};
+use crate::{
+    decimal::BigDecimal,
+    value::{Number, Value},
+};
+
+const MAX_DEPTH: usize = 512;
+
+pub fn to_vec(value: &Value, options: &EncodeOptions) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    encode(&mut out, value, options, &mut Vec::new())?;
+    Ok(out)
+}
+
+fn encode(
+    out: &mut Vec<u8>,
+    value: &Value,
+    options: &EncodeOptions,
+    path: &mut Vec<PathSegment>,
+) -> Result<()> {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(Number::Int(i)) => encode_int(out, *i),
+        Value::Number(n @ (Number::Float(_) | Number::Decimal(_))) => {
+            let f = n.as_f64();
+            if let Number::Decimal(d) = n {
+                ensure!(
+                    BigDecimal::try_from(f).is_ok_and(|f| f == *d),
+                    "{} is {}, which cannot be represented exactly in MessagePack",
+                    path_to_string(path),
+                    d
+                );
+            }
+            if options.shortest_floats && ((f as f32) as f64 == f || f.is_nan()) {
+                out.push(0xca);
+                out.extend_from_slice(&(f as f32).to_be_bytes());
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&f.to_be_bytes());
+            }
+        }
+        Value::String(s) => encode_str(out, s),
+        Value::Array(a) => {
+            if a.len() < 16 {
+                out.push(0x90 | a.len() as u8);
+            } else {
+                write_length(out, 0xdc, a.len());
+            }
+            for (i, item) in a.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                encode(out, item, options, path)?;
+                path.pop();
+            }
+        }
+        Value::Object(o) => {
+            if o.len() < 16 {
+                out.push(0x80 | o.len() as u8);
+            } else {
+                write_length(out, 0xde, o.len());
+            }
+            let mut entries: Vec<(Vec<u8>, &String, &Value)> = o
+                .iter()
+                .map(|(k, v)| {
+                    let mut key = Vec::new();
+                    encode_str(&mut key, k);
+                    (key, k, v)
+                })
+                .collect();
+            if options.canonical_keys {
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            for (key, k, v) in entries {
+                out.extend_from_slice(&key);
+                path.push(PathSegment::Key(k.clone()));
+                encode(out, v, options, path)?;
+                path.pop();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_str(out: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    if len < 32 {
+        out.push(0xa0 | len as u8);
+    } else if len <= u8::MAX as usize {
+        out.extend_from_slice(&[0xd9, len as u8]);
+    } else {
+        write_length(out, 0xda, len);
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode_int(out: &mut Vec<u8>, i: i64) {
+    match i {
+        0..=127 => out.push(i as u8),
+        -32..=-1 => out.push(i as i8 as u8),
+        128..=0xff => out.extend_from_slice(&[0xcc, i as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(i as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(i as u32).to_be_bytes());
+        }
+        0x1_0000_0000.. => {
+            out.push(0xcf);
+            out.extend_from_slice(&(i as u64).to_be_bytes());
+        }
+        -0x80..=-33 => out.extend_from_slice(&[0xd0, i as i8 as u8]),
+        -0x8000..=-0x81 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(i as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(i as i32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xd3);
+            out.extend_from_slice(&i.to_be_bytes());
+        }
+    }
+}
+
+/// Write a 16-bit or 32-bit length using the marker `base` or `base + 1`.
+fn write_length(out: &mut Vec<u8>, base: u8, len: usize) {
+    if len <= u16::MAX as usize {
+        out.push(base);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(base + 1);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+pub fn from_slice(input: &[u8]) -> Result<Value> {
+    let mut d = Decoder { input, position: 0 };
+    let value = d.decode(0)?;
+    ensure!(
+        d.position == input.len(),
+        "{} trailing bytes after MessagePack value",
+        input.len() - d.position
+    );
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self
+            .position
+            .checked_add(n)
+            .filter(|&end| end <= self.input.len())
+            .context(format!("unexpected end of input at byte {}", self.position))?;
+        let bytes = &self.input[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn len8(&mut self) -> Result<usize> {
+        Ok(self.array::<1>()?[0] as usize)
+    }
+
+    fn len16(&mut self) -> Result<usize> {
+        Ok(u16::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn len32(&mut self) -> Result<usize> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn decode(&mut self, depth: usize) -> Result<Value> {
+        ensure!(
+            depth <= MAX_DEPTH,
+            "MessagePack nesting exceeds {} levels",
+            MAX_DEPTH
+        );
+        let start = self.position;
+        let marker = self.array::<1>()?[0];
+
+        let int = |i: i64| Value::Number(Number::Int(i));
+        let value = match marker {
+            0x00..=0x7f => int(marker as i64),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth)?,
+            0x90..=0x9f => self.seq((marker & 0x0f) as usize, depth)?,
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize)?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xca | 0xcb => {
+                let f = match marker {
+                    0xca => f32::from_be_bytes(self.array()?) as f64,
+                    _ => f64::from_be_bytes(self.array()?),
+                };
+                ensure!(
+                    f.is_finite(),
+                    "{} at byte {} cannot be represented in JSON",
+                    f,
+                    start
+                );
+                Value::Number(Number::Float(f))
+            }
+            0xcc => int(u8::from_be_bytes(self.array()?) as i64),
+            0xcd => int(u16::from_be_bytes(self.array()?) as i64),
+            0xce => int(u32::from_be_bytes(self.array()?) as i64),
+            0xcf => {
+                let n = u64::from_be_bytes(self.array()?);
+                i64::try_from(n).map_or(Value::Number(Number::Float(n as f64)), int)
+            }
+            0xd0 => int(i8::from_be_bytes(self.array()?) as i64),
+            0xd1 => int(i16::from_be_bytes(self.array()?) as i64),
+            0xd2 => int(i32::from_be_bytes(self.array()?) as i64),
+            0xd3 => int(i64::from_be_bytes(self.array()?)),
+            0xd9 => {
+                let len = self.len8()?;
+                self.str(len)?
+            }
+            0xda => {
+                let len = self.len16()?;
+                self.str(len)?
+            }
+            0xdb => {
+                let len = self.len32()?;
+                self.str(len)?
+            }
+            0xdc => {
+                let len = self.len16()?;
+                self.seq(len, depth)?
+            }
+            0xdd => {
+                let len = self.len32()?;
+                self.seq(len, depth)?
+            }
+            0xde => {
+                let len = self.len16()?;
+                self.map(len, depth)?
+            }
+            0xdf => {
+                let len = self.len32()?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => int(marker as i8 as i64),
+            0xc4..=0xc6 => bail!("bin value at byte {} cannot be represented in JSON", start),
+            0xc7..=0xc9 | 0xd4..=0xd8 => {
+                bail!("ext value at byte {} cannot be represented in JSON", start)
+            }
+            0xc1 => bail!("reserved marker 0xc1 at byte {}", start),
+        };
+        Ok(value)
+    }
+
+    fn str(&mut self, len: usize) -> Result<Value> {
+        let start = self.position;
+        let bytes = self.take(len)?.to_vec();
+        let s = String::from_utf8(bytes)
+            .context(format!("invalid UTF-8 in string at byte {}", start))?;
+        Ok(Value::String(s))
+    }
+
+    fn seq(&mut self, len: usize, depth: usize) -> Result<Value> {
+        // every element takes at least one byte, which bounds the allocation
+        let mut items = Vec::with_capacity(len.min(self.input.len() - self.position));
+        for _ in 0..len {
+            items.push(self.decode(depth + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<Value> {
+        let mut object = BTreeMap::new();
+        for _ in 0..len {
+            let start = self.position;
+            let Value::String(key) = self.decode(depth + 1)? else {
+                bail!("map key at byte {} is not a string", start);
+            };
+            object.insert(key, self.decode(depth + 1)?);
+        }
+        Ok(Value::Object(object))
+    }
+}
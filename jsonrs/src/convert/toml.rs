@@ -0,0 +1,613 @@
+//! TOML 1.0 documents.
+//!
+//! Dates and times are read as strings, since the value tree has no
+//! date type. On output the document root must be an object and `null` is
+//! rejected wherever it appears.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+
+use super::{path_to_string, PathSegment};
+use crate::value::{write_escaped, Number, Value};
+
+pub fn from_str(input: &str) -> Result<Value> {
+    let mut p = TomlParser {
+        chars: input.chars().collect(),
+        position: 0,
+        line: 1,
+    };
+    p.parse_document()
+        .map_err(|e| anyhow!("line {}: {}", p.line, e))
+}
+
+struct TomlParser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl TomlParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_str(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.position + i) == Some(&c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        self.position += 1;
+        c
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        ensure!(
+            self.peek() == Some(c),
+            "expected `{}`, got {:?}",
+            c,
+            self.peek()
+        );
+        self.bump();
+        Ok(())
+    }
+
+    /// Skip spaces and tabs, but not newlines.
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    /// Skip whitespace, newlines and comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\n' | '\r') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.bump();
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.chars.get(self.position + 1) == Some(&'\n') => Ok(()),
+            Some(c) => bail!("expected end of line, got `{}`", c),
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Value> {
+        let mut root = Value::Object(BTreeMap::new());
+        // path of the table that key/value pairs are currently added to
+        let mut current: Vec<PathSegment> = Vec::new();
+
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => return Ok(root),
+                Some('[') if self.peek_str("[[") => {
+                    self.position += 2;
+                    let keys = self.parse_key()?;
+                    self.skip_spaces();
+                    self.expect(']')?;
+                    self.expect(']')?;
+                    self.end_of_line()?;
+                    current = append_table(&mut root, &keys)?;
+                }
+                Some('[') => {
+                    self.bump();
+                    let keys = self.parse_key()?;
+                    self.skip_spaces();
+                    self.expect(']')?;
+                    self.end_of_line()?;
+                    current = open_table(&mut root, &keys)?;
+                }
+                Some(_) => {
+                    let (keys, value) = self.parse_key_value()?;
+                    self.end_of_line()?;
+                    let table = resolve_mut(&mut root, &current);
+                    insert_dotted(table, &keys, value)?;
+                }
+            }
+        }
+    }
+
+    fn parse_key_value(&mut self) -> Result<(Vec<String>, Value)> {
+        let keys = self.parse_key()?;
+        self.skip_spaces();
+        self.expect('=')?;
+        self.skip_spaces();
+        let value = self.parse_value()?;
+        Ok((keys, value))
+    }
+
+    /// Parse a possibly dotted key such as `a."b.c".d`.
+    fn parse_key(&mut self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let start = self.position;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.bump();
+                    }
+                    ensure!(
+                        self.position > start,
+                        "expected a key, got {:?}",
+                        self.peek()
+                    );
+                    self.chars[start..self.position].iter().collect()
+                }
+            };
+            keys.push(key);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.bump();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') if self.peek_str("\"\"\"") => self.parse_multiline_basic().map(Value::from),
+            Some('\'') if self.peek_str("'''") => self.parse_multiline_literal().map(Value::from),
+            Some('"') => self.parse_basic_string().map(Value::from),
+            Some('\'') => self.parse_literal_string().map(Value::from),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_inline_table(),
+            Some(_) => {
+                let start = self.position;
+                while self
+                    .peek()
+                    .is_some_and(|c| !matches!(c, ',' | ']' | '}' | '#' | '\n' | '\r'))
+                {
+                    self.bump();
+                }
+                let raw: String = self.chars[start..self.position].iter().collect();
+                parse_bare_value(raw.trim_end())
+            }
+            None => bail!("expected a value"),
+        }
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(out),
+                Some('\\') => out.push(self.parse_escape()?),
+                Some('\n') | None => bail!("unterminated string"),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char> {
+        let c = match self.bump() {
+            Some('b') => '\u{08}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{0c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(u @ ('u' | 'U')) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let hex: String = (0..len).filter_map(|_| self.bump()).collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .context(format!("invalid unicode escape `\\{}{}`", u, hex))?;
+                char::from_u32(code).ok_or_else(|| anyhow!("invalid code point {:#x}", code))?
+            }
+            c => bail!("invalid escape sequence {:?}", c),
+        };
+        Ok(c)
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String> {
+        self.expect('\'')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(out),
+                Some('\n') | None => bail!("unterminated string"),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_multiline_basic(&mut self) -> Result<String> {
+        self.position += 3;
+        self.skip_leading_newline();
+        let mut out = String::new();
+        loop {
+            if self.peek_str("\"\"\"") && !self.peek_str("\"\"\"\"") {
+                self.position += 3;
+                return Ok(out);
+            }
+            match self.bump() {
+                Some('\\') => {
+                    // a backslash at the end of a line trims the following whitespace
+                    if matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+                        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+                            self.bump();
+                        }
+                    } else {
+                        out.push(self.parse_escape()?);
+                    }
+                }
+                Some(c) => out.push(c),
+                None => bail!("unterminated multi-line string"),
+            }
+        }
+    }
+
+    fn parse_multiline_literal(&mut self) -> Result<String> {
+        self.position += 3;
+        self.skip_leading_newline();
+        let mut out = String::new();
+        loop {
+            if self.peek_str("'''") && !self.peek_str("''''") {
+                self.position += 3;
+                return Ok(out);
+            }
+            match self.bump() {
+                Some(c) => out.push(c),
+                None => bail!("unterminated multi-line string"),
+            }
+        }
+    }
+
+    fn skip_leading_newline(&mut self) {
+        if self.peek_str("\r\n") {
+            self.position += 1;
+        }
+        if self.peek() == Some('\n') {
+            self.bump();
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value()?);
+            self.skip_trivia();
+            match self.bump() {
+                Some(',') => (),
+                Some(']') => return Ok(Value::Array(items)),
+                c => bail!("expected `,` or `]` in array, got {:?}", c),
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut table = Value::Object(BTreeMap::new());
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(table);
+        }
+        loop {
+            let (keys, value) = self.parse_key_value()?;
+            insert_dotted(&mut table, &keys, value)?;
+            self.skip_spaces();
+            match self.bump() {
+                Some(',') => (),
+                Some('}') => return Ok(table),
+                c => bail!("expected `,` or `}}` in inline table, got {:?}", c),
+            }
+        }
+    }
+}
+
+fn parse_bare_value(raw: &str) -> Result<Value> {
+    let value = match raw {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        // JSON has no infinities or NaN, and writing them as `null` would
+        // silently change the data
+        "inf" | "+inf" | "-inf" | "nan" | "+nan" | "-nan" => {
+            bail!("`{}` cannot be represented in JSON", raw)
+        }
+        _ if is_datetime(raw) => Value::String(raw.into()),
+        _ => {
+            let digits = raw.replace('_', "");
+            let radix = match digits.get(..2) {
+                Some("0x") => Some(16),
+                Some("0o") => Some(8),
+                Some("0b") => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                let n = i64::from_str_radix(&digits[2..], radix)
+                    .context(format!("invalid integer `{}`", raw))?;
+                Value::Number(Number::Int(n))
+            } else {
+                ensure!(
+                    digits.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+')
+                        && !digits.contains(['x', 'o', 'b']),
+                    "invalid value `{}`",
+                    raw
+                );
+                let n = digits
+                    .trim_start_matches('+')
+                    .parse()
+                    .context(format!("invalid number `{}`", raw))?;
+                ensure!(
+                    !matches!(n, Number::Float(f) if !f.is_finite()),
+                    "number `{}` is out of range",
+                    raw
+                );
+                Value::Number(n)
+            }
+        }
+    };
+    Ok(value)
+}
+
+/// Offset date-times, local date-times, dates and times.
+fn is_datetime(raw: &str) -> bool {
+    let b = raw.as_bytes();
+    let date = b.len() >= 10 && b[4] == b'-' && b[7] == b'-';
+    let time = b.len() >= 8 && b[2] == b':' && b[5] == b':';
+    date || time
+}
+
+/// Walk `path` from `root`, descending into the last element of arrays of
+/// tables.
+fn resolve_mut<'a>(root: &'a mut Value, path: &[PathSegment]) -> &'a mut Value {
+    path.iter()
+        .fold(root, |target, segment| match (target, segment) {
+            (Value::Object(o), PathSegment::Key(k)) => o.get_mut(k).expect("table path exists"),
+            (Value::Array(a), PathSegment::Index(i)) => &mut a[*i],
+            _ => unreachable!("table paths only contain objects and arrays"),
+        })
+}
+
+/// Descend through `keys` creating tables as needed, returning the path of
+/// the final table.
+fn descend(root: &mut Value, keys: &[String]) -> Result<Vec<PathSegment>> {
+    let mut path = Vec::new();
+    let mut target = root;
+    for key in keys {
+        let Value::Object(o) = target else {
+            bail!("`{}` is not a table", path_to_string(&path));
+        };
+        path.push(PathSegment::Key(key.clone()));
+        target = o
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(BTreeMap::new()));
+        if let Value::Array(a) = target {
+            ensure!(
+                a.last().is_some_and(|v| matches!(v, Value::Object(_))),
+                "`{}` is not an array of tables",
+                path_to_string(&path)
+            );
+            path.push(PathSegment::Index(a.len() - 1));
+            target = a.last_mut().expect("checked above");
+        }
+    }
+    Ok(path)
+}
+
+fn open_table(root: &mut Value, keys: &[String]) -> Result<Vec<PathSegment>> {
+    let (last, parents) = keys.split_last().expect("keys are never empty");
+    let mut path = descend(root, parents)?;
+    let Value::Object(parent) = resolve_mut(root, &path) else {
+        bail!("`{}` is not a table", path_to_string(&path));
+    };
+    let table = parent
+        .entry(last.clone())
+        .or_insert_with(|| Value::Object(BTreeMap::new()));
+    path.push(PathSegment::Key(last.clone()));
+    ensure!(
+        matches!(table, Value::Object(_)),
+        "`{}` is already defined as {}",
+        path_to_string(&path),
+        table.type_name()
+    );
+    Ok(path)
+}
+
+fn append_table(root: &mut Value, keys: &[String]) -> Result<Vec<PathSegment>> {
+    let (last, parents) = keys.split_last().expect("keys are never empty");
+    let mut path = descend(root, parents)?;
+    let Value::Object(parent) = resolve_mut(root, &path) else {
+        bail!("`{}` is not a table", path_to_string(&path));
+    };
+    let array = parent
+        .entry(last.clone())
+        .or_insert_with(|| Value::Array(Vec::new()));
+    path.push(PathSegment::Key(last.clone()));
+    let Value::Array(array) = array else {
+        bail!("`{}` is not an array of tables", path_to_string(&path));
+    };
+    array.push(Value::Object(BTreeMap::new()));
+    path.push(PathSegment::Index(array.len() - 1));
+    Ok(path)
+}
+
+fn insert_dotted(table: &mut Value, keys: &[String], value: Value) -> Result<()> {
+    let (last, parents) = keys.split_last().expect("keys are never empty");
+    let mut target = table;
+    for key in parents {
+        let Value::Object(o) = target else {
+            bail!("cannot add key `{}` to a non-table", key);
+        };
+        target = o
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(BTreeMap::new()));
+    }
+    let Value::Object(o) = target else {
+        bail!("cannot add key `{}` to a non-table", last);
+    };
+    ensure!(
+        o.insert(last.clone(), value).is_none(),
+        "duplicate key `{}`",
+        last
+    );
+    Ok(())
+}
+
+pub fn to_string(value: &Value) -> Result<String> {
+    let Value::Object(root) = value else {
+        bail!("TOML documents must be tables, got {}", value.type_name());
+    };
+    let mut out = String::new();
+    write_table(&mut out, root, &mut Vec::new())?;
+    Ok(out)
+}
+
+fn is_array_of_tables(value: &Value) -> bool {
+    matches!(value, Value::Array(a) if !a.is_empty() && a.iter().all(|v| matches!(v, Value::Object(_))))
+}
+
+fn write_table(
+    out: &mut String,
+    table: &BTreeMap<String, Value>,
+    path: &mut Vec<PathSegment>,
+) -> Result<()> {
+    // plain keys must come before any sub-table headers
+    for (key, value) in table {
+        if matches!(value, Value::Object(_)) || is_array_of_tables(value) {
+            continue;
+        }
+        path.push(PathSegment::Key(key.clone()));
+        write_key(out, key);
+        out.push_str(" = ");
+        write_inline(out, value, path)?;
+        out.push('\n');
+        path.pop();
+    }
+
+    for (key, value) in table {
+        path.push(PathSegment::Key(key.clone()));
+        match value {
+            Value::Object(o) => {
+                out.push_str(if out.is_empty() { "[" } else { "\n[" });
+                write_header(out, path);
+                out.push_str("]\n");
+                write_table(out, o, path)?;
+            }
+            Value::Array(a) if is_array_of_tables(value) => {
+                for (i, item) in a.iter().enumerate() {
+                    let Value::Object(o) = item else {
+                        unreachable!("checked by is_array_of_tables")
+                    };
+                    out.push_str(if out.is_empty() { "[[" } else { "\n[[" });
+                    write_header(out, path);
+                    out.push_str("]]\n");
+                    path.push(PathSegment::Index(i));
+                    write_table(out, o, path)?;
+                    path.pop();
+                }
+            }
+            _ => (),
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+fn write_header(out: &mut String, path: &[PathSegment]) {
+    let keys: Vec<_> = path
+        .iter()
+        .filter_map(|segment| match segment {
+            PathSegment::Key(k) => Some(k),
+            PathSegment::Index(_) => None,
+        })
+        .collect();
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        write_key(out, key);
+    }
+}
+
+fn write_key(out: &mut String, key: &str) {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        out.push_str(key);
+    } else {
+        write_escaped(out, key).expect("writing to a String cannot fail");
+    }
+}
+
+fn write_inline(out: &mut String, value: &Value, path: &mut Vec<PathSegment>) -> Result<()> {
+    match value {
+        Value::Null => bail!(
+            "{} is null, which cannot be represented in TOML",
+            path_to_string(path)
+        ),
+        Value::Bool(b) => out.push_str(&b.to_string()),
+        Value::Number(Number::Float(f)) if f.is_nan() => out.push_str("nan"),
+        Value::Number(Number::Float(f)) if f.is_infinite() => {
+            out.push_str(if *f > 0.0 { "inf" } else { "-inf" })
+        }
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::String(s) => write_escaped(out, s).expect("writing to a String cannot fail"),
+        Value::Array(a) => {
+            out.push('[');
+            for (i, item) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                path.push(PathSegment::Index(i));
+                write_inline(out, item, path)?;
+                path.pop();
+            }
+            out.push(']');
+        }
+        Value::Object(o) => {
+            out.push('{');
+            for (i, (key, item)) in o.iter().enumerate() {
+                out.push_str(if i > 0 { ", " } else { " " });
+                write_key(out, key);
+                out.push_str(" = ");
+                path.push(PathSegment::Key(key.clone()));
+                write_inline(out, item, path)?;
+                path.pop();
+            }
+            out.push_str(if o.is_empty() { "}" } else { " }" });
+        }
+    }
+    Ok(())
+}
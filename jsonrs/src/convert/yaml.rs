@@ -0,0 +1,597 @@
+//! A practical subset of YAML 1.2.
+//!
+//! Reading supports block mappings and sequences, flow collections (`[a, b]`,
+//! `{a: 1}`), plain and quoted scalars, `|`/`>` block scalars, comments and a
+//! single document optionally introduced by `---`. Anchors, tags and multiple
+//! documents are rejected. Writing always produces block style.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::{
+    parser::unescape,
+    value::{write_escaped, Number, Value},
+};
+
+#[derive(Debug, Clone)]
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+pub fn from_str(input: &str) -> Result<Value> {
+    let mut lines = Vec::new();
+    for (i, raw) in input.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        if text.trim().is_empty() {
+            continue;
+        }
+        if text == "---" && lines.is_empty() {
+            continue;
+        }
+        ensure!(
+            text != "---" && text != "...",
+            "line {}: multiple documents are not supported",
+            i + 1
+        );
+        let indent = text.len() - text.trim_start().len();
+        ensure!(
+            !text[..indent].contains('\t'),
+            "line {}: tabs are not allowed for indentation",
+            i + 1
+        );
+        lines.push(Line {
+            number: i + 1,
+            indent,
+            text: text.trim_start().into(),
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    let mut p = BlockParser {
+        raw: input.lines().collect(),
+        lines,
+        position: 0,
+    };
+    let indent = p.lines[0].indent;
+    let value = p.parse_block(indent)?;
+    if let Some(line) = p.lines.get(p.position) {
+        bail!("line {}: unexpected content {:?}", line.number, line.text);
+    }
+    Ok(value)
+}
+
+/// Remove a trailing `# comment`, ignoring `#` inside quotes or words. A quote
+/// only opens a quoted scalar at the start of a value or flow item, so the
+/// apostrophe in `note: don't` does not.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    // the last character before the current run of whitespace
+    let mut indicator = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, '"' | '\'')
+                if (prev.is_whitespace() || "[{,".contains(prev))
+                    && indicator.is_none_or(|p| ":-?[{,".contains(p)) =>
+            {
+                quote = Some(c)
+            }
+            // `''` is an escaped quote inside a single-quoted scalar
+            (Some('\''), '\'') if chars.peek().is_some_and(|&(_, c)| c == '\'') => {
+                chars.next();
+            }
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if prev.is_whitespace() => return &line[..i],
+            _ => (),
+        }
+        if !c.is_whitespace() {
+            indicator = Some(c);
+        }
+        prev = c;
+    }
+    line
+}
+
+struct BlockParser<'a> {
+    raw: Vec<&'a str>,
+    lines: Vec<Line>,
+    position: usize,
+}
+
+impl BlockParser<'_> {
+    fn current(&self) -> Option<&Line> {
+        self.lines.get(self.position)
+    }
+
+    fn parse_block(&mut self, indent: usize) -> Result<Value> {
+        let line = self.current().expect("caller checks for a line").clone();
+        if is_sequence_item(&line.text) {
+            self.parse_sequence(line.indent)
+        } else if split_key(&line.text).is_some() {
+            self.parse_mapping(line.indent)
+        } else {
+            ensure!(
+                line.indent >= indent,
+                "line {}: bad indentation",
+                line.number
+            );
+            self.position += 1;
+            parse_inline(&line.text).map_err(|e| anyhow!("line {}: {}", line.number, e))
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Value> {
+        let mut items = Vec::new();
+        while let Some(line) = self.current().cloned() {
+            if line.indent != indent || !is_sequence_item(&line.text) {
+                ensure!(
+                    line.indent <= indent,
+                    "line {}: bad indentation",
+                    line.number
+                );
+                break;
+            }
+
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() {
+                self.position += 1;
+                items.push(self.parse_nested(indent)?);
+            } else {
+                // re-read the item's content as if it were on its own line, so
+                // `- key: value` starts a mapping indented past the dash
+                let offset = line.text.len() - rest.len();
+                self.lines[self.position] = Line {
+                    number: line.number,
+                    indent: indent + offset,
+                    text: rest.into(),
+                };
+                items.push(self.parse_value_at(indent + offset, indent)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Value> {
+        let mut object = BTreeMap::new();
+        while let Some(line) = self.current().cloned() {
+            if line.indent != indent {
+                ensure!(
+                    line.indent < indent,
+                    "line {}: bad indentation",
+                    line.number
+                );
+                break;
+            }
+            let (key, rest) = split_key(&line.text)
+                .ok_or_else(|| anyhow!("line {}: expected `key: value`", line.number))?;
+            let key = parse_inline(key).map_err(|e| anyhow!("line {}: {}", line.number, e))?;
+            let key = match key {
+                Value::String(s) => s,
+                Value::Null => "null".into(),
+                v => v.to_string(),
+            };
+            self.position += 1;
+
+            let value = match rest {
+                "" => match self.current() {
+                    // sequences may sit at the same indentation as their key
+                    Some(next) if next.indent == indent && is_sequence_item(&next.text) => {
+                        self.parse_sequence(indent)?
+                    }
+                    _ => self.parse_nested(indent)?,
+                },
+                "|" | "|-" | "|+" | ">" | ">-" | ">+" => self.parse_block_scalar(rest, indent)?,
+                rest => parse_inline(rest).map_err(|e| anyhow!("line {}: {}", line.number, e))?,
+            };
+            ensure!(
+                object.insert(key.clone(), value).is_none(),
+                "line {}: duplicate key {:?}",
+                line.number,
+                key
+            );
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// Parse a value at `indent` whose parent sits at `parent`.
+    fn parse_value_at(&mut self, indent: usize, parent: usize) -> Result<Value> {
+        let line = self.current().expect("caller checks for a line").clone();
+        if is_sequence_item(&line.text) || split_key(&line.text).is_some() {
+            return self.parse_block(indent);
+        }
+        self.position += 1;
+        let value = match line.text.as_str() {
+            t @ ("|" | "|-" | "|+" | ">" | ">-" | ">+") => self.parse_block_scalar(t, parent)?,
+            t => parse_inline(t).map_err(|e| anyhow!("line {}: {}", line.number, e))?,
+        };
+        Ok(value)
+    }
+
+    /// Parse the block nested under a line at `parent`, or `null` if none.
+    fn parse_nested(&mut self, parent: usize) -> Result<Value> {
+        match self.current() {
+            Some(next) if next.indent > parent => {
+                let indent = next.indent;
+                self.parse_block(indent)
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn parse_block_scalar(&mut self, header: &str, parent: usize) -> Result<Value> {
+        let Some(first) = self.current() else {
+            return Ok(Value::String(String::new()));
+        };
+        if first.indent <= parent {
+            return Ok(Value::String(String::new()));
+        }
+        let indent = first.indent;
+
+        // block scalars keep blank lines and `#`, so read the raw source lines
+        let start = first.number - 1;
+        let mut end = start;
+        let mut body = Vec::new();
+        for raw in &self.raw[start..] {
+            let leading = raw.len() - raw.trim_start().len();
+            if !raw.trim().is_empty() && leading < indent {
+                break;
+            }
+            body.push(raw.get(indent..).unwrap_or("").to_string());
+            end += 1;
+        }
+        while self.current().is_some_and(|l| l.number <= end) {
+            self.position += 1;
+        }
+
+        let keep = header.ends_with('+');
+        let strip = header.ends_with('-');
+        while !keep && body.last().is_some_and(|l| l.trim().is_empty()) {
+            body.pop();
+        }
+
+        let mut text = if header.starts_with('|') {
+            body.join("\n")
+        } else {
+            fold(&body)
+        };
+        if !strip {
+            text.push('\n');
+        }
+        Ok(Value::String(text))
+    }
+}
+
+/// Join folded (`>`) lines with spaces, keeping blank lines as newlines.
+fn fold(lines: &[String]) -> String {
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            out.push('\n');
+        } else {
+            if i > 0 && !lines[i - 1].is_empty() {
+                out.push(' ');
+            }
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Split `key: rest` at the first `:` followed by a space or end of line that
+/// is outside quotes and flow collections.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    if text.starts_with(['[', '{']) {
+        return None;
+    }
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if i == 0 => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ':') => {
+                let rest = &text[i + 1..];
+                if rest.is_empty() || rest.starts_with(' ') {
+                    return Some((text[..i].trim_end(), rest.trim()));
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parse a scalar or flow collection that fits on one line.
+fn parse_inline(text: &str) -> Result<Value> {
+    let mut p = FlowParser {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    let value = p.parse_value(false)?;
+    p.skip_whitespace();
+    ensure!(
+        p.position == p.chars.len(),
+        "unexpected trailing content in {:?}",
+        text
+    );
+    Ok(value)
+}
+
+struct FlowParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl FlowParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        ensure!(
+            self.peek() == Some(c),
+            "expected `{}`, got {:?}",
+            c,
+            self.peek()
+        );
+        self.position += 1;
+        Ok(())
+    }
+
+    fn parse_value(&mut self, in_flow: bool) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('[') => self.parse_sequence(),
+            Some('{') => self.parse_mapping(),
+            Some('"') => self.parse_double_quoted().map(Value::String),
+            Some('\'') => self.parse_single_quoted().map(Value::String),
+            Some('&' | '*' | '!') => bail!("anchors, aliases and tags are not supported"),
+            _ => resolve_plain(&self.parse_plain(in_flow)),
+        }
+    }
+
+    fn parse_sequence(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.position += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value(true)?);
+            self.skip_whitespace();
+            if self.peek() == Some(',') {
+                self.position += 1;
+            } else {
+                self.expect(']')?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn parse_mapping(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut object = BTreeMap::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.position += 1;
+                return Ok(Value::Object(object));
+            }
+            let key = match self.parse_value(true)? {
+                Value::String(s) => s,
+                v => v.to_string(),
+            };
+            self.expect(':')?;
+            let value = self.parse_value(true)?;
+            object.insert(key, value);
+            self.skip_whitespace();
+            if self.peek() == Some(',') {
+                self.position += 1;
+            } else {
+                self.expect('}')?;
+                return Ok(Value::Object(object));
+            }
+        }
+    }
+
+    fn parse_double_quoted(&mut self) -> Result<String> {
+        self.position += 1;
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    let raw: String = self.chars[start..self.position].iter().collect();
+                    self.position += 1;
+                    return unescape(&raw);
+                }
+                '\\' => self.position += 2,
+                _ => self.position += 1,
+            }
+        }
+        bail!("unterminated double-quoted string")
+    }
+
+    fn parse_single_quoted(&mut self) -> Result<String> {
+        self.position += 1;
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            self.position += 1;
+            if c == '\'' {
+                if self.peek() == Some('\'') {
+                    self.position += 1;
+                    out.push('\'');
+                    continue;
+                }
+                return Ok(out);
+            }
+            out.push(c);
+        }
+        bail!("unterminated single-quoted string")
+    }
+
+    fn parse_plain(&mut self, in_flow: bool) -> String {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            let next = self.chars.get(self.position + 1).copied();
+            let ends = if in_flow {
+                matches!(c, ',' | ']' | '}') || (c == ':' && next.is_none_or(|n| n == ' '))
+            } else {
+                false
+            };
+            if ends {
+                break;
+            }
+            self.position += 1;
+        }
+        self.chars[start..self.position]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .into()
+    }
+}
+
+/// Resolve a plain scalar using the YAML 1.2 core schema. Infinities and NaN
+/// have no JSON representation, so they are rejected rather than read as
+/// floats that would be written as `null`.
+fn resolve_plain(text: &str) -> Result<Value> {
+    let value = match text {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Bool(true),
+        "false" | "False" | "FALSE" => Value::Bool(false),
+        ".inf" | "+.inf" | ".Inf" | "+.Inf" | ".INF" | "+.INF" | "-.inf" | "-.Inf" | "-.INF"
+        | ".nan" | ".NaN" | ".NAN" => {
+            bail!("`{}` cannot be represented in JSON", text)
+        }
+        _ if is_number(text) => match text.parse() {
+            Ok(Number::Float(f)) if !f.is_finite() => bail!("number `{}` is out of range", text),
+            Ok(n) => Value::Number(n),
+            Err(_) => Value::String(text.into()),
+        },
+        _ => Value::String(text.into()),
+    };
+    Ok(value)
+}
+
+fn is_number(text: &str) -> bool {
+    let body = text.strip_prefix(['-', '+']).unwrap_or(text);
+    body.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && body.chars().any(|c| c.is_ascii_digit())
+        && body
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+'))
+}
+
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Array(a) if !a.is_empty() => write_sequence(&mut out, a, 0),
+        Value::Object(o) if !o.is_empty() => write_mapping(&mut out, o, 0),
+        v => {
+            write_scalar(&mut out, v);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn write_sequence(out: &mut String, items: &[Value], indent: usize) {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push('-');
+        match item {
+            Value::Array(a) if !a.is_empty() => {
+                out.push('\n');
+                write_sequence(out, a, indent + 2);
+            }
+            Value::Object(o) if !o.is_empty() => {
+                // the first entry shares the dash's line
+                let mut first = String::new();
+                write_mapping(&mut first, o, indent + 2);
+                out.push(' ');
+                out.push_str(&first[indent + 2..]);
+            }
+            v => {
+                out.push(' ');
+                write_scalar(out, v);
+                out.push('\n');
+            }
+        }
+    }
+}
+
+fn write_mapping(out: &mut String, object: &BTreeMap<String, Value>, indent: usize) {
+    for (key, value) in object {
+        out.push_str(&" ".repeat(indent));
+        write_string(out, key);
+        out.push(':');
+        match value {
+            Value::Array(a) if !a.is_empty() => {
+                out.push('\n');
+                write_sequence(out, a, indent + 2);
+            }
+            Value::Object(o) if !o.is_empty() => {
+                out.push('\n');
+                write_mapping(out, o, indent + 2);
+            }
+            v => {
+                out.push(' ');
+                write_scalar(out, v);
+                out.push('\n');
+            }
+        }
+    }
+}
+
+fn write_scalar(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Number(Number::Float(f)) if f.is_nan() => out.push_str(".nan"),
+        Value::Number(Number::Float(f)) if f.is_infinite() => {
+            out.push_str(if *f > 0.0 { ".inf" } else { "-.inf" })
+        }
+        Value::String(s) => write_string(out, s),
+        Value::Array(_) => out.push_str("[]"),
+        Value::Object(_) => out.push_str("{}"),
+        v => out.push_str(&v.to_string()),
+    }
+}
+
+/// Write a string plain when it would read back unchanged, quoted otherwise.
+fn write_string(out: &mut String, s: &str) {
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && matches!(resolve_plain(s), Ok(Value::String(_)))
+        && !s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with(':')
+        && !s.contains(|c: char| c.is_control());
+    if plain {
+        out.push_str(s);
+    } else {
+        write_escaped(out, s).expect("writing to a String cannot fail");
+    }
+}
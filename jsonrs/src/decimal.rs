@@ -0,0 +1,276 @@
+//! Exact decimal numbers of any size and precision.
+//!
+//! A [`BigDecimal`] is a string of decimal digits scaled by a power of ten, so
+//! it holds every JSON number exactly. It keeps the scale it was written with:
+//! `1.50` prints as `1.50` but compares equal to `1.5`, and [`normalize`]
+//! strips the trailing zeros. Conversions to machine numbers fail rather than
+//! overflow.
+//!
+//! [`normalize`]: BigDecimal::normalize
+
+use std::{cmp::Ordering, fmt, ops::Neg, str::FromStr};
+
+use anyhow::{bail, ensure, Context, Error, Result};
+
+/// Largest magnitude of an exponent, which keeps offset arithmetic in range.
+const MAX_EXPONENT: i64 = i64::MAX / 4;
+
+/// The number `(-1)^negative * digits * 10^exponent`.
+#[derive(Debug, Clone)]
+pub struct BigDecimal {
+    negative: bool,
+    /// ASCII digits without leading zeros, or a single `0`.
+    digits: String,
+    exponent: i64,
+}
+
+impl BigDecimal {
+    pub fn is_zero(&self) -> bool {
+        self.digits == "0"
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative && !self.is_zero()
+    }
+
+    pub fn abs(&self) -> Self {
+        BigDecimal {
+            negative: false,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the number has no fractional part.
+    pub fn is_integer(&self) -> bool {
+        let normal = self.normalize();
+        normal.exponent >= 0
+    }
+
+    /// The same number with trailing zeros moved into the exponent and zero
+    /// made positive, so that equal numbers have equal representations.
+    pub fn normalize(&self) -> Self {
+        if self.is_zero() {
+            return BigDecimal {
+                negative: false,
+                digits: "0".into(),
+                exponent: 0,
+            };
+        }
+        let digits = self.digits.trim_end_matches('0');
+        let zeros = self.digits.len() - digits.len();
+        BigDecimal {
+            negative: self.negative,
+            digits: digits.into(),
+            exponent: self.exponent + zeros as i64,
+        }
+    }
+
+    /// Convert to an `i64`, failing if the number has a fractional part or is
+    /// out of range.
+    pub fn to_i64(&self) -> Result<i64> {
+        let normal = self.normalize();
+        ensure!(normal.exponent >= 0, "{} has a fractional part", self);
+        if normal.is_zero() {
+            return Ok(0);
+        }
+        let width = normal.digits.len() as i64 + normal.exponent;
+        ensure!(width <= 19, "{} is out of range for i64", self);
+        let magnitude: i128 = format!("{}{}", normal.digits, "0".repeat(normal.exponent as usize))
+            .parse()
+            .expect("at most 19 digits fit in an i128");
+        let value = if normal.negative {
+            -magnitude
+        } else {
+            magnitude
+        };
+        i64::try_from(value)
+            .ok()
+            .context(format!("{} is out of range for i64", self))
+    }
+
+    /// Convert to the nearest `f64`, failing if the number is too large for
+    /// one. Numbers too small for an `f64` become zero.
+    pub fn to_f64(&self) -> Result<f64> {
+        let sign = if self.negative { "-" } else { "" };
+        let value: f64 = format!("{}{}e{}", sign, self.digits, self.exponent)
+            .parse()
+            .expect("digits and exponent form a float");
+        ensure!(value.is_finite(), "{} is out of range for f64", self);
+        Ok(value)
+    }
+
+    /// The sign, the digits without leading zeros and the power of ten they
+    /// are scaled by, as written: `-1.50` is `(true, "150", -2)`.
+    pub fn parts(&self) -> (bool, &str, i64) {
+        (self.is_negative(), &self.digits, self.exponent)
+    }
+
+    /// Position of the most significant digit, as a power of ten.
+    fn magnitude(&self) -> i64 {
+        self.digits.len() as i64 - 1 + self.exponent
+    }
+}
+
+impl FromStr for BigDecimal {
+    type Err = Error;
+
+    /// Parse an optionally signed decimal with an optional fraction and
+    /// exponent, such as `-12.50e-3`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || format!("invalid decimal {:?}", s);
+        let (negative, rest) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (mantissa, exponent) = match rest.find(['e', 'E']) {
+            Some(i) => {
+                let exponent = &rest[i + 1..];
+                let unsigned = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                ensure!(
+                    !unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit()),
+                    invalid()
+                );
+                let exponent: i64 = exponent
+                    .parse()
+                    .ok()
+                    .filter(|e: &i64| e.abs() <= MAX_EXPONENT)
+                    .with_context(|| format!("exponent out of range in {:?}", s))?;
+                (&rest[..i], exponent)
+            }
+            None => (rest, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        ensure!(!integer.is_empty() || !fraction.is_empty(), invalid());
+        ensure!(
+            integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit()),
+            invalid()
+        );
+
+        let digits = format!("{}{}", integer, fraction);
+        let digits = match digits.trim_start_matches('0') {
+            "" => "0",
+            digits => digits,
+        };
+        let exponent = match exponent - fraction.len() as i64 {
+            // there is nothing to scale up in zero
+            e if e > 0 && digits == "0" => 0,
+            e => e,
+        };
+        if exponent.abs() > MAX_EXPONENT {
+            bail!("exponent out of range in {:?}", s);
+        }
+        Ok(BigDecimal {
+            negative,
+            digits: digits.into(),
+            exponent,
+        })
+    }
+}
+
+impl fmt::Display for BigDecimal {
+    /// Plain notation for moderate exponents and scientific notation, with one
+    /// digit before the point, otherwise. Trailing zeros are kept.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("-")?;
+        }
+        let digits = &self.digits;
+        let len = digits.len() as i64;
+        let magnitude = self.magnitude();
+        if self.exponent <= 0 && magnitude >= -7 {
+            let point = len + self.exponent;
+            if self.exponent == 0 {
+                f.write_str(digits)
+            } else if point <= 0 {
+                write!(f, "0.{}{}", "0".repeat(-point as usize), digits)
+            } else {
+                let (integer, fraction) = digits.split_at(point as usize);
+                write!(f, "{}.{}", integer, fraction)
+            }
+        } else if self.exponent > 0 && len + self.exponent <= 21 {
+            write!(f, "{}{}", digits, "0".repeat(self.exponent as usize))
+        } else {
+            let (first, rest) = digits.split_at(1);
+            f.write_str(first)?;
+            if !rest.is_empty() {
+                write!(f, ".{}", rest)?;
+            }
+            write!(f, "e{}", magnitude)
+        }
+    }
+}
+
+impl Ord for BigDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = |d: &BigDecimal| match (d.is_zero(), d.negative) {
+            (true, _) => 0,
+            (false, true) => -1,
+            (false, false) => 1,
+        };
+        let by_sign = sign(self).cmp(&sign(other));
+        if by_sign != Ordering::Equal || self.is_zero() {
+            return by_sign;
+        }
+        // with equal magnitudes the digits are aligned at the most significant
+        // one, so the trimmed strings compare lexicographically
+        let by_size = self.magnitude().cmp(&other.magnitude()).then_with(|| {
+            let a = self.digits.trim_end_matches('0');
+            let b = other.digits.trim_end_matches('0');
+            a.cmp(b)
+        });
+        if self.negative {
+            by_size.reverse()
+        } else {
+            by_size
+        }
+    }
+}
+
+impl PartialOrd for BigDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for BigDecimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BigDecimal {}
+
+impl Neg for BigDecimal {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        BigDecimal {
+            negative: !self.negative,
+            ..self
+        }
+    }
+}
+
+impl From<i64> for BigDecimal {
+    fn from(value: i64) -> Self {
+        BigDecimal {
+            negative: value < 0,
+            digits: value.unsigned_abs().to_string(),
+            exponent: 0,
+        }
+    }
+}
+
+impl TryFrom<f64> for BigDecimal {
+    type Error = Error;
+
+    /// The shortest decimal that converts back to `value`.
+    fn try_from(value: f64) -> Result<Self> {
+        ensure!(value.is_finite(), "{} has no decimal representation", value);
+        format!("{:e}", value).parse()
+    }
+}
@@ -0,0 +1,116 @@
+//! Detection and decoding of the Unicode encodings JSON may arrive in.
+//!
+//! RFC 8259 requires UTF-8 for interchange, but files written by Windows tools
+//! are often UTF-16 and may start with a byte order mark. A BOM decides the
+//! encoding when present; otherwise the pattern of zero bytes among the first
+//! four does, as described in section 3 of RFC 4627, since the first two
+//! characters of a JSON text are always ASCII.
+
+use std::fmt;
+
+use anyhow::{bail, ensure, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf32Le => "UTF-32LE",
+            Encoding::Utf32Be => "UTF-32BE",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Detect the encoding of `bytes`, returning it with the length of its BOM.
+pub fn detect(bytes: &[u8]) -> (Encoding, usize) {
+    // UTF-32LE must be checked first, as its BOM starts with the UTF-16LE one
+    match bytes {
+        [0x00, 0x00, 0xfe, 0xff, ..] => return (Encoding::Utf32Be, 4),
+        [0xff, 0xfe, 0x00, 0x00, ..] => return (Encoding::Utf32Le, 4),
+        [0xef, 0xbb, 0xbf, ..] => return (Encoding::Utf8, 3),
+        [0xfe, 0xff, ..] => return (Encoding::Utf16Be, 2),
+        [0xff, 0xfe, ..] => return (Encoding::Utf16Le, 2),
+        _ => {}
+    }
+
+    let encoding = match bytes {
+        [0, 0, 0, b, ..] if *b != 0 => Encoding::Utf32Be,
+        [a, 0, 0, 0, ..] if *a != 0 => Encoding::Utf32Le,
+        [0, b, ..] if *b != 0 => Encoding::Utf16Be,
+        [a, 0, ..] if *a != 0 => Encoding::Utf16Le,
+        _ => Encoding::Utf8,
+    };
+    (encoding, 0)
+}
+
+/// Decode `bytes` to a string, dropping any BOM. Errors give the byte offset
+/// of the first invalid sequence in the original input.
+pub fn decode(bytes: &[u8]) -> Result<String> {
+    let (encoding, bom) = detect(bytes);
+    let body = &bytes[bom..];
+    match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(body) {
+            Ok(s) => Ok(s.into()),
+            Err(e) => bail!("invalid UTF-8 at byte {}", bom + e.valid_up_to()),
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            ensure!(
+                body.len().is_multiple_of(2),
+                "truncated {} input: odd number of bytes",
+                encoding
+            );
+            let units = body.chunks_exact(2).map(|c| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([c[0], c[1]]),
+                _ => u16::from_be_bytes([c[0], c[1]]),
+            });
+            let mut out = String::with_capacity(body.len() / 2);
+            let mut offset = bom;
+            for ch in char::decode_utf16(units) {
+                match ch {
+                    Ok(ch) => {
+                        out.push(ch);
+                        offset += ch.len_utf16() * 2;
+                    }
+                    Err(_) => bail!("unpaired surrogate in {} at byte {}", encoding, offset),
+                }
+            }
+            Ok(out)
+        }
+        Encoding::Utf32Le | Encoding::Utf32Be => {
+            ensure!(
+                body.len().is_multiple_of(4),
+                "truncated {} input: length is not a multiple of four",
+                encoding
+            );
+            let mut out = String::with_capacity(body.len() / 4);
+            for (i, c) in body.chunks_exact(4).enumerate() {
+                let c = [c[0], c[1], c[2], c[3]];
+                let scalar = match encoding {
+                    Encoding::Utf32Le => u32::from_le_bytes(c),
+                    _ => u32::from_be_bytes(c),
+                };
+                let Some(ch) = char::from_u32(scalar) else {
+                    bail!(
+                        "invalid code point {:#x} in {} at byte {}",
+                        scalar,
+                        encoding,
+                        bom + i * 4
+                    );
+                };
+                out.push(ch);
+            }
+            Ok(out)
+        }
+    }
+}
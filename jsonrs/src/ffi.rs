@@ -0,0 +1,160 @@
+//! C bindings, exported by the `cdylib` and `staticlib` builds.
+//!
+//! Text crosses the boundary as UTF-8 and every pointer these functions
+//! return is owned by the caller until it is passed to the matching `_free`
+//! function. `include/jsonrs.h` declares them for C; `tests/ffi.rs` checks
+//! that it is what cbindgen generates from this file and runs a C program
+//! against the library.
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr, slice,
+};
+
+use crate::{
+    parser::{parse_with_position, Position},
+    value::Value,
+};
+
+/// A parsed document, opaque to C.
+pub struct JsonrsDocument(Value);
+
+/// Why parsing failed. Lines and columns count from 1, columns in characters.
+#[repr(C)]
+pub struct JsonrsError {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    /// NUL-terminated message, freed by `jsonrs_error_free`.
+    pub message: *mut c_char,
+}
+
+/// Parse `len` bytes of UTF-8 at `text`. Returns NULL on failure and, if
+/// `error` is not NULL, describes the failure there.
+///
+/// # Safety
+///
+/// `text` must point to `len` readable bytes and `error` must be NULL or
+/// point to a writable `JsonrsError`.
+#[no_mangle]
+pub unsafe extern "C" fn jsonrs_parse(
+    text: *const c_char,
+    len: usize,
+    error: *mut JsonrsError,
+) -> *mut JsonrsDocument {
+    let bytes = if text.is_null() {
+        &[][..]
+    } else {
+        slice::from_raw_parts(text as *const u8, len)
+    };
+    let result = match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            parse_with_position(text).map_err(|(e, position)| (format!("{:#}", e), position))
+        }
+        Err(e) => {
+            let valid = std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default();
+            let position = Position::of(valid, valid.len());
+            Err((
+                format!("invalid UTF-8 at byte {}", position.offset),
+                position,
+            ))
+        }
+    };
+    match result {
+        Ok(value) => Box::into_raw(Box::new(JsonrsDocument(value))),
+        Err((message, position)) => {
+            if let Some(error) = error.as_mut() {
+                *error = JsonrsError {
+                    line: position.line,
+                    column: position.column,
+                    offset: position.offset,
+                    message: into_c_string(message),
+                };
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a document returned by `jsonrs_parse`. NULL is ignored.
+///
+/// # Safety
+///
+/// `document` must be NULL or a pointer from `jsonrs_parse` that has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn jsonrs_document_free(document: *mut JsonrsDocument) {
+    if !document.is_null() {
+        drop(Box::from_raw(document));
+    }
+}
+
+/// The value at the RFC 6901 JSON Pointer `pointer` as compact JSON, or NULL
+/// if there is none. The result is freed by `jsonrs_string_free`.
+///
+/// # Safety
+///
+/// `document` must be a live pointer from `jsonrs_parse` and `pointer` a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn jsonrs_pointer(
+    document: *const JsonrsDocument,
+    pointer: *const c_char,
+) -> *mut c_char {
+    let (Some(document), false) = (document.as_ref(), pointer.is_null()) else {
+        return ptr::null_mut();
+    };
+    let Ok(pointer) = CStr::from_ptr(pointer).to_str() else {
+        return ptr::null_mut();
+    };
+    match document.0.pointer(pointer) {
+        Some(value) => into_c_string(value.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The whole document as compact JSON, freed by `jsonrs_string_free`.
+///
+/// # Safety
+///
+/// `document` must be a live pointer from `jsonrs_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn jsonrs_to_string(document: *const JsonrsDocument) -> *mut c_char {
+    match document.as_ref() {
+        Some(document) => into_c_string(document.0.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Free a string returned by this library. NULL is ignored.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string from this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn jsonrs_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Free the message of an error filled in by `jsonrs_parse` and set it to
+/// NULL, so freeing twice is harmless.
+///
+/// # Safety
+///
+/// `error` must be NULL or point to a `JsonrsError` filled in by
+/// `jsonrs_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn jsonrs_error_free(error: *mut JsonrsError) {
+    if let Some(error) = error.as_mut() {
+        jsonrs_string_free(error.message);
+        error.message = ptr::null_mut();
+    }
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // JSON output escapes NUL, so only messages can contain one
+    let s = CString::new(s.replace('\0', "\\0")).expect("NUL bytes were replaced");
+    s.into_raw()
+}
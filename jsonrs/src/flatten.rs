@@ -0,0 +1,300 @@
+//! Conversion between nested documents and flat maps of paths to leaves.
+//!
+//! Leaves are scalars and empty arrays or objects, so that `unflatten`
+//! restores exactly what `flatten` produced. Keys that contain the separator
+//! cannot be told apart from nesting in dotted paths, and all-digit keys from
+//! array indices; use JSON Pointer paths when keys are arbitrary. There a
+//! container is only taken to be an array when its tokens are exactly `0` to
+//! `n - 1`, so the one ambiguity left is an object with just those keys.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, ensure, Result};
+
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum IndexStyle {
+    /// `items.0.name`
+    #[default]
+    Separator,
+    /// `items[0].name`
+    Brackets,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlattenOptions {
+    /// Joins object keys in dotted paths.
+    pub separator: String,
+    /// How array indices appear in dotted paths.
+    pub index_style: IndexStyle,
+    /// Use RFC 6901 JSON Pointers such as `/items/0/name` instead of dotted
+    /// paths. The separator and index style are ignored.
+    pub pointer: bool,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions {
+            separator: ".".into(),
+            index_style: IndexStyle::Separator,
+            pointer: false,
+        }
+    }
+}
+
+/// How many array elements `unflatten` may create beyond one per path, to
+/// fill gaps with nulls. Without a limit a single path such as
+/// `a.4000000000` would need gigabytes.
+pub const MAX_GAP: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Flatten `value` into a map from paths to leaves.
+pub fn flatten(value: &Value, options: &FlattenOptions) -> BTreeMap<String, Value> {
+    let mut out = BTreeMap::new();
+    walk(value, &mut Vec::new(), options, &mut out);
+    out
+}
+
+fn walk(
+    value: &Value,
+    path: &mut Vec<Segment>,
+    options: &FlattenOptions,
+    out: &mut BTreeMap<String, Value>,
+) {
+    match value {
+        Value::Array(a) if !a.is_empty() => {
+            for (i, item) in a.iter().enumerate() {
+                path.push(Segment::Index(i));
+                walk(item, path, options, out);
+                path.pop();
+            }
+        }
+        Value::Object(o) if !o.is_empty() => {
+            for (key, item) in o {
+                path.push(Segment::Key(key.clone()));
+                walk(item, path, options, out);
+                path.pop();
+            }
+        }
+        leaf => {
+            out.insert(join(path, options), leaf.clone());
+        }
+    }
+}
+
+fn join(path: &[Segment], options: &FlattenOptions) -> String {
+    let mut out = String::new();
+    for (i, segment) in path.iter().enumerate() {
+        match segment {
+            _ if options.pointer => {
+                out.push('/');
+                match segment {
+                    Segment::Key(k) => out.push_str(&k.replace('~', "~0").replace('/', "~1")),
+                    Segment::Index(n) => out.push_str(&n.to_string()),
+                }
+            }
+            Segment::Index(n) if options.index_style == IndexStyle::Brackets => {
+                out.push_str(&format!("[{}]", n));
+            }
+            Segment::Key(k) => {
+                if i > 0 {
+                    out.push_str(&options.separator);
+                }
+                out.push_str(k);
+            }
+            Segment::Index(n) => {
+                if i > 0 {
+                    out.push_str(&options.separator);
+                }
+                out.push_str(&n.to_string());
+            }
+        }
+    }
+    out
+}
+
+/// Split a dotted path back into segments. Without brackets, all-digit
+/// segments are taken to be array indices.
+fn split(path: &str, options: &FlattenOptions) -> Result<Vec<Segment>> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    ensure!(!options.separator.is_empty(), "separator must not be empty");
+    let mut segments = Vec::new();
+    for part in path.split(options.separator.as_str()) {
+        if options.index_style == IndexStyle::Separator {
+            segments.push(index_or_key(part.into()));
+            continue;
+        }
+        let (key, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !key.is_empty() || rest.is_empty() {
+            segments.push(Segment::Key(key.into()));
+        }
+        while let Some(inner) = rest.strip_prefix('[') {
+            let Some(end) = inner.find(']') else {
+                bail!("unclosed `[` in path {:?}", path);
+            };
+            let Ok(index) = inner[..end].parse() else {
+                bail!("invalid array index {:?} in path {:?}", &inner[..end], path);
+            };
+            segments.push(Segment::Index(index));
+            rest = &inner[end + 1..];
+        }
+        ensure!(
+            rest.is_empty(),
+            "unexpected {:?} after index in path {:?}",
+            rest,
+            path
+        );
+    }
+    Ok(segments)
+}
+
+fn index_or_key(token: String) -> Segment {
+    match as_index(&token) {
+        Some(i) => Segment::Index(i),
+        None => Segment::Key(token),
+    }
+}
+
+/// The array index written as `token`, without leading zeros.
+fn as_index(token: &str) -> Option<usize> {
+    let is_index = !token.is_empty()
+        && token.chars().all(|c| c.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    token.parse().ok().filter(|_| is_index)
+}
+
+/// Split JSON Pointers into segments. An all-digit token is an index only if
+/// the tokens below the same prefix are exactly `0` to `n - 1`, as they are
+/// for every array `flatten` writes, so numeric keys such as years stay keys.
+fn split_pointers<'a>(paths: impl Iterator<Item = &'a String>) -> Result<Vec<Vec<Segment>>> {
+    let mut tokens = Vec::new();
+    for path in paths {
+        if path.is_empty() {
+            tokens.push(Vec::new());
+            continue;
+        }
+        ensure!(
+            path.starts_with('/'),
+            "JSON Pointer {:?} must start with `/`",
+            path
+        );
+        tokens.push(
+            path[1..]
+                .split('/')
+                .map(|token| token.replace("~1", "/").replace("~0", "~"))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let mut children: BTreeMap<&[String], BTreeSet<&str>> = BTreeMap::new();
+    for path in &tokens {
+        for i in 0..path.len() {
+            children.entry(&path[..i]).or_default().insert(&path[i]);
+        }
+    }
+    let arrays: BTreeSet<&[String]> = children
+        .into_iter()
+        .filter(|(_, tokens)| {
+            let indices: BTreeSet<usize> = tokens.iter().filter_map(|t| as_index(t)).collect();
+            indices.len() == tokens.len() && indices.last() == Some(&(tokens.len() - 1))
+        })
+        .map(|(prefix, _)| prefix)
+        .collect();
+
+    Ok(tokens
+        .iter()
+        .map(|path| {
+            path.iter()
+                .enumerate()
+                .map(|(i, token)| match as_index(token) {
+                    Some(index) if arrays.contains(&path[..i]) => Segment::Index(index),
+                    _ => Segment::Key(token.clone()),
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Rebuild a nested document from a map of paths to leaves. Arrays in dotted
+/// paths may have gaps, which are filled with nulls, but all arrays together
+/// hold at most [`MAX_GAP`] more elements than there are paths.
+pub fn unflatten(flat: &BTreeMap<String, Value>, options: &FlattenOptions) -> Result<Value> {
+    let segments = if options.pointer {
+        split_pointers(flat.keys())?
+    } else {
+        flat.keys()
+            .map(|path| split(path, options))
+            .collect::<Result<_>>()?
+    };
+    let mut root = None;
+    let mut room = flat.len() + MAX_GAP;
+    for ((path, leaf), segments) in flat.iter().zip(segments) {
+        insert(&mut root, &segments, leaf.clone(), path, &mut room)?;
+    }
+    Ok(root.unwrap_or(Value::Object(BTreeMap::new())))
+}
+
+/// Place `leaf` at `segments` below `slot`, creating containers on the way.
+/// `None` marks a slot that has not been written yet. `room` is how many
+/// more array elements may be created.
+fn insert(
+    slot: &mut Option<Value>,
+    segments: &[Segment],
+    leaf: Value,
+    path: &str,
+    room: &mut usize,
+) -> Result<()> {
+    let Some((first, rest)) = segments.split_first() else {
+        ensure!(slot.is_none(), "path {:?} is set more than once", path);
+        *slot = Some(leaf);
+        return Ok(());
+    };
+
+    let container = slot.get_or_insert_with(|| match first {
+        Segment::Key(_) => Value::Object(BTreeMap::new()),
+        Segment::Index(_) => Value::Array(Vec::new()),
+    });
+
+    match (container, first) {
+        (Value::Object(o), Segment::Key(k)) => {
+            let mut child = o.remove(k);
+            insert(&mut child, rest, leaf, path, room)?;
+            o.insert(k.clone(), child.expect("insert always fills the slot"));
+        }
+        (Value::Array(a), Segment::Index(i)) => {
+            if a.len() <= *i {
+                ensure!(
+                    i - a.len() < *room,
+                    "array index {} in path {:?} is too large",
+                    i,
+                    path
+                );
+                let grow = i - a.len() + 1;
+                *room -= grow;
+                a.resize(a.len() + grow, Value::Null);
+            }
+            // gaps are filled with nulls, which may be overwritten later
+            let mut child = match std::mem::replace(&mut a[*i], Value::Null) {
+                Value::Null => None,
+                v => Some(v),
+            };
+            insert(&mut child, rest, leaf, path, room)?;
+            a[*i] = child.expect("insert always fills the slot");
+        }
+        (container, _) => bail!(
+            "path {:?} conflicts with an existing {}",
+            path,
+            container.type_name()
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! A small jq-style filter language over [`Value`].
+//!
+//! Supported syntax covers paths (`.a.b[0]`, `.[]`, `.[1:3]`, `..`), pipes
+//! and commas, array and object construction, arithmetic, comparison,
+//! `and`/`or`/`//`, `if`/`elif`/`else`/`end` and a handful of builtins such as
+//! `map`, `select`, `keys` and `length`.
+
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+
+use crate::value::Value;
+
+mod interpreter;
+mod parser;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// `.`
+    Identity,
+    /// `..`
+    Recurse,
+    Literal(Value),
+    /// `term[index]`, including the `.foo` shorthand.
+    Index(Box<Filter>, Box<Filter>),
+    /// `term[from:to]`
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    /// `term[]`
+    Iterate(Box<Filter>),
+    /// `term?`
+    Optional(Box<Filter>),
+    /// `[f]`, or `[]` when empty.
+    Array(Option<Box<Filter>>),
+    /// `{k: v, ...}` as a list of key and value filters.
+    Object(Vec<(Filter, Filter)>),
+    Neg(Box<Filter>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Binary(BinOp, Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    /// `a // b`
+    Alternative(Box<Filter>, Box<Filter>),
+    /// `if c then t else e end`, with `elif` desugared into nested `If`s.
+    If(Box<Filter>, Box<Filter>, Option<Box<Filter>>),
+    /// A builtin such as `length` or `map(f)`.
+    Call(String, Vec<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Filter {
+    /// Run the filter against `input`, collecting every output.
+    pub fn run(&self, input: &Value) -> Result<Vec<Value>> {
+        interpreter::eval(self, input)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parser::parse(s)
+    }
+}
@@ -0,0 +1,461 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use super::{BinOp, Filter};
+use crate::value::{Number, Value};
+
+/// Evaluate `filter` against `input`, returning the stream of outputs.
+pub fn eval(filter: &Filter, input: &Value) -> Result<Vec<Value>> {
+    let out = match filter {
+        Filter::Identity => vec![input.clone()],
+        Filter::Recurse => {
+            let mut out = Vec::new();
+            recurse(input, &mut out);
+            out
+        }
+        Filter::Literal(v) => vec![v.clone()],
+        Filter::Index(term, index) => {
+            let mut out = Vec::new();
+            for target in eval(term, input)? {
+                for key in eval(index, input)? {
+                    out.push(index_value(&target, &key)?);
+                }
+            }
+            out
+        }
+        Filter::Slice(term, from, to) => {
+            let bounds = |f: &Option<Box<Filter>>| -> Result<Vec<Value>> {
+                match f {
+                    Some(f) => eval(f, input),
+                    None => Ok(vec![Value::Null]),
+                }
+            };
+            let mut out = Vec::new();
+            for target in eval(term, input)? {
+                for from in bounds(from)? {
+                    for to in bounds(to)? {
+                        out.push(slice_value(&target, &from, &to)?);
+                    }
+                }
+            }
+            out
+        }
+        Filter::Iterate(term) => {
+            let mut out = Vec::new();
+            for target in eval(term, input)? {
+                out.extend(iterate(&target)?);
+            }
+            out
+        }
+        Filter::Optional(term) => eval(term, input).unwrap_or_default(),
+        Filter::Array(None) => vec![Value::Array(Vec::new())],
+        Filter::Array(Some(inner)) => vec![Value::Array(eval(inner, input)?)],
+        Filter::Object(entries) => {
+            let mut objects = vec![BTreeMap::new()];
+            for (key, value) in entries {
+                let keys = eval(key, input)?;
+                let values = eval(value, input)?;
+                let mut next = Vec::new();
+                for object in &objects {
+                    for k in &keys {
+                        let Value::String(k) = k else {
+                            bail!("object keys must be strings, got {}", k.type_name());
+                        };
+                        for v in &values {
+                            let mut object = object.clone();
+                            object.insert(k.clone(), v.clone());
+                            next.push(object);
+                        }
+                    }
+                }
+                objects = next;
+            }
+            objects.into_iter().map(Value::Object).collect()
+        }
+        Filter::Neg(inner) => eval(inner, input)?
+            .into_iter()
+            .map(|v| match v {
+                Value::Number(n) => Ok(Value::Number(negate(n))),
+                v => Err(anyhow!("{} cannot be negated", v.type_name())),
+            })
+            .collect::<Result<_>>()?,
+        Filter::Pipe(lhs, rhs) => {
+            let mut out = Vec::new();
+            for v in eval(lhs, input)? {
+                out.extend(eval(rhs, &v)?);
+            }
+            out
+        }
+        Filter::Comma(lhs, rhs) => {
+            let mut out = eval(lhs, input)?;
+            out.extend(eval(rhs, input)?);
+            out
+        }
+        Filter::Binary(op, lhs, rhs) => {
+            let mut out = Vec::new();
+            for r in eval(rhs, input)? {
+                for l in eval(lhs, input)? {
+                    out.push(binary(*op, &l, &r)?);
+                }
+            }
+            out
+        }
+        Filter::And(lhs, rhs) => {
+            let mut out = Vec::new();
+            for l in eval(lhs, input)? {
+                if !truthy(&l) {
+                    out.push(Value::Bool(false));
+                    continue;
+                }
+                for r in eval(rhs, input)? {
+                    out.push(Value::Bool(truthy(&r)));
+                }
+            }
+            out
+        }
+        Filter::Or(lhs, rhs) => {
+            let mut out = Vec::new();
+            for l in eval(lhs, input)? {
+                if truthy(&l) {
+                    out.push(Value::Bool(true));
+                    continue;
+                }
+                for r in eval(rhs, input)? {
+                    out.push(Value::Bool(truthy(&r)));
+                }
+            }
+            out
+        }
+        Filter::Alternative(lhs, rhs) => {
+            let out: Vec<_> = eval(lhs, input)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(truthy)
+                .collect();
+            if out.is_empty() {
+                eval(rhs, input)?
+            } else {
+                out
+            }
+        }
+        Filter::If(cond, then, otherwise) => {
+            let mut out = Vec::new();
+            for c in eval(cond, input)? {
+                if truthy(&c) {
+                    out.extend(eval(then, input)?);
+                } else if let Some(otherwise) = otherwise {
+                    out.extend(eval(otherwise, input)?);
+                } else {
+                    out.push(input.clone());
+                }
+            }
+            out
+        }
+        Filter::Call(name, args) => call(name, args, input)?,
+    };
+
+    Ok(out)
+}
+
+fn call(name: &str, args: &[Filter], input: &Value) -> Result<Vec<Value>> {
+    let out = match (name, args) {
+        ("empty", []) => vec![],
+        ("not", []) => vec![Value::Bool(!truthy(input))],
+        ("type", []) => vec![Value::String(input.type_name().into())],
+        ("length", []) => vec![length(input)?],
+        ("keys", []) => vec![keys(input)?],
+        ("add", []) => {
+            let mut acc = Value::Null;
+            for v in iterate(input)? {
+                acc = binary(BinOp::Add, &acc, &v)?;
+            }
+            vec![acc]
+        }
+        ("sort", []) => match input {
+            Value::Array(a) => {
+                let mut sorted = a.clone();
+                sorted.sort_by(compare);
+                vec![Value::Array(sorted)]
+            }
+            v => bail!("{} cannot be sorted, as it is not an array", v.type_name()),
+        },
+        ("tostring", []) => match input {
+            Value::String(_) => vec![input.clone()],
+            v => vec![Value::String(v.to_string())],
+        },
+        ("tonumber", []) => match input {
+            Value::Number(_) => vec![input.clone()],
+            Value::String(s) => vec![Value::Number(s.trim().parse()?)],
+            v => bail!("{} cannot be parsed as a number", v.type_name()),
+        },
+        ("map", [f]) => {
+            let mut out = Vec::new();
+            for v in iterate(input)? {
+                out.extend(eval(f, &v)?);
+            }
+            vec![Value::Array(out)]
+        }
+        ("select", [f]) => eval(f, input)?
+            .iter()
+            .filter(|v| truthy(v))
+            .map(|_| input.clone())
+            .collect(),
+        ("has", [f]) => eval(f, input)?
+            .iter()
+            .map(|key| match (input, key) {
+                (Value::Object(o), Value::String(k)) => Ok(Value::Bool(o.contains_key(k))),
+                (Value::Array(a), Value::Number(n)) => Ok(Value::Bool(
+                    n.as_f64() >= 0.0 && n.as_f64() < a.len() as f64,
+                )),
+                (v, k) => Err(anyhow!(
+                    "cannot check whether {} has a {} key",
+                    v.type_name(),
+                    k.type_name()
+                )),
+            })
+            .collect::<Result<_>>()?,
+        (name, args) => bail!("{}/{} is not defined", name, args.len()),
+    };
+
+    Ok(out)
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+fn recurse(value: &Value, out: &mut Vec<Value>) {
+    out.push(value.clone());
+    match value {
+        Value::Array(a) => a.iter().for_each(|v| recurse(v, out)),
+        Value::Object(o) => o.values().for_each(|v| recurse(v, out)),
+        _ => (),
+    }
+}
+
+fn iterate(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(a) => Ok(a.clone()),
+        Value::Object(o) => Ok(o.values().cloned().collect()),
+        v => bail!("cannot iterate over {}", v.type_name()),
+    }
+}
+
+fn index_value(target: &Value, key: &Value) -> Result<Value> {
+    let out = match (target, key) {
+        (Value::Null, Value::String(_) | Value::Number(_)) => Value::Null,
+        (Value::Object(o), Value::String(k)) => o.get(k).cloned().unwrap_or(Value::Null),
+        (Value::Array(a), Value::Number(n)) => resolve_index(n.as_f64().floor(), a.len())
+            .and_then(|i| a.get(i).cloned())
+            .unwrap_or(Value::Null),
+        (t, k) => bail!("cannot index {} with {}", t.type_name(), k.type_name()),
+    };
+    Ok(out)
+}
+
+/// Turn a possibly negative index into an offset from the start.
+fn resolve_index(index: f64, len: usize) -> Option<usize> {
+    let index = if index < 0.0 {
+        len as f64 + index
+    } else {
+        index
+    };
+    (index >= 0.0).then_some(index as usize)
+}
+
+fn slice_value(target: &Value, from: &Value, to: &Value) -> Result<Value> {
+    let len = match target {
+        Value::Null => return Ok(Value::Null),
+        Value::Array(a) => a.len(),
+        Value::String(s) => s.chars().count(),
+        t => bail!("cannot slice {}", t.type_name()),
+    };
+    let bound = |v: &Value, default: usize| -> Result<usize> {
+        match v {
+            Value::Null => Ok(default),
+            Value::Number(n) => Ok(resolve_index(n.as_f64().floor(), len).unwrap_or(0).min(len)),
+            v => bail!("slice indices must be numbers, got {}", v.type_name()),
+        }
+    };
+    let from = bound(from, 0)?;
+    let to = bound(to, len)?.max(from);
+
+    Ok(match target {
+        Value::Array(a) => Value::Array(a[from..to].to_vec()),
+        Value::String(s) => Value::String(s.chars().skip(from).take(to - from).collect()),
+        _ => unreachable!(),
+    })
+}
+
+fn length(value: &Value) -> Result<Value> {
+    let len = match value {
+        Value::Null => 0,
+        Value::Number(n) => return Ok(Value::Number(abs(n))),
+        Value::String(s) => s.chars().count(),
+        Value::Array(a) => a.len(),
+        Value::Object(o) => o.len(),
+        Value::Bool(_) => bail!("boolean has no length"),
+    };
+    Ok(Value::Number(Number::Int(len as i64)))
+}
+
+fn keys(value: &Value) -> Result<Value> {
+    match value {
+        Value::Object(o) => Ok(Value::Array(
+            o.keys().map(|k| Value::String(k.clone())).collect(),
+        )),
+        Value::Array(a) => Ok(Value::Array(
+            (0..a.len() as i64)
+                .map(|i| Value::Number(i.into()))
+                .collect(),
+        )),
+        v => bail!("{} has no keys", v.type_name()),
+    }
+}
+
+fn binary(op: BinOp, lhs: &Value, rhs: &Value) -> Result<Value> {
+    let out = match op {
+        BinOp::Eq => Value::Bool(compare(lhs, rhs) == Ordering::Equal),
+        BinOp::Ne => Value::Bool(compare(lhs, rhs) != Ordering::Equal),
+        BinOp::Lt => Value::Bool(compare(lhs, rhs) == Ordering::Less),
+        BinOp::Le => Value::Bool(compare(lhs, rhs) != Ordering::Greater),
+        BinOp::Gt => Value::Bool(compare(lhs, rhs) == Ordering::Greater),
+        BinOp::Ge => Value::Bool(compare(lhs, rhs) != Ordering::Less),
+        BinOp::Add => match (lhs, rhs) {
+            (Value::Null, v) | (v, Value::Null) => v.clone(),
+            (Value::Number(a), Value::Number(b)) => Value::Number(arithmetic(op, a, b)?),
+            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+            (Value::Array(a), Value::Array(b)) => {
+                Value::Array(a.iter().chain(b.iter()).cloned().collect())
+            }
+            (Value::Object(a), Value::Object(b)) => {
+                let mut merged = a.clone();
+                merged.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+                Value::Object(merged)
+            }
+            (a, b) => bail!("{} and {} cannot be added", a.type_name(), b.type_name()),
+        },
+        BinOp::Sub => match (lhs, rhs) {
+            (Value::Number(a), Value::Number(b)) => Value::Number(arithmetic(op, a, b)?),
+            (Value::Array(a), Value::Array(b)) => {
+                Value::Array(a.iter().filter(|v| !b.contains(v)).cloned().collect())
+            }
+            (a, b) => bail!(
+                "{} and {} cannot be subtracted",
+                a.type_name(),
+                b.type_name()
+            ),
+        },
+        BinOp::Div => match (lhs, rhs) {
+            (Value::Number(a), Value::Number(b)) => Value::Number(arithmetic(op, a, b)?),
+            (Value::String(a), Value::String(b)) => {
+                Value::Array(a.split(b.as_str()).map(Value::from).collect())
+            }
+            (a, b) => bail!("{} and {} cannot be divided", a.type_name(), b.type_name()),
+        },
+        BinOp::Mul | BinOp::Rem => match (lhs, rhs) {
+            (Value::Number(a), Value::Number(b)) => Value::Number(arithmetic(op, a, b)?),
+            (a, b) => bail!(
+                "{} and {} cannot be combined with {:?}",
+                a.type_name(),
+                b.type_name(),
+                op
+            ),
+        },
+    };
+    Ok(out)
+}
+
+/// Integer arithmetic where it is exact, falling back to floats otherwise,
+/// including for exact decimals.
+fn arithmetic(op: BinOp, a: &Number, b: &Number) -> Result<Number> {
+    if let (&Number::Int(a), &Number::Int(b)) = (a, b) {
+        let exact = match op {
+            BinOp::Add => a.checked_add(b),
+            BinOp::Sub => a.checked_sub(b),
+            BinOp::Mul => a.checked_mul(b),
+            BinOp::Div if b != 0 && a % b == 0 => a.checked_div(b),
+            BinOp::Rem => {
+                ensure!(b != 0, "{} cannot be divided by zero", a);
+                a.checked_rem(b)
+            }
+            _ => None,
+        };
+        if let Some(n) = exact {
+            return Ok(Number::Int(n));
+        }
+    }
+
+    let (x, y) = (a.as_f64(), b.as_f64());
+    let out = match op {
+        BinOp::Add => x + y,
+        BinOp::Sub => x - y,
+        BinOp::Mul => x * y,
+        BinOp::Div => {
+            ensure!(y != 0.0, "{} cannot be divided by zero", a);
+            x / y
+        }
+        BinOp::Rem => {
+            ensure!(y.trunc() != 0.0, "{} cannot be divided by zero", a);
+            x.trunc() % y.trunc()
+        }
+        _ => unreachable!("not an arithmetic operator"),
+    };
+    Ok(Number::Float(out))
+}
+
+fn negate(n: Number) -> Number {
+    match n {
+        Number::Int(i) => i
+            .checked_neg()
+            .map_or(Number::Float(-(i as f64)), Number::Int),
+        Number::Float(f) => Number::Float(-f),
+        Number::Decimal(d) => Number::Decimal(-d),
+    }
+}
+
+fn abs(n: &Number) -> Number {
+    match *n {
+        Number::Int(i) => i
+            .checked_abs()
+            .map_or(Number::Float((i as f64).abs()), Number::Int),
+        Number::Float(f) => Number::Float(f.abs()),
+        Number::Decimal(ref d) => Number::Decimal(d.abs()),
+    }
+}
+
+/// Total ordering used by jq: null < false < true < numbers < strings <
+/// arrays < objects.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.partial_cmp(y).unwrap_or(Ordering::Less),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => x
+            .iter()
+            .zip(y.iter())
+            .map(|(x, y)| compare(x, y))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (Value::Object(x), Value::Object(y)) => x.keys().cmp(y.keys()).then_with(|| {
+            x.values()
+                .zip(y.values())
+                .map(|(x, y)| compare(x, y))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        }),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
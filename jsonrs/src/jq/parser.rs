@@ -0,0 +1,451 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+
+use super::{BinOp, Filter};
+use crate::{
+    parser::unescape,
+    value::{Number, Value},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    DotDot,
+    Field(String),
+    Ident(String),
+    Num(Number),
+    Str(String),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: [&str; 23] = [
+    "==", "!=", "<=", ">=", "//", "[", "]", "{", "}", "(", ")", "|", ",", ":", ";", "?", "+", "-",
+    "*", "/", "%", "<", ">",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '.' {
+            if chars.get(i + 1) == Some(&'.') {
+                tokens.push(Token::DotDot);
+                i += 2;
+            } else if chars.get(i + 1).is_some_and(|&c| is_ident_start(c)) {
+                let (ident, end) = read_ident(&chars, i + 1);
+                tokens.push(Token::Field(ident));
+                i = end;
+            } else {
+                tokens.push(Token::Dot);
+                i += 1;
+            }
+        } else if is_ident_start(c) {
+            let (ident, end) = read_ident(&chars, i);
+            tokens.push(Token::Ident(ident));
+            i = end;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || chars[i] == '.'
+                    || matches!(chars[i], 'e' | 'E')
+                    || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(literal.parse()?));
+        } else if c == '"' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            ensure!(i < chars.len(), "unterminated string literal in filter");
+            let raw: String = chars[start..i].iter().collect();
+            tokens.push(Token::Str(unescape(&raw)?));
+            i += 1;
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .ok_or_else(|| anyhow!("unexpected character `{}` in filter", c))?;
+            tokens.push(Token::Punct(punct));
+            i += punct.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn read_ident(chars: &[char], start: usize) -> (String, usize) {
+    let mut end = start;
+    while end < chars.len() && (is_ident_start(chars[end]) || chars[end].is_ascii_digit()) {
+        end += 1;
+    }
+    (chars[start..end].iter().collect(), end)
+}
+
+/// Parse a filter expression into its syntax tree.
+pub fn parse(input: &str) -> Result<Filter> {
+    let mut p = FilterParser {
+        tokens: tokenize(input)?,
+        position: 0,
+    };
+    let filter = p.parse_pipe()?;
+    if let Some(tok) = p.peek() {
+        bail!("unexpected token {:?} in filter", tok);
+    }
+    Ok(filter)
+}
+
+struct FilterParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl FilterParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        tok
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(i)) if i == keyword)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.is(punct) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        ensure!(
+            self.eat(punct),
+            "expected `{}` in filter, got {:?}",
+            punct,
+            self.peek()
+        );
+        Ok(())
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        ensure!(
+            self.is_keyword(keyword),
+            "expected `{}` in filter, got {:?}",
+            keyword,
+            self.peek()
+        );
+        self.position += 1;
+        Ok(())
+    }
+
+    fn parse_pipe(&mut self) -> Result<Filter> {
+        let lhs = self.parse_comma()?;
+        if self.eat("|") {
+            let rhs = self.parse_pipe()?;
+            return Ok(Filter::Pipe(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comma(&mut self) -> Result<Filter> {
+        let mut lhs = self.parse_alternative()?;
+        while self.eat(",") {
+            let rhs = self.parse_alternative()?;
+            lhs = Filter::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_alternative(&mut self) -> Result<Filter> {
+        let lhs = self.parse_or()?;
+        if self.eat("//") {
+            let rhs = self.parse_alternative()?;
+            return Ok(Filter::Alternative(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter> {
+        let mut lhs = self.parse_and()?;
+        while self.is_keyword("or") {
+            self.position += 1;
+            let rhs = self.parse_and()?;
+            lhs = Filter::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter> {
+        let mut lhs = self.parse_comparison()?;
+        while self.is_keyword("and") {
+            self.position += 1;
+            let rhs = self.parse_comparison()?;
+            lhs = Filter::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Filter> {
+        let lhs = self.parse_additive()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => BinOp::Eq,
+            Some(Token::Punct("!=")) => BinOp::Ne,
+            Some(Token::Punct("<")) => BinOp::Lt,
+            Some(Token::Punct("<=")) => BinOp::Le,
+            Some(Token::Punct(">")) => BinOp::Gt,
+            Some(Token::Punct(">=")) => BinOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.position += 1;
+        let rhs = self.parse_additive()?;
+        Ok(Filter::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Filter> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("+")) => BinOp::Add,
+                Some(Token::Punct("-")) => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.position += 1;
+            let rhs = self.parse_multiplicative()?;
+            lhs = Filter::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Filter> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("*")) => BinOp::Mul,
+                Some(Token::Punct("/")) => BinOp::Div,
+                Some(Token::Punct("%")) => BinOp::Rem,
+                _ => return Ok(lhs),
+            };
+            self.position += 1;
+            let rhs = self.parse_unary()?;
+            lhs = Filter::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter> {
+        if self.eat("-") {
+            return Ok(Filter::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Filter> {
+        let mut term = self.parse_term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Field(name)) => {
+                    let key = Filter::Literal(Value::String(name.clone()));
+                    self.position += 1;
+                    term = Filter::Index(Box::new(term), Box::new(key));
+                }
+                Some(Token::Dot) => {
+                    self.position += 1;
+                    term = match self.next() {
+                        Some(Token::Str(s)) => {
+                            Filter::Index(Box::new(term), Box::new(Filter::Literal(s.into())))
+                        }
+                        Some(Token::Punct("[")) => self.parse_bracket_suffix(term)?,
+                        tok => bail!("unexpected token {:?} after `.`", tok),
+                    };
+                }
+                Some(Token::Punct("[")) => {
+                    self.position += 1;
+                    term = self.parse_bracket_suffix(term)?;
+                }
+                Some(Token::Punct("?")) => {
+                    self.position += 1;
+                    term = Filter::Optional(Box::new(term));
+                }
+                _ => return Ok(term),
+            }
+        }
+    }
+
+    /// Parse what follows an opening `[` in `term[]`, `term[i]` or `term[a:b]`.
+    fn parse_bracket_suffix(&mut self, term: Filter) -> Result<Filter> {
+        let term = Box::new(term);
+        if self.eat("]") {
+            return Ok(Filter::Iterate(term));
+        }
+        if self.eat(":") {
+            let to = self.parse_pipe()?;
+            self.expect("]")?;
+            return Ok(Filter::Slice(term, None, Some(Box::new(to))));
+        }
+
+        let index = self.parse_pipe()?;
+        if self.eat(":") {
+            let to = if self.is("]") {
+                None
+            } else {
+                Some(Box::new(self.parse_pipe()?))
+            };
+            self.expect("]")?;
+            return Ok(Filter::Slice(term, Some(Box::new(index)), to));
+        }
+        self.expect("]")?;
+        Ok(Filter::Index(term, Box::new(index)))
+    }
+
+    fn parse_term(&mut self) -> Result<Filter> {
+        let tok = self
+            .next()
+            .context("unexpected end of filter, expected a term")?;
+        let term = match tok {
+            Token::Dot => match self.peek() {
+                Some(Token::Str(s)) => {
+                    let key = Filter::Literal(Value::String(s.clone()));
+                    self.position += 1;
+                    Filter::Index(Box::new(Filter::Identity), Box::new(key))
+                }
+                Some(Token::Punct("[")) => {
+                    self.position += 1;
+                    self.parse_bracket_suffix(Filter::Identity)?
+                }
+                _ => Filter::Identity,
+            },
+            Token::DotDot => Filter::Recurse,
+            Token::Field(name) => Filter::Index(
+                Box::new(Filter::Identity),
+                Box::new(Filter::Literal(Value::String(name))),
+            ),
+            Token::Num(n) => Filter::Literal(Value::Number(n)),
+            Token::Str(s) => Filter::Literal(Value::String(s)),
+            Token::Punct("(") => {
+                let inner = self.parse_pipe()?;
+                self.expect(")")?;
+                inner
+            }
+            Token::Punct("[") => {
+                if self.eat("]") {
+                    Filter::Array(None)
+                } else {
+                    let inner = self.parse_pipe()?;
+                    self.expect("]")?;
+                    Filter::Array(Some(Box::new(inner)))
+                }
+            }
+            Token::Punct("{") => self.parse_object()?,
+            Token::Ident(ident) => match ident.as_str() {
+                "true" => Filter::Literal(Value::Bool(true)),
+                "false" => Filter::Literal(Value::Bool(false)),
+                "null" => Filter::Literal(Value::Null),
+                "if" => self.parse_if()?,
+                _ => {
+                    let mut args = Vec::new();
+                    if self.eat("(") {
+                        args.push(self.parse_pipe()?);
+                        while self.eat(";") {
+                            args.push(self.parse_pipe()?);
+                        }
+                        self.expect(")")?;
+                    }
+                    Filter::Call(ident, args)
+                }
+            },
+            tok => bail!("unexpected token {:?} in filter", tok),
+        };
+        Ok(term)
+    }
+
+    fn parse_object(&mut self) -> Result<Filter> {
+        let mut entries = Vec::new();
+        if self.eat("}") {
+            return Ok(Filter::Object(entries));
+        }
+
+        loop {
+            let key = match self.next() {
+                Some(Token::Ident(name)) | Some(Token::Str(name)) => {
+                    Filter::Literal(Value::String(name))
+                }
+                Some(Token::Punct("(")) => {
+                    let key = self.parse_pipe()?;
+                    self.expect(")")?;
+                    key
+                }
+                tok => bail!("unexpected object key {:?} in filter", tok),
+            };
+
+            let value = if self.eat(":") {
+                self.parse_object_value()?
+            } else {
+                // `{a}` is shorthand for `{a: .a}`
+                Filter::Index(Box::new(Filter::Identity), Box::new(key.clone()))
+            };
+            entries.push((key, value));
+
+            if self.eat("}") {
+                return Ok(Filter::Object(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// Object values may contain pipes but not commas, which separate entries.
+    fn parse_object_value(&mut self) -> Result<Filter> {
+        let lhs = self.parse_alternative()?;
+        if self.eat("|") {
+            let rhs = self.parse_object_value()?;
+            return Ok(Filter::Pipe(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_if(&mut self) -> Result<Filter> {
+        let cond = self.parse_pipe()?;
+        self.expect_keyword("then")?;
+        let then = self.parse_pipe()?;
+
+        let otherwise = if self.is_keyword("elif") {
+            self.position += 1;
+            return Ok(Filter::If(
+                Box::new(cond),
+                Box::new(then),
+                Some(Box::new(self.parse_if()?)),
+            ));
+        } else if self.is_keyword("else") {
+            self.position += 1;
+            Some(Box::new(self.parse_pipe()?))
+        } else {
+            None
+        };
+        self.expect_keyword("end")?;
+
+        Ok(Filter::If(Box::new(cond), Box::new(then), otherwise))
+    }
+}
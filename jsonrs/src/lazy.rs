@@ -0,0 +1,331 @@
+//! On-demand extraction of a few values from a large document.
+//!
+//! [`get_lazy`] scans the input once and only descends into members on the
+//! way to a requested JSON Pointer. Every other subtree is skipped by matching
+//! brackets and strings, without building values or checking its contents, so
+//! a malformed document may go unnoticed outside the requested values.
+//!
+//! When an object has the same key more than once the last member wins, as
+//! in [`Parser`], so every object on the way to a requested value is scanned
+//! to its end. The scan only stops early once every pointer has been resolved
+//! and no such object is left open, e.g. inside a top-level array.
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    lexer::Lexer,
+    parser::{unescape, Parser, MAX_DEPTH},
+    value::Value,
+};
+
+/// Look up each of `pointers` in `input`, returning `None` for those that do
+/// not exist. Duplicate keys resolve to their last member.
+pub fn get_lazy(input: &str, pointers: &[&str]) -> Result<Vec<Option<Value>>> {
+    get_lazy_with(input, pointers, false)
+}
+
+/// Like [`get_lazy`], keeping the numbers of the values found as exact
+/// decimals if `exact_numbers` is set, as [`Parser::exact_numbers`] does.
+pub fn get_lazy_with(
+    input: &str,
+    pointers: &[&str],
+    exact_numbers: bool,
+) -> Result<Vec<Option<Value>>> {
+    let targets = pointers
+        .iter()
+        .map(|p| tokens(p))
+        .collect::<Result<Vec<_>>>()?;
+    let mut scan = Scan {
+        input,
+        bytes: input.as_bytes(),
+        position: 0,
+        targets: &targets,
+        results: vec![None; targets.len()],
+        remaining: targets.len(),
+        open_objects: 0,
+        exact_numbers,
+    };
+    if scan.remaining > 0 {
+        let active: Vec<usize> = (0..targets.len()).collect();
+        scan.skip_whitespace();
+        scan.visit(0, &active)?;
+    }
+    Ok(scan.results)
+}
+
+/// Split a JSON Pointer into unescaped reference tokens.
+fn tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    ensure!(
+        pointer.starts_with('/'),
+        "JSON Pointer {:?} must start with `/`",
+        pointer
+    );
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+struct Scan<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+    targets: &'a [Vec<String>],
+    results: Vec<Option<Value>>,
+    remaining: usize,
+    /// Objects entered and not yet closed, any of which may still repeat a
+    /// key on the way to a resolved target.
+    open_objects: usize,
+    exact_numbers: bool,
+}
+
+impl Scan<'_> {
+    /// Visit the value at the current position, which is `depth` tokens deep
+    /// along the path shared by the `active` targets.
+    fn visit(&mut self, depth: usize, active: &[usize]) -> Result<()> {
+        ensure!(depth <= MAX_DEPTH, "nesting exceeds {} levels", MAX_DEPTH);
+        let (found, deeper): (Vec<usize>, Vec<usize>) = active
+            .iter()
+            .partition(|&&t| self.targets[t].len() == depth);
+
+        if !found.is_empty() {
+            let start = self.position;
+            self.skip_value()?;
+            let value = parse(&self.input[start..self.position], self.exact_numbers)
+                .context(format!("invalid value at byte {}", start))?;
+            // targets below this one are answered from the parsed value
+            for t in found.into_iter().chain(deeper) {
+                let rest = &self.targets[t][depth..];
+                self.resolve(t, lookup(&value, rest).cloned());
+            }
+            return Ok(());
+        }
+
+        match self.peek()? {
+            b'{' => self.visit_object(depth, active),
+            b'[' => self.visit_array(depth, active),
+            _ => self.skip_value(),
+        }
+    }
+
+    fn visit_object(&mut self, depth: usize, active: &[usize]) -> Result<()> {
+        self.open_objects += 1;
+        self.visit_members(depth, active)?;
+        self.open_objects -= 1;
+        Ok(())
+    }
+
+    fn visit_members(&mut self, depth: usize, active: &[usize]) -> Result<()> {
+        self.position += 1;
+        self.skip_whitespace();
+        if self.peek()? == b'}' {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.read_key()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+
+            let matching: Vec<usize> = active
+                .iter()
+                .copied()
+                .filter(|&t| self.targets[t][depth] == key)
+                .collect();
+            if matching.is_empty() {
+                self.skip_value()?;
+            } else {
+                // a later member with the same key replaces what was found
+                for &t in &matching {
+                    self.unresolve(t);
+                }
+                self.visit(depth + 1, &matching)?;
+            }
+
+            if !self.next_member(b'}')? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn visit_array(&mut self, depth: usize, active: &[usize]) -> Result<()> {
+        self.position += 1;
+        self.skip_whitespace();
+        if self.peek()? == b']' {
+            self.position += 1;
+            return Ok(());
+        }
+        let mut index = 0usize;
+        loop {
+            let token = index.to_string();
+            let matching: Vec<usize> = active
+                .iter()
+                .copied()
+                .filter(|&t| self.targets[t][depth] == token)
+                .collect();
+            if matching.is_empty() {
+                self.skip_value()?;
+            } else {
+                self.visit(depth + 1, &matching)?;
+                if self.remaining == 0 && self.open_objects == 0 {
+                    return Ok(());
+                }
+            }
+
+            if !self.next_member(b']')? {
+                return Ok(());
+            }
+            index += 1;
+        }
+    }
+
+    /// After a member, consume `,` and return true, or consume `close` and
+    /// return false.
+    fn next_member(&mut self, close: u8) -> Result<bool> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b',' => {
+                self.position += 1;
+                self.skip_whitespace();
+                Ok(true)
+            }
+            b if b == close => {
+                self.position += 1;
+                Ok(false)
+            }
+            b => bail!(
+                "expected `,` or `{}` at byte {}, got {:?}",
+                close as char,
+                self.position,
+                b as char
+            ),
+        }
+    }
+
+    fn resolve(&mut self, target: usize, value: Option<Value>) {
+        if self.results[target].is_none() && value.is_some() {
+            self.results[target] = value;
+            self.remaining -= 1;
+        }
+    }
+
+    fn unresolve(&mut self, target: usize) {
+        if self.results[target].take().is_some() {
+            self.remaining += 1;
+        }
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.bytes
+            .get(self.position)
+            .copied()
+            .context(format!("unexpected end of input at byte {}", self.position))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        ensure!(
+            self.peek()? == byte,
+            "expected `{}` at byte {}",
+            byte as char,
+            self.position
+        );
+        self.position += 1;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(
+            self.bytes.get(self.position),
+            Some(b' ' | b'\t' | b'\n' | b'\r')
+        ) {
+            self.position += 1;
+        }
+    }
+
+    fn read_key(&mut self) -> Result<String> {
+        let start = self.position;
+        ensure!(
+            self.peek()? == b'"',
+            "expected object key at byte {}",
+            start
+        );
+        self.skip_string()?;
+        unescape(&self.input[start + 1..self.position - 1])
+            .context(format!("invalid object key at byte {}", start))
+    }
+
+    /// Skip a string starting at its opening quote.
+    fn skip_string(&mut self) -> Result<()> {
+        let start = self.position;
+        self.position += 1;
+        while let Some(&b) = self.bytes.get(self.position) {
+            self.position += 1;
+            match b {
+                b'"' => return Ok(()),
+                b'\\' => self.position += 1,
+                _ => {}
+            }
+        }
+        bail!("unterminated string starting at byte {}", start)
+    }
+
+    /// Skip the value at the current position by matching brackets.
+    fn skip_value(&mut self) -> Result<()> {
+        let start = self.position;
+        match self.peek()? {
+            b'"' => self.skip_string(),
+            b'{' | b'[' => {
+                let mut depth = 0usize;
+                while let Some(&b) = self.bytes.get(self.position) {
+                    match b {
+                        b'"' => {
+                            self.skip_string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.position += 1;
+                                return Ok(());
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.position += 1;
+                }
+                bail!("unclosed container starting at byte {}", start)
+            }
+            _ => {
+                while let Some(&b) = self.bytes.get(self.position) {
+                    if matches!(b, b',' | b']' | b'}' | b' ' | b'\t' | b'\n' | b'\r') {
+                        break;
+                    }
+                    self.position += 1;
+                }
+                ensure!(self.position > start, "expected value at byte {}", start);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn parse(input: &str, exact_numbers: bool) -> Result<Value> {
+    let mut l = Lexer::new(input.into());
+    Parser::new(&mut l).exact_numbers(exact_numbers).parse()
+}
+
+fn lookup<'a>(value: &'a Value, tokens: &[String]) -> Option<&'a Value> {
+    tokens.iter().try_fold(value, |target, token| match target {
+        Value::Object(o) => o.get(token),
+        Value::Array(a) => {
+            let index = token.parse::<usize>().ok()?;
+            (index.to_string() == *token).then(|| a.get(index))?
+        }
+        _ => None,
+    })
+}
@@ -6,6 +6,7 @@ pub struct Lexer {
     position: usize,
     read_position: usize,
     ch: char,
+    token_start: usize,
 }
 
 impl Lexer {
@@ -15,6 +16,7 @@ impl Lexer {
             position: 0,
             read_position: 0,
             ch: '0',
+            token_start: 0,
         };
         l.read_char();
         l
@@ -22,6 +24,7 @@ impl Lexer {
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_start = self.position;
 
         let tok = match self.ch {
             '{' => Token::new(TokenType::LBRACE, self.ch.into()),
@@ -30,18 +33,20 @@ impl Lexer {
             ']' => Token::new(TokenType::RBRACKET, self.ch.into()),
             ':' => Token::new(TokenType::COLON, self.ch.into()),
             ',' => Token::new(TokenType::COMMA, self.ch.into()),
-            '\0' => Token::new(TokenType::EOF, "\0".into()),
+            '\0' if self.at_end() => Token::new(TokenType::EOF, "\0".into()),
             '"' => {
-                let string = self.read_string();
-                return Token::new(TokenType::STRING, string);
+                return match self.read_string() {
+                    Some(string) => Token::new(TokenType::STRING, string),
+                    None => Token::new(TokenType::ILLEGAL, "\"".into()),
+                };
             }
             _ => {
                 if is_letter(self.ch) {
                     let literal = self.read_literal();
                     return Token::from(literal.as_ref());
                 }
-                if is_digit(self.ch) {
-                    let literal = self.read_digit();
+                if is_digit(self.ch) || self.ch == '-' {
+                    let literal = self.read_number();
                     return Token::new(TokenType::INT, literal);
                 }
                 return Token::new(TokenType::ILLEGAL, self.ch.into());
@@ -53,39 +58,58 @@ impl Lexer {
         tok
     }
 
+    /// Byte offset of the start of the last token returned.
+    pub fn token_start(&self) -> usize {
+        self.token_start
+    }
+
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = '\0';
-        } else {
-            // TODO: find better way to index string
-            self.ch = self.input.chars().nth(self.read_position).expect(&format!(
-                "could not read character at position {}",
-                self.read_position
-            ));
-        }
         self.position = self.read_position;
-        self.read_position += 1;
+        match self
+            .input
+            .get(self.read_position..)
+            .and_then(|s| s.chars().next())
+        {
+            Some(ch) => {
+                self.ch = ch;
+                self.read_position += ch.len_utf8();
+            }
+            None => {
+                self.ch = '\0';
+                self.read_position += 1;
+            }
+        }
     }
 
-    fn read_string(&mut self) -> String {
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    /// Read the raw contents of a string, or `None` if it is unterminated.
+    fn read_string(&mut self) -> Option<String> {
         let position = self.position + 1;
         self.read_char();
         while self.ch != '"' {
+            if self.ch == '\\' {
+                self.read_char();
+            }
+            if self.at_end() {
+                return None;
+            }
             self.read_char()
         }
         let res = self.input[position..self.position].into();
         self.read_char();
-        res
+        Some(res)
     }
 
-    fn read_digit(&mut self) -> String {
+    fn read_number(&mut self) -> String {
         let position = self.position;
-        while is_digit(self.ch) {
+        while is_digit(self.ch) || matches!(self.ch, '-' | '+' | 'e' | 'E') {
             self.read_char()
         }
 
-        let res = self.input[position..self.position].into();
-        res
+        self.input[position..self.position].into()
     }
 
     fn read_literal(&mut self) -> String {
@@ -94,12 +118,11 @@ impl Lexer {
             self.read_char()
         }
 
-        let res = self.input[position..self.position].into();
-        res
+        self.input[position..self.position].into()
     }
 
     fn skip_whitespace(&mut self) {
-        while self.ch.is_whitespace() {
+        while matches!(self.ch, ' ' | '\t' | '\n' | '\r') {
             self.read_char();
         }
     }
@@ -1,3 +1,18 @@
+pub mod batch;
+pub mod convert;
+pub mod decimal;
+pub mod encoding;
+pub mod ffi;
+pub mod flatten;
+pub mod jq;
+pub mod lazy;
 pub mod lexer;
 pub mod parser;
+pub mod redact;
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod structural;
 pub mod token;
+pub mod value;
+pub mod wasm;
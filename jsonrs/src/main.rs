@@ -1,25 +1,503 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
-use jsonrs::{lexer::Lexer, parser::Parser};
+use jsonrs::{
+    batch::{self, ReportFormat, Summary},
+    convert::{self, BinaryFormat, EncodeOptions, Format},
+    encoding,
+    flatten::{self, FlattenOptions, IndexStyle},
+    jq::Filter,
+    lazy,
+    lexer::Lexer,
+    parser::Parser,
+    redact::Redactor,
+    stats::Stats,
+    value::Value,
+};
 
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// Path to JSON file
-    name: PathBuf,
+    #[arg(required = true)]
+    name: Option<PathBuf>,
+
+    /// Keep numbers beyond i64 and f64 precision exactly instead of rounding
+    #[arg(long, global = true)]
+    exact_numbers: bool,
+}
+
+#[derive(clap::Subcommand)]
+enum Commands {
+    /// Run a jq-style filter over a JSON document
+    Jq {
+        /// Filter expression, e.g. `.items[] | select(.id > 2) | .name`
+        filter: String,
+
+        /// Path to JSON file. If no filename is provided, stdin is used.
+        file: Option<PathBuf>,
+
+        /// Treat the input as newline-delimited JSON, one document per line
+        #[arg(long)]
+        ndjson: bool,
+
+        /// Print each output on a single line
+        #[arg(short, long)]
+        compact: bool,
+
+        /// Print strings without surrounding quotes
+        #[arg(short, long)]
+        raw_output: bool,
+    },
+    /// Convert a document between JSON, YAML, TOML and CSV
+    Convert {
+        /// Format of the input
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        from: Format,
+
+        /// Format to write to stdout
+        #[arg(long, value_enum)]
+        to: Format,
+
+        /// Path to input file. If no filename is provided, stdin is used.
+        file: Option<PathBuf>,
+    },
+    /// Encode a JSON file as CBOR or MessagePack
+    Encode {
+        #[arg(long, value_enum)]
+        format: BinaryFormat,
+
+        /// Sort map keys by encoded bytes and use the shortest exact floats
+        #[arg(long)]
+        deterministic: bool,
+
+        #[arg(value_name = "SOURCE")]
+        source: PathBuf,
+
+        #[arg(value_name = "DESTINATION")]
+        destination: PathBuf,
+    },
+    /// Decode a CBOR or MessagePack file to JSON
+    Decode {
+        #[arg(long, value_enum)]
+        format: BinaryFormat,
+
+        #[arg(value_name = "SOURCE")]
+        source: PathBuf,
+
+        /// Output path. If no destination is provided, stdout is used.
+        #[arg(value_name = "DESTINATION")]
+        destination: Option<PathBuf>,
+    },
+    /// Flatten a document into an object of paths to scalar values
+    Flatten {
+        /// Path to JSON file. If no filename is provided, stdin is used.
+        file: Option<PathBuf>,
+
+        #[command(flatten)]
+        paths: PathArgs,
+    },
+    /// Rebuild a nested document from an object of paths to values
+    Unflatten {
+        /// Path to JSON file. If no filename is provided, stdin is used.
+        file: Option<PathBuf>,
+
+        #[command(flatten)]
+        paths: PathArgs,
+    },
+    /// Print the values at JSON Pointers without parsing the rest of the file
+    Get {
+        /// Path to JSON file
+        file: PathBuf,
+
+        /// JSON Pointers such as `/items/0/name`
+        #[arg(required = true)]
+        pointers: Vec<String>,
+    },
+    /// Mask sensitive values matched by a rules file
+    Redact {
+        /// JSON file with masking rules. The key of `hash` rules may also be
+        /// given in the JSONRS_HASH_KEY environment variable, which takes
+        /// precedence over the rules file.
+        #[arg(long)]
+        rules: PathBuf,
+
+        /// Path to JSON file. If no filename is provided, stdin is used.
+        file: Option<PathBuf>,
+
+        /// Treat the input as newline-delimited JSON and write one masked
+        /// document per line
+        #[arg(long)]
+        ndjson: bool,
+    },
+    /// Report the shape of a document and what makes it large
+    Stats {
+        /// Path to JSON file. If no filename is provided, stdin is used.
+        file: Option<PathBuf>,
+
+        /// Treat the input as newline-delimited JSON, one document per line
+        #[arg(long)]
+        ndjson: bool,
+
+        /// Number of entries to list in each ranking
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Validate many files in parallel
+    Validate {
+        /// Files, directories to search for JSON files, and glob patterns such
+        /// as `fixtures/**/*.json`
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// Number of worker threads [default: number of CPUs]
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Extension of the files picked up from directories
+        #[arg(long, default_value = "json")]
+        extension: String,
+
+        /// Number of slowest files to list in the summary
+        #[arg(long, default_value_t = 5)]
+        slowest: usize,
+
+        /// Only print failures and the summary
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// Format of the report written to `--report-file`
+        #[arg(long, value_enum, requires = "report_file")]
+        report: Option<ReportFormat>,
+
+        /// Where to write the report
+        #[arg(long, requires = "report")]
+        report_file: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::Args)]
+struct PathArgs {
+    /// Separator between keys in dotted paths
+    #[arg(long, default_value = ".")]
+    separator: String,
+
+    /// How array indices are written in dotted paths
+    #[arg(long, value_enum, default_value_t = IndexStyle::Separator)]
+    index_style: IndexStyle,
+
+    /// Use JSON Pointer paths such as `/a/0/b`
+    #[arg(long, conflicts_with_all = ["separator", "index_style"])]
+    pointer: bool,
+}
+
+impl From<PathArgs> for FlattenOptions {
+    fn from(args: PathArgs) -> Self {
+        FlattenOptions {
+            separator: args.separator,
+            index_style: args.index_style,
+            pointer: args.pointer,
+        }
+    }
 }
 
 fn main() -> Result<()> {
     let cli = <Cli as clap::Parser>::parse();
+    // the file name and subcommands cannot be declared as conflicting without
+    // also keeping global flags such as `--exact-numbers` before a subcommand
+    if cli.command.is_some() && cli.name.is_some() {
+        <Cli as clap::CommandFactory>::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "a file name cannot be used with a subcommand",
+            )
+            .exit();
+    }
+    let exact = cli.exact_numbers;
 
-    let contents = std::fs::read_to_string(&cli.name)
-            .context(format!("failed to open file `{:?}`", &cli.name))?;
+    match cli.command {
+        Some(Commands::Jq {
+            filter,
+            file,
+            ndjson,
+            compact,
+            raw_output,
+        }) => jq(&filter, file, ndjson, compact, raw_output, exact),
+        Some(Commands::Convert { from, to, file }) => {
+            let text = read_text(&file)?;
+            let value = match from {
+                Format::Json => parse_json(&text, exact).context("failed to read JSON input")?,
+                _ if exact => anyhow::bail!("--exact-numbers only applies to JSON input"),
+                from => convert::read(from, &text)?,
+            };
+            print!("{}", convert::write(to, &value)?);
+            Ok(())
+        }
+        Some(Commands::Encode {
+            format,
+            deterministic,
+            source,
+            destination,
+        }) => {
+            let contents = read_file(&source)?;
+            let options = if deterministic {
+                EncodeOptions::deterministic()
+            } else {
+                EncodeOptions::default()
+            };
+            let encoded = convert::encode(format, &parse_json(&contents, exact)?, &options)?;
+            std::fs::write(&destination, encoded)
+                .context(format!("failed to write file `{:?}`", destination))
+        }
+        Some(Commands::Decode {
+            format,
+            source,
+            destination,
+        }) => {
+            let contents =
+                std::fs::read(&source).context(format!("failed to open file `{:?}`", source))?;
+            let json = convert::decode(format, &contents)?.to_pretty_string() + "\n";
+            match destination {
+                Some(path) => std::fs::write(&path, json)
+                    .context(format!("failed to write file `{:?}`", path)),
+                None => {
+                    print!("{}", json);
+                    Ok(())
+                }
+            }
+        }
+        Some(Commands::Flatten { file, paths }) => {
+            let value = read_json(&file, exact)?;
+            let flat = flatten::flatten(&value, &paths.into());
+            println!("{}", Value::Object(flat).to_pretty_string());
+            Ok(())
+        }
+        Some(Commands::Unflatten { file, paths }) => {
+            let Value::Object(flat) = read_json(&file, exact)? else {
+                anyhow::bail!("unflatten expects an object of paths to values");
+            };
+            let value = flatten::unflatten(&flat, &paths.into())?;
+            println!("{}", value.to_pretty_string());
+            Ok(())
+        }
+        Some(Commands::Get { file, pointers }) => {
+            let contents = read_file(&file)?;
+            let pointers: Vec<&str> = pointers.iter().map(String::as_str).collect();
+            for (pointer, value) in pointers
+                .iter()
+                .zip(lazy::get_lazy_with(&contents, &pointers, exact)?)
+            {
+                match value {
+                    Some(value) => println!("{}", value),
+                    None => anyhow::bail!("no value at {:?}", pointer),
+                }
+            }
+            Ok(())
+        }
+        Some(Commands::Redact {
+            rules,
+            file,
+            ndjson,
+        }) => {
+            let mut redactor: Redactor = read_file(&rules)?
+                .parse()
+                .context(format!("invalid rules in `{:?}`", rules))?;
+            if let Some(key) = std::env::var_os("JSONRS_HASH_KEY").filter(|k| !k.is_empty()) {
+                redactor = redactor.hash_key(key.into_encoded_bytes());
+            }
+            if ndjson {
+                let mut stdout = io::stdout().lock();
+                for_each_line(open_input(&file)?, exact, |mut value| {
+                    redactor.redact(&mut value)?;
+                    Ok(writeln!(stdout, "{}", value)?)
+                })
+            } else {
+                let mut value = read_json(&file, exact)?;
+                redactor.redact(&mut value)?;
+                println!("{}", value.to_pretty_string());
+                Ok(())
+            }
+        }
+        Some(Commands::Stats {
+            file,
+            ndjson,
+            top,
+            json,
+        }) => {
+            let mut stats = Stats::new(top);
+            if ndjson {
+                for_each_line(open_input(&file)?, exact, |value| {
+                    stats.add(&value);
+                    Ok(())
+                })?;
+            } else {
+                stats.add(&read_json(&file, exact)?);
+            }
+            if json {
+                println!("{}", stats.to_value().to_pretty_string());
+            } else {
+                print!("{}", stats);
+            }
+            Ok(())
+        }
+        Some(Commands::Validate {
+            inputs,
+            jobs,
+            extension,
+            slowest,
+            quiet,
+            report,
+            report_file,
+        }) => {
+            let paths = batch::expand(&inputs, &extension)?;
+            let jobs =
+                jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+            let mut stdout = io::stdout().lock();
+            let outcomes = batch::validate(&paths, jobs, exact, |outcome| {
+                // a closed stdout should not stop the report from being written
+                let _ = match &outcome.error {
+                    None if quiet => Ok(()),
+                    None => writeln!(stdout, "ok    {}", outcome.path.display()),
+                    Some(error) => writeln!(stdout, "FAIL  {}: {}", outcome.path.display(), error),
+                };
+            });
+            let summary = Summary::new(&outcomes, slowest);
+            write!(stdout, "\n{}", summary)?;
 
-    let mut lexer = Lexer::new(contents);
-    let mut parser = Parser::new(&mut lexer);
+            if let (Some(format), Some(path)) = (report, report_file) {
+                let contents = match format {
+                    ReportFormat::Junit => batch::junit(&outcomes),
+                    ReportFormat::Json => batch::json_report(&outcomes).to_pretty_string() + "\n",
+                };
+                std::fs::write(&path, contents)
+                    .context(format!("failed to write file `{:?}`", path))?;
+            }
+            anyhow::ensure!(
+                summary.failed == 0,
+                "{} of {} files failed",
+                summary.failed,
+                outcomes.len()
+            );
+            Ok(())
+        }
+        None => validate(
+            &cli.name
+                .expect("file name is required without a subcommand"),
+            exact,
+        ),
+    }
+}
 
-    parser.parse()?;
+fn validate(name: &Path, exact: bool) -> Result<()> {
+    parse_json(&read_file(name)?, exact)?;
     println!("Successfully parsed JSON.");
     Ok(())
 }
+
+fn jq(
+    filter: &str,
+    file: Option<PathBuf>,
+    ndjson: bool,
+    compact: bool,
+    raw_output: bool,
+    exact: bool,
+) -> Result<()> {
+    let filter: Filter = filter.parse().context("failed to parse filter")?;
+    let mut stdout = io::stdout().lock();
+
+    let mut emit = |value: &Value| -> Result<()> {
+        for out in filter.run(value)? {
+            match out {
+                Value::String(s) if raw_output => writeln!(stdout, "{}", s)?,
+                v if compact => writeln!(stdout, "{}", v)?,
+                v => writeln!(stdout, "{}", v.to_pretty_string())?,
+            }
+        }
+        Ok(())
+    };
+
+    if ndjson {
+        for_each_line(open_input(&file)?, exact, |value| emit(&value))?;
+    } else {
+        emit(&read_json(&file, exact)?)?;
+    }
+
+    Ok(())
+}
+
+/// Parse each non-blank line of `input` as a JSON document and pass it to `f`.
+/// NDJSON is always UTF-8, but a leading BOM is tolerated.
+fn for_each_line(
+    input: impl BufRead,
+    exact: bool,
+    mut f: impl FnMut(Value) -> Result<()>,
+) -> Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let line = line.context(format!("failed to read line {}", i + 1))?;
+        let line = match i {
+            0 => line.trim_start_matches('\u{feff}'),
+            _ => &line,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value =
+            parse_json(line, exact).context(format!("failed to parse JSON on line {}", i + 1))?;
+        f(value)?;
+    }
+    Ok(())
+}
+
+/// Read and parse a whole JSON document from `file` or stdin.
+fn read_json(file: &Option<PathBuf>, exact: bool) -> Result<Value> {
+    parse_json(&read_text(file)?, exact)
+}
+
+/// Parse `text`, keeping numbers as exact decimals if `exact` is set.
+fn parse_json(text: &str, exact: bool) -> Result<Value> {
+    let mut lexer = Lexer::new(text.into());
+    Parser::new(&mut lexer).exact_numbers(exact).parse()
+}
+
+/// Read `file` or stdin as text, transcoding UTF-16 and UTF-32 to UTF-8.
+fn read_text(file: &Option<PathBuf>) -> Result<String> {
+    match file {
+        Some(path) => read_file(path),
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .context("failed to read input")?;
+            encoding::decode(&bytes).context("failed to decode input")
+        }
+    }
+}
+
+/// Read `path` as text, transcoding UTF-16 and UTF-32 to UTF-8.
+fn read_file(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path).context(format!("failed to open file `{:?}`", path))?;
+    encoding::decode(&bytes).context(format!("failed to decode file `{:?}`", path))
+}
+
+/// Open `file` for buffered reading, falling back to stdin.
+fn open_input(file: &Option<PathBuf>) -> Result<Box<dyn BufRead>> {
+    match file {
+        Some(path) => {
+            let f = File::open(path).context(format!("failed to open file `{:?}`", path))?;
+            Ok(Box::new(BufReader::new(f)))
+        }
+        None => Ok(Box::new(BufReader::new(io::stdin()))),
+    }
+}
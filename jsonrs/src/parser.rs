@@ -1,15 +1,26 @@
-use anyhow::{anyhow, ensure, Context, Result};
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
 
 use crate::{
     lexer::Lexer,
     token::{Token, TokenType},
+    value::{Number, Value},
 };
 
+/// Deepest nesting of arrays and objects accepted, which keeps the recursive
+/// descent well within the stack.
+pub const MAX_DEPTH: usize = 512;
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     l: &'a mut Lexer,
     cur_token: Token,
     peek_token: Token,
+    cur_offset: usize,
+    peek_offset: usize,
+    depth: usize,
+    exact_numbers: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -18,61 +29,93 @@ impl<'a> Parser<'a> {
             l,
             cur_token: Token::new(TokenType::EOF, "\0".into()),
             peek_token: Token::new(TokenType::EOF, "\0".into()),
+            cur_offset: 0,
+            peek_offset: 0,
+            depth: 0,
+            exact_numbers: false,
         };
         p.next_token();
         p.next_token();
         p
     }
 
+    /// Keep numbers that do not fit in an `i64` as exact decimals instead of
+    /// rounding them to `f64`, so that serializing the result reproduces every
+    /// digit.
+    pub fn exact_numbers(mut self, exact: bool) -> Self {
+        self.exact_numbers = exact;
+        self
+    }
+
     pub fn next_token(&mut self) {
         self.cur_token = self.peek_token.clone();
+        self.cur_offset = self.peek_offset;
         self.peek_token = self.l.next_token();
+        self.peek_offset = self.l.token_start();
     }
 
-    pub fn parse(&mut self) -> Result<()> {
-        ensure!(
-            self.cur_token.token_type == TokenType::LBRACE,
-            "JSON object should start with `{{`, got {:?}",
-            self.cur_token.literal
-        );
+    /// Byte offset of the current token, which is where parsing stopped after
+    /// an error.
+    pub fn offset(&self) -> usize {
+        self.cur_offset
+    }
 
-        self.parse_object().context("Could not parse object")?;
+    pub fn parse(&mut self) -> Result<Value> {
+        let value = self.parse_value().context("Could not parse document")?;
         self.next_token();
         ensure!(
             self.cur_token.token_type == TokenType::EOF,
             "expected EOF, got {:?}",
             self.cur_token.token_type
         );
-        Ok(())
+        Ok(value)
     }
 
-    pub fn parse_object(&mut self) -> Result<()> {
+    pub fn parse_object(&mut self) -> Result<Value> {
         ensure!(
             self.cur_token.token_type == TokenType::LBRACE,
             "expected `{{`, got {:?}",
             self.cur_token.literal
         );
 
+        let mut object = BTreeMap::new();
         self.next_token();
-        while self.cur_token.token_type != TokenType::RBRACE {
-            self.parse_pair()?;
+        if self.cur_token.token_type == TokenType::RBRACE {
+            return Ok(Value::Object(object));
         }
 
-        ensure!(
-            self.cur_token.token_type == TokenType::RBRACE,
-            "objects should end with `}}`, got {:?}",
-            self.cur_token.literal
-        );
-        Ok(())
+        loop {
+            let (key, value) = self.parse_pair()?;
+            object.insert(key, value);
+
+            self.next_token();
+            match self.cur_token.token_type {
+                TokenType::COMMA => {
+                    self.next_token();
+                    ensure!(
+                        self.cur_token.token_type != TokenType::RBRACE,
+                        "json objects should not end with a comma"
+                    );
+                }
+                TokenType::RBRACE => break,
+                _ => bail!(
+                    "objects should end with `}}`, got {:?}",
+                    self.cur_token.literal
+                ),
+            }
+        }
+        Ok(Value::Object(object))
     }
 
-    fn parse_pair(&mut self) -> Result<()> {
+    fn parse_pair(&mut self) -> Result<(String, Value)> {
         ensure!(
             self.cur_token.token_type == TokenType::STRING,
             "expected JSON key, got {:?}",
             self.cur_token
         );
 
+        let key = self.parse_string()?;
+
         self.next_token();
         ensure!(
             self.cur_token.token_type == TokenType::COLON,
@@ -81,40 +124,36 @@ impl<'a> Parser<'a> {
         );
 
         self.next_token();
-        self.parse_value()?;
-
-        if self.peek_token.token_type == TokenType::COMMA {
-            self.next_token();
-
-            if self.peek_token.token_type == TokenType::RBRACE {
-                return Err(anyhow!("json objects should not end with a comma"));
-            }
-        }
-        self.next_token();
+        let value = self.parse_value()?;
 
-        Ok(())
+        Ok((key, value))
     }
 
-    fn parse_list(&mut self) -> Result<()> {
+    fn parse_list(&mut self) -> Result<Value> {
         ensure!(
             self.cur_token.token_type == TokenType::LBRACKET,
             "lists should start with `[`, got {:?}",
             self.cur_token.literal
         );
 
+        let mut list = Vec::new();
         self.next_token();
 
         loop {
             if self.cur_token.token_type == TokenType::RBRACKET {
                 break;
             }
-            self.parse_value()?;
+            list.push(self.parse_value()?);
             self.next_token();
 
             if self.cur_token.token_type != TokenType::COMMA {
                 break;
             }
             self.next_token();
+            ensure!(
+                self.cur_token.token_type != TokenType::RBRACKET,
+                "lists should not end with a comma"
+            );
         }
 
         ensure!(
@@ -123,21 +162,182 @@ impl<'a> Parser<'a> {
             self.cur_token.literal
         );
 
-        Ok(())
+        Ok(Value::Array(list))
     }
 
-    fn parse_value(&mut self) -> Result<()> {
-        match self.cur_token.token_type {
-            TokenType::LBRACE => self.parse_object()?,
-            TokenType::LBRACKET => self.parse_list()?,
-            TokenType::STRING
-            | TokenType::INT
-            | TokenType::TRUE
-            | TokenType::FALSE
-            | TokenType::NULL => (),
+    fn parse_value(&mut self) -> Result<Value> {
+        let value = match self.cur_token.token_type {
+            TokenType::LBRACE | TokenType::LBRACKET => {
+                ensure!(
+                    self.depth < MAX_DEPTH,
+                    "nesting exceeds {} levels",
+                    MAX_DEPTH
+                );
+                self.depth += 1;
+                let value = if self.cur_token.token_type == TokenType::LBRACE {
+                    self.parse_object()
+                } else {
+                    self.parse_list()
+                };
+                self.depth -= 1;
+                value?
+            }
+            TokenType::STRING => Value::String(self.parse_string()?),
+            TokenType::INT => {
+                let literal = &self.cur_token.literal;
+                ensure!(is_number(literal), "invalid number {:?}", literal);
+                if self.exact_numbers {
+                    Value::Number(Number::parse_exact(literal)?)
+                } else {
+                    Value::Number(literal.parse::<Number>()?)
+                }
+            }
+            TokenType::TRUE => Value::Bool(true),
+            TokenType::FALSE => Value::Bool(false),
+            TokenType::NULL => Value::Null,
             _ => return Err(anyhow!("unexpected token {:?}", self.cur_token)),
         };
 
-        Ok(())
+        Ok(value)
+    }
+
+    fn parse_string(&self) -> Result<String> {
+        string_contents(&self.cur_token.literal)
+    }
+}
+
+/// Where in the input something happened. Lines and columns count from 1,
+/// and columns count characters rather than bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of byte `offset` of `input`.
+    pub fn of(input: &str, offset: usize) -> Self {
+        let before = input.get(..offset).unwrap_or(input);
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            offset: before.len(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
     }
 }
+
+/// Parse `input`, returning on failure the position of the token where
+/// parsing stopped along with the error.
+pub fn parse_with_position(input: &str) -> std::result::Result<Value, (Error, Position)> {
+    let mut l = Lexer::new(input.into());
+    let mut p = Parser::new(&mut l);
+    p.parse().map_err(|e| (e, Position::of(input, p.offset())))
+}
+
+/// Decode the raw contents of a JSON string, which may not hold unescaped
+/// control characters.
+pub(crate) fn string_contents(literal: &str) -> Result<String> {
+    if let Some(c) = literal.chars().find(|c| *c < ' ') {
+        bail!("unescaped control character {:?} in string", c);
+    }
+    unescape(literal)
+}
+
+/// Whether `literal` follows the number grammar of RFC 8259, which rules out
+/// leading zeros, a bare `.` and a leading `+`.
+pub(crate) fn is_number(literal: &str) -> bool {
+    let b = literal.strip_prefix('-').unwrap_or(literal).as_bytes();
+    let digits = |mut i: usize| {
+        while b.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut i = match b.first() {
+        Some(b'0') => 1,
+        Some(b'1'..=b'9') => digits(1),
+        _ => return false,
+    };
+    if b.get(i) == Some(&b'.') {
+        let end = digits(i + 1);
+        if end == i + 1 {
+            return false;
+        }
+        i = end;
+    }
+    if matches!(b.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(b.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        let end = digits(i);
+        if end == i {
+            return false;
+        }
+        i = end;
+    }
+    i == b.len()
+}
+
+/// Decode the escape sequences in the raw contents of a string token.
+pub fn unescape(literal: &str) -> Result<String> {
+    let mut out = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('/') => out.push('/'),
+            Some('b') => out.push('\u{08}'),
+            Some('f') => out.push('\u{0c}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hi = read_hex(&mut chars)?;
+                let code = if (0xD800..0xDC00).contains(&hi) {
+                    ensure!(
+                        chars.next() == Some('\\') && chars.next() == Some('u'),
+                        "unpaired surrogate \\u{:04x}",
+                        hi
+                    );
+                    let lo = read_hex(&mut chars)?;
+                    ensure!(
+                        (0xDC00..0xE000).contains(&lo),
+                        "invalid low surrogate \\u{:04x}",
+                        lo
+                    );
+                    0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00)
+                } else {
+                    hi
+                };
+                out.push(
+                    char::from_u32(code)
+                        .ok_or_else(|| anyhow!("invalid code point {:#x}", code))?,
+                );
+            }
+            Some(other) => bail!("invalid escape sequence `\\{}`", other),
+            None => bail!("unterminated escape sequence"),
+        }
+    }
+
+    Ok(out)
+}
+
+fn read_hex(chars: &mut std::str::Chars) -> Result<u32> {
+    let hex: String = chars.take(4).collect();
+    ensure!(
+        hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        "truncated unicode escape `\\u{}`",
+        hex
+    );
+    u32::from_str_radix(&hex, 16).context(format!("invalid unicode escape `\\u{}`", hex))
+}
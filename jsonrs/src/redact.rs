@@ -0,0 +1,277 @@
+//! Masking of sensitive values before documents are shared.
+//!
+//! Rules are read from a JSON document such as
+//!
+//! ```json
+//! {
+//!   "placeholder": "[REDACTED]",
+//!   "hash_key": "a long random secret",
+//!   "rules": [
+//!     {"key": "(?i)password|secret|token"},
+//!     {"pointer": "/user/email", "action": "hash"},
+//!     {"value": "^\\d{13,19}$"}
+//!   ]
+//! }
+//! ```
+//!
+//! A `key` rule masks the whole value of every member whose name matches the
+//! regex, a `pointer` rule the value at an RFC 6901 JSON Pointer, and a `value`
+//! rule every string or number whose JSON text matches. The first matching rule
+//! decides the action: `placeholder` (the default) or `hash`, which keeps equal
+//! values recognisable. Hashes are HMAC-SHA256 under `hash_key`, which is
+//! required by `hash` rules: a plain digest of a PIN, an email address or any
+//! other short value can be reversed by hashing every candidate, and would
+//! link equal values across unrelated datasets. Only someone with the key can
+//! do either, so keep it secret, and use a different key per dataset where
+//! values should not be linkable.
+
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::{bail, ensure, Context, Result};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::Sha256;
+
+use crate::value::Value;
+
+pub const DEFAULT_PLACEHOLDER: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Action {
+    /// Replace the value with the placeholder string.
+    #[default]
+    Placeholder,
+    /// Replace the value with `hmac-sha256:` and the hex HMAC-SHA256 of its
+    /// compact JSON under the redactor's hash key.
+    Hash,
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// Regex searched for in member names.
+    Key(Regex),
+    /// Unescaped reference tokens of a JSON Pointer.
+    Pointer(Vec<String>),
+    /// Regex searched for in the JSON text of strings and numbers.
+    Value(Regex),
+}
+
+impl Matcher {
+    /// Match the value at the JSON Pointer `pointer`.
+    pub fn pointer(pointer: &str) -> Result<Self> {
+        Ok(Matcher::Pointer(pointer_tokens(pointer)?))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub matcher: Matcher,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    placeholder: String,
+    hash_key: Option<Vec<u8>>,
+    rules: Vec<Rule>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Redactor::new(DEFAULT_PLACEHOLDER)
+    }
+}
+
+impl Redactor {
+    pub fn new(placeholder: impl Into<String>) -> Self {
+        Redactor {
+            placeholder: placeholder.into(),
+            hash_key: None,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Set the secret key of [`Action::Hash`], replacing any from the rules.
+    pub fn hash_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.hash_key = Some(key.into());
+        self
+    }
+
+    /// Add a rule; rules are tried in the order they were added.
+    pub fn rule(mut self, matcher: Matcher, action: Action) -> Self {
+        self.rules.push(Rule { matcher, action });
+        self
+    }
+
+    /// Build a redactor from a rules document as described in the module docs.
+    pub fn from_value(value: &Value) -> Result<Self> {
+        let Value::Object(config) = value else {
+            bail!("rules must be an object, found {}", value.type_name());
+        };
+        if let Some(unknown) = config
+            .keys()
+            .find(|k| !matches!(k.as_str(), "placeholder" | "hash_key" | "rules"))
+        {
+            bail!("unknown field {:?} in rules", unknown);
+        }
+
+        let mut redactor = match config.get("placeholder") {
+            None => Redactor::default(),
+            Some(Value::String(s)) => Redactor::new(s.as_str()),
+            Some(v) => bail!("placeholder must be a string, found {}", v.type_name()),
+        };
+        match config.get("hash_key") {
+            None => {}
+            Some(Value::String(s)) if !s.is_empty() => redactor = redactor.hash_key(s.as_str()),
+            Some(Value::String(_)) => bail!("hash_key must not be empty"),
+            Some(v) => bail!("hash_key must be a string, found {}", v.type_name()),
+        }
+        let rules = match config.get("rules") {
+            Some(Value::Array(rules)) => rules,
+            _ => bail!("rules must contain a `rules` array"),
+        };
+        for (i, rule) in rules.iter().enumerate() {
+            let rule = parse_rule(rule).context(format!("invalid rule {}", i))?;
+            redactor.rules.push(rule);
+        }
+        Ok(redactor)
+    }
+
+    /// Mask every matching value in `value`, returning how many were masked.
+    /// Fails without touching `value` if a rule hashes but there is no key.
+    pub fn redact(&self, value: &mut Value) -> Result<usize> {
+        ensure!(
+            self.hash_key.is_some() || self.rules.iter().all(|r| r.action != Action::Hash),
+            "`hash` rules need a secret hash_key"
+        );
+        let mut count = 0;
+        self.walk(value, &mut Vec::new(), None, &mut count);
+        Ok(count)
+    }
+
+    fn walk(
+        &self,
+        value: &mut Value,
+        path: &mut Vec<String>,
+        key: Option<&str>,
+        count: &mut usize,
+    ) {
+        if let Some(action) = self.action(value, path, key) {
+            *value = self.mask(value, action);
+            *count += 1;
+            return;
+        }
+        match value {
+            Value::Array(a) => {
+                for (i, item) in a.iter_mut().enumerate() {
+                    path.push(i.to_string());
+                    self.walk(item, path, None, count);
+                    path.pop();
+                }
+            }
+            Value::Object(o) => {
+                for (k, item) in o.iter_mut() {
+                    path.push(k.clone());
+                    self.walk(item, path, Some(k), count);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The action of the first rule that matches `value` at `path`.
+    fn action(&self, value: &Value, path: &[String], key: Option<&str>) -> Option<Action> {
+        let text = match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        };
+        self.rules
+            .iter()
+            .find(|rule| match &rule.matcher {
+                Matcher::Key(re) => key.is_some_and(|k| re.is_match(k)),
+                Matcher::Pointer(tokens) => tokens == path,
+                Matcher::Value(re) => text.as_deref().is_some_and(|t| re.is_match(t)),
+            })
+            .map(|rule| rule.action)
+    }
+
+    fn mask(&self, value: &Value, action: Action) -> Value {
+        match action {
+            Action::Placeholder => Value::String(self.placeholder.clone()),
+            Action::Hash => {
+                let key = self.hash_key.as_deref().expect("redact checks for a key");
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+                mac.update(value.to_string().as_bytes());
+                let mut out = String::from("hmac-sha256:");
+                for byte in mac.finalize().into_bytes() {
+                    write!(out, "{:02x}", byte).expect("writing to a String cannot fail");
+                }
+                Value::String(out)
+            }
+        }
+    }
+}
+
+impl FromStr for Redactor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Redactor::from_value(&s.parse().context("failed to parse rules")?)
+    }
+}
+
+fn parse_rule(rule: &Value) -> Result<Rule> {
+    let Value::Object(rule) = rule else {
+        bail!("expected an object, found {}", rule.type_name());
+    };
+    let action = match rule.get("action").map(Value::as_str) {
+        None | Some(Some("placeholder")) => Action::Placeholder,
+        Some(Some("hash")) => Action::Hash,
+        Some(_) => bail!("action must be \"placeholder\" or \"hash\""),
+    };
+
+    let mut matchers = rule
+        .iter()
+        .filter(|(k, _)| k.as_str() != "action")
+        .map(parse_matcher);
+    let matcher = matchers
+        .next()
+        .context("expected one of `key`, `pointer` or `value`")??;
+    ensure!(
+        matchers.next().is_none(),
+        "a rule takes exactly one of `key`, `pointer` or `value`"
+    );
+    Ok(Rule { matcher, action })
+}
+
+fn parse_matcher((kind, pattern): (&String, &Value)) -> Result<Matcher> {
+    let Some(pattern) = pattern.as_str() else {
+        bail!("`{}` must be a string, found {}", kind, pattern.type_name());
+    };
+    let regex = || Regex::new(pattern).context(format!("invalid regex {:?}", pattern));
+    match kind.as_str() {
+        "key" => Ok(Matcher::Key(regex()?)),
+        "value" => Ok(Matcher::Value(regex()?)),
+        "pointer" => Matcher::pointer(pattern),
+        _ => bail!("unknown field {:?}", kind),
+    }
+}
+
+/// Split a JSON Pointer into unescaped reference tokens.
+fn pointer_tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    ensure!(
+        pointer.starts_with('/'),
+        "JSON Pointer {:?} must start with `/`",
+        pointer
+    );
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
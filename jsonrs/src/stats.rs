@@ -0,0 +1,275 @@
+//! Shape and size statistics for one or more documents.
+//!
+//! Sizes are measured in bytes of compact JSON, so they do not depend on how
+//! the input happened to be formatted.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
+
+use crate::{
+    convert::{path_to_string, PathSegment},
+    value::{write_escaped, Value},
+};
+
+/// A container or string found while walking, with its length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Zero-based index of the document it was found in.
+    pub document: usize,
+    /// Location such as `.items[3].name`.
+    pub path: String,
+    /// Elements, members or characters, depending on the kind of entry.
+    pub len: usize,
+}
+
+/// Statistics accumulated over every document passed to [`Stats::add`].
+#[derive(Debug, Clone)]
+pub struct Stats {
+    limit: usize,
+    pub documents: usize,
+    /// Container nesting of the deepest value; a scalar document has depth 0.
+    pub max_depth: usize,
+    /// Number of values of each type, keyed by [`Value::type_name`].
+    pub counts: BTreeMap<&'static str, usize>,
+    pub largest_arrays: Vec<Entry>,
+    pub largest_objects: Vec<Entry>,
+    pub longest_strings: Vec<Entry>,
+    /// How often each object key occurs anywhere in the documents.
+    pub key_frequency: BTreeMap<String, usize>,
+    /// Bytes taken by each top-level member, including its key. Documents
+    /// that are not objects are attributed to `.`.
+    pub size_by_key: BTreeMap<String, usize>,
+    pub total_size: usize,
+}
+
+impl Stats {
+    /// Create empty statistics that keep the `limit` largest entries of each
+    /// kind.
+    pub fn new(limit: usize) -> Self {
+        Stats {
+            limit,
+            documents: 0,
+            max_depth: 0,
+            counts: BTreeMap::new(),
+            largest_arrays: Vec::new(),
+            largest_objects: Vec::new(),
+            longest_strings: Vec::new(),
+            key_frequency: BTreeMap::new(),
+            size_by_key: BTreeMap::new(),
+            total_size: 0,
+        }
+    }
+
+    /// Statistics for a single document.
+    pub fn of(value: &Value, limit: usize) -> Self {
+        let mut stats = Stats::new(limit);
+        stats.add(value);
+        stats
+    }
+
+    /// Add another document, e.g. the next line of an NDJSON stream.
+    pub fn add(&mut self, value: &Value) {
+        let depth = self.walk(value, &mut Vec::new());
+        self.max_depth = self.max_depth.max(depth);
+
+        let size = value.to_string().len();
+        self.total_size += size;
+        match value {
+            Value::Object(o) => {
+                for (key, v) in o {
+                    let mut quoted = String::new();
+                    write_escaped(&mut quoted, key).expect("writing to a String cannot fail");
+                    // `"key":value`, without the separating comma
+                    *self.size_by_key.entry(key.clone()).or_default() +=
+                        quoted.len() + 1 + v.to_string().len();
+                }
+            }
+            _ => *self.size_by_key.entry(".".into()).or_default() += size,
+        }
+        self.documents += 1;
+    }
+
+    /// Walk `value` at `path`, returning its depth.
+    fn walk(&mut self, value: &Value, path: &mut Vec<PathSegment>) -> usize {
+        *self.counts.entry(value.type_name()).or_default() += 1;
+        match value {
+            Value::Array(a) => {
+                self.record(Kind::Array, path, a.len());
+                let mut depth = 0;
+                for (i, item) in a.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    depth = depth.max(self.walk(item, path));
+                    path.pop();
+                }
+                depth + 1
+            }
+            Value::Object(o) => {
+                self.record(Kind::Object, path, o.len());
+                let mut depth = 0;
+                for (key, item) in o {
+                    *self.key_frequency.entry(key.clone()).or_default() += 1;
+                    path.push(PathSegment::Key(key.clone()));
+                    depth = depth.max(self.walk(item, path));
+                    path.pop();
+                }
+                depth + 1
+            }
+            Value::String(s) => {
+                self.record(Kind::String, path, s.chars().count());
+                0
+            }
+            _ => 0,
+        }
+    }
+
+    /// Keep the entry if it is among the `limit` largest of its kind so far.
+    /// Earlier entries win ties.
+    fn record(&mut self, kind: Kind, path: &[PathSegment], len: usize) {
+        let limit = self.limit;
+        let entries = match kind {
+            Kind::Array => &mut self.largest_arrays,
+            Kind::Object => &mut self.largest_objects,
+            Kind::String => &mut self.longest_strings,
+        };
+        if limit == 0 || (entries.len() == limit && entries[limit - 1].len >= len) {
+            return;
+        }
+        let entry = Entry {
+            document: self.documents,
+            path: path_to_string(path),
+            len,
+        };
+        let at = entries.partition_point(|e| e.len >= len);
+        entries.insert(at, entry);
+        entries.truncate(limit);
+    }
+
+    /// The `limit` most frequent keys, most frequent first.
+    pub fn top_keys(&self) -> Vec<(&str, usize)> {
+        let mut keys: Vec<_> = self
+            .key_frequency
+            .iter()
+            .map(|(k, n)| (k.as_str(), *n))
+            .collect();
+        keys.sort_by_key(|&(_, n)| Reverse(n));
+        keys.truncate(self.limit);
+        keys
+    }
+
+    /// Top-level members by size, largest first.
+    pub fn sizes(&self) -> Vec<(&str, usize)> {
+        let mut sizes: Vec<_> = self
+            .size_by_key
+            .iter()
+            .map(|(k, n)| (k.as_str(), *n))
+            .collect();
+        sizes.sort_by_key(|&(_, n)| Reverse(n));
+        sizes
+    }
+
+    /// The report as a JSON value, for machine consumption.
+    pub fn to_value(&self) -> Value {
+        let entries = |entries: &[Entry]| -> Value {
+            entries
+                .iter()
+                .map(|e| {
+                    let mut object = BTreeMap::new();
+                    if self.documents > 1 {
+                        object.insert("document".into(), Value::from(e.document as i64));
+                    }
+                    object.insert("path".into(), Value::from(e.path.as_str()));
+                    object.insert("length".into(), Value::from(e.len as i64));
+                    Value::Object(object)
+                })
+                .collect::<Vec<_>>()
+                .into()
+        };
+        let counts = |pairs: Vec<(&str, usize)>| -> Value {
+            pairs
+                .into_iter()
+                .map(|(k, n)| Value::from(vec![Value::from(k), Value::from(n as i64)]))
+                .collect::<Vec<_>>()
+                .into()
+        };
+
+        let mut report = BTreeMap::new();
+        report.insert("documents".into(), Value::from(self.documents as i64));
+        report.insert("max_depth".into(), Value::from(self.max_depth as i64));
+        report.insert(
+            "counts".into(),
+            Value::Object(
+                self.counts
+                    .iter()
+                    .map(|(k, n)| (k.to_string(), Value::from(*n as i64)))
+                    .collect(),
+            ),
+        );
+        report.insert("largest_arrays".into(), entries(&self.largest_arrays));
+        report.insert("largest_objects".into(), entries(&self.largest_objects));
+        report.insert("longest_strings".into(), entries(&self.longest_strings));
+        report.insert("top_keys".into(), counts(self.top_keys()));
+        report.insert("size_by_key".into(), counts(self.sizes()));
+        report.insert("total_size".into(), Value::from(self.total_size as i64));
+        Value::Object(report)
+    }
+
+    fn location(&self, entry: &Entry) -> String {
+        if self.documents > 1 {
+            format!("document {} {}", entry.document + 1, entry.path)
+        } else {
+            entry.path.clone()
+        }
+    }
+}
+
+enum Kind {
+    Array,
+    Object,
+    String,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "documents:  {}", self.documents)?;
+        writeln!(f, "max depth:  {}", self.max_depth)?;
+        writeln!(f, "total size: {} bytes", self.total_size)?;
+
+        writeln!(f, "\nvalues by type:")?;
+        for (name, n) in &self.counts {
+            writeln!(f, "  {:<8} {}", name, n)?;
+        }
+
+        let sections = [
+            ("largest arrays", &self.largest_arrays, "items"),
+            ("largest objects", &self.largest_objects, "members"),
+            ("longest strings", &self.longest_strings, "chars"),
+        ];
+        for (title, entries, unit) in sections {
+            if entries.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n{}:", title)?;
+            for e in entries {
+                writeln!(f, "  {:>8} {}  {}", e.len, unit, self.location(e))?;
+            }
+        }
+
+        let keys = self.top_keys();
+        if !keys.is_empty() {
+            writeln!(f, "\nmost frequent keys:")?;
+            for (key, n) in keys {
+                writeln!(f, "  {:>8}  {}", n, key)?;
+            }
+        }
+
+        writeln!(f, "\nsize by top-level key:")?;
+        for (key, n) in self.sizes() {
+            let share = if self.total_size == 0 {
+                0.0
+            } else {
+                100.0 * n as f64 / self.total_size as f64
+            };
+            writeln!(f, "  {:>8} bytes {:>5.1}%  {}", n, share, key)?;
+        }
+        Ok(())
+    }
+}
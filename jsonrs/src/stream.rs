@@ -0,0 +1,343 @@
+//! Parsing from an asynchronous byte stream, behind the `async` feature.
+//!
+//! [`AsyncParser`] reads from any [`AsyncRead`] only as far as it needs to,
+//! so it can sit directly on a socket. [`next_event`] yields one
+//! [`Event`] at a time without building values, and [`next_value`] assembles
+//! the next whole document. The input is a sequence of UTF-8 JSON documents,
+//! separated by whitespace where they would otherwise run together, and each
+//! is checked as strictly as by [`Parser`](crate::parser::Parser).
+//!
+//! [`next_event`]: AsyncParser::next_event
+//! [`next_value`]: AsyncParser::next_value
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Context, Result};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    parser::{is_number, string_contents, MAX_DEPTH},
+    value::{Number, Value},
+};
+
+/// Bytes requested from the reader at a time.
+const CHUNK: usize = 8 * 1024;
+
+/// One step through a document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// An object key, always followed by the events of its value.
+    Key(String),
+    /// A string, number, boolean or null.
+    Value(Value),
+}
+
+/// What may come next at the current position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    /// A value or the `]` closing an empty array.
+    ValueOrEnd,
+    Key,
+    /// A key or the `}` closing an empty object.
+    KeyOrEnd,
+    Colon,
+    CommaOrEnd,
+}
+
+enum Token {
+    Open(u8),
+    Close(u8),
+    Colon,
+    Comma,
+    String(String),
+    Scalar(Value),
+}
+
+pub struct AsyncParser<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// Position of the next unread byte in `buf`.
+    pos: usize,
+    /// Bytes dropped from the front of `buf`, for error offsets.
+    dropped: usize,
+    eof: bool,
+    /// Open containers, as their opening bytes.
+    stack: Vec<u8>,
+    expect: Expect,
+}
+
+impl<R: AsyncRead + Unpin> AsyncParser<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncParser {
+            reader,
+            buf: Vec::new(),
+            pos: 0,
+            dropped: 0,
+            eof: false,
+            stack: Vec::new(),
+            expect: Expect::Value,
+        }
+    }
+
+    /// The next event, or `None` once the input ends between documents.
+    pub async fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            let Some((offset, token)) = self.token().await? else {
+                ensure!(
+                    self.stack.is_empty(),
+                    "unexpected end of input at byte {}",
+                    self.offset()
+                );
+                return Ok(None);
+            };
+
+            let event = match (self.expect, token) {
+                (Expect::Value | Expect::ValueOrEnd, Token::Open(open)) => {
+                    ensure!(
+                        self.stack.len() < MAX_DEPTH,
+                        "nesting exceeds {} levels",
+                        MAX_DEPTH
+                    );
+                    self.stack.push(open);
+                    if open == b'{' {
+                        self.expect = Expect::KeyOrEnd;
+                        Event::StartObject
+                    } else {
+                        self.expect = Expect::ValueOrEnd;
+                        Event::StartArray
+                    }
+                }
+                (Expect::Value | Expect::ValueOrEnd, Token::String(s)) => {
+                    self.after_value();
+                    Event::Value(Value::String(s))
+                }
+                (Expect::Value | Expect::ValueOrEnd, Token::Scalar(v)) => {
+                    self.after_value();
+                    Event::Value(v)
+                }
+                (Expect::Key | Expect::KeyOrEnd, Token::String(key)) => {
+                    self.expect = Expect::Colon;
+                    Event::Key(key)
+                }
+                (Expect::Colon, Token::Colon) => {
+                    self.expect = Expect::Value;
+                    continue;
+                }
+                (Expect::CommaOrEnd, Token::Comma) => {
+                    self.expect = match self.stack.last() {
+                        Some(b'{') => Expect::Key,
+                        _ => Expect::Value,
+                    };
+                    continue;
+                }
+                (Expect::ValueOrEnd, Token::Close(close @ b']'))
+                | (Expect::KeyOrEnd, Token::Close(close @ b'}'))
+                | (Expect::CommaOrEnd, Token::Close(close)) => self.close(close, offset)?,
+                (Expect::Key, Token::Close(b'}')) => {
+                    bail!("json objects should not end with a comma")
+                }
+                (Expect::Value, Token::Close(b']')) if self.stack.last() == Some(&b'[') => {
+                    bail!("lists should not end with a comma")
+                }
+                (expect, _) => bail!("expected {} at byte {}", describe(expect), offset),
+            };
+            return Ok(Some(event));
+        }
+    }
+
+    /// The next whole document, or `None` once the input ends between
+    /// documents.
+    pub async fn next_value(&mut self) -> Result<Option<Value>> {
+        enum Partial {
+            Object(BTreeMap<String, Value>, Option<String>),
+            Array(Vec<Value>),
+        }
+        let mut stack: Vec<Partial> = Vec::new();
+
+        while let Some(event) = self.next_event().await? {
+            let value = match event {
+                Event::StartObject => {
+                    stack.push(Partial::Object(BTreeMap::new(), None));
+                    continue;
+                }
+                Event::StartArray => {
+                    stack.push(Partial::Array(Vec::new()));
+                    continue;
+                }
+                Event::Key(key) => {
+                    if let Some(Partial::Object(_, pending)) = stack.last_mut() {
+                        *pending = Some(key);
+                    }
+                    continue;
+                }
+                Event::Value(value) => value,
+                Event::EndObject | Event::EndArray => match stack.pop() {
+                    Some(Partial::Object(object, _)) => Value::Object(object),
+                    Some(Partial::Array(array)) => Value::Array(array),
+                    None => unreachable!("events are balanced"),
+                },
+            };
+            match stack.last_mut() {
+                None => return Ok(Some(value)),
+                Some(Partial::Object(object, key)) => {
+                    object.insert(key.take().expect("a key precedes each value"), value);
+                }
+                Some(Partial::Array(array)) => array.push(value),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pop the container closed by `close`.
+    fn close(&mut self, close: u8, offset: usize) -> Result<Event> {
+        let event = match (self.stack.pop(), close) {
+            (Some(b'{'), b'}') => Event::EndObject,
+            (Some(b'['), b']') => Event::EndArray,
+            _ => bail!("mismatched `{}` at byte {}", close as char, offset),
+        };
+        self.after_value();
+        Ok(event)
+    }
+
+    fn after_value(&mut self) {
+        self.expect = if self.stack.is_empty() {
+            Expect::Value
+        } else {
+            Expect::CommaOrEnd
+        };
+    }
+
+    fn offset(&self) -> usize {
+        self.dropped + self.pos
+    }
+
+    /// Read more input, returning false at the end of the stream.
+    async fn fill(&mut self) -> Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.dropped += self.pos;
+            self.pos = 0;
+        }
+        self.buf.reserve(CHUNK);
+        let n = self
+            .reader
+            .read_buf(&mut self.buf)
+            .await
+            .context("failed to read input")?;
+        self.eof = n == 0;
+        Ok(n > 0)
+    }
+
+    /// The next token and its offset, or `None` at the end of the input.
+    async fn token(&mut self) -> Result<Option<(usize, Token)>> {
+        loop {
+            match self.buf.get(self.pos).copied() {
+                Some(b' ' | b'\t' | b'\n' | b'\r') => self.pos += 1,
+                Some(_) => break,
+                None if self.fill().await? => {}
+                None => return Ok(None),
+            }
+        }
+
+        let start = self.offset();
+        let b = self.buf[self.pos];
+        let token = match b {
+            b'{' | b'[' | b'}' | b']' | b':' | b',' => {
+                self.pos += 1;
+                match b {
+                    b'{' | b'[' => Token::Open(b),
+                    b'}' | b']' => Token::Close(b),
+                    b':' => Token::Colon,
+                    _ => Token::Comma,
+                }
+            }
+            b'"' => self.string(start).await?,
+            _ => self.scalar(start).await?,
+        };
+        Ok(Some((start, token)))
+    }
+
+    /// Read the string whose opening quote is the next byte.
+    async fn string(&mut self, start: usize) -> Result<Token> {
+        // offset of the next byte to examine, relative to the opening quote
+        let mut i = 1;
+        loop {
+            while let Some(&b) = self.buf.get(self.pos + i) {
+                match b {
+                    b'"' => {
+                        let raw = &self.buf[self.pos + 1..self.pos + i];
+                        let raw = std::str::from_utf8(raw)
+                            .context(format!("invalid UTF-8 in string at byte {}", start))?;
+                        let s = string_contents(raw)
+                            .context(format!("invalid string at byte {}", start))?;
+                        self.pos += i + 1;
+                        return Ok(Token::String(s));
+                    }
+                    b'\\' => i += 2,
+                    _ => i += 1,
+                }
+            }
+            ensure!(
+                self.fill().await?,
+                "unterminated string starting at byte {}",
+                start
+            );
+        }
+    }
+
+    /// Read the literal or number starting at the next byte, which runs up to
+    /// the next structural or whitespace byte.
+    async fn scalar(&mut self, start: usize) -> Result<Token> {
+        let mut i = 0;
+        loop {
+            while let Some(&b) = self.buf.get(self.pos + i) {
+                if matches!(
+                    b,
+                    b'{' | b'}' | b'[' | b']' | b':' | b',' | b'"' | b' ' | b'\t' | b'\n' | b'\r'
+                ) {
+                    return self.finish_scalar(i, start);
+                }
+                i += 1;
+            }
+            if !self.fill().await? {
+                return self.finish_scalar(i, start);
+            }
+        }
+    }
+
+    fn finish_scalar(&mut self, len: usize, start: usize) -> Result<Token> {
+        let text = std::str::from_utf8(&self.buf[self.pos..self.pos + len]).ok();
+        let value = match text {
+            Some("true") => Value::Bool(true),
+            Some("false") => Value::Bool(false),
+            Some("null") => Value::Null,
+            Some(text) if is_number(text) => Value::Number(text.parse::<Number>()?),
+            _ => bail!(
+                "unexpected {:?} at byte {}",
+                String::from_utf8_lossy(&self.buf[self.pos..self.pos + len]),
+                start
+            ),
+        };
+        self.pos += len;
+        Ok(Token::Scalar(value))
+    }
+}
+
+fn describe(expect: Expect) -> &'static str {
+    match expect {
+        Expect::Value => "a value",
+        Expect::ValueOrEnd => "a value or `]`",
+        Expect::Key => "an object key",
+        Expect::KeyOrEnd => "an object key or `}`",
+        Expect::Colon => "`:` after key",
+        Expect::CommaOrEnd => "`,` or the end of the container",
+    }
+}
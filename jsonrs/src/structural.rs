@@ -0,0 +1,200 @@
+//! Two-stage parsing in the style of simdjson.
+//!
+//! Stage 1 ([`index`]) classifies the input 64 bytes at a time into bitmasks
+//! and from them computes the byte offsets of every structural character
+//! outside strings, every opening quote and the first byte of every other
+//! scalar. Only the classification depends on the CPU: [`Backend::detect`]
+//! picks AVX2 when the running x86_64 CPU has it and falls back to a scalar
+//! loop otherwise. Other architectures, such as aarch64, only have the
+//! scalar loop for now, as stable Rust has no portable SIMD. Stage 2
+//! ([`parse`]) walks those offsets to build a [`Value`], accepting exactly
+//! what [`Parser`](crate::parser::Parser) accepts.
+
+use anyhow::{ensure, Result};
+
+use crate::value::Value;
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+mod scalar;
+mod stage2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+impl Backend {
+    /// The fastest backend the running CPU supports.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            return Backend::Avx2;
+        }
+        Backend::Scalar
+    }
+
+    /// Every backend the running CPU supports, slowest first.
+    pub fn available() -> Vec<Self> {
+        let mut backends = vec![Backend::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        if Backend::Avx2.is_supported() {
+            backends.push(Backend::Avx2);
+        }
+        backends
+    }
+
+    /// Whether the running CPU can use this backend.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+        }
+    }
+
+    /// The classification function, which must only ever be obtained here
+    /// so that no CPU runs instructions it lacks.
+    fn classify(self) -> Result<fn(&[u8; 64]) -> Masks> {
+        ensure!(
+            self.is_supported(),
+            "the {:?} backend is not supported by this CPU",
+            self
+        );
+        Ok(match self {
+            Backend::Scalar => scalar::classify,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => avx2::classify,
+        })
+    }
+}
+
+/// One bit per byte of a 64-byte block, for each class of interest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Masks {
+    backslash: u64,
+    quote: u64,
+    /// `{`, `}`, `[`, `]`, `:` and `,`.
+    structural: u64,
+    /// Space, tab, line feed and carriage return.
+    whitespace: u64,
+}
+
+const EVEN_BITS: u64 = 0x5555_5555_5555_5555;
+
+/// State carried from one block to the next.
+#[derive(Default)]
+struct Carry {
+    /// The first byte of the next block is escaped by a trailing backslash.
+    escaped: u64,
+    /// All ones if the block ended inside a string.
+    in_string: u64,
+    /// The last byte of the block was part of a scalar.
+    scalar: u64,
+}
+
+impl Carry {
+    /// Bits of the bytes escaped by a backslash, for the backslashes in
+    /// `backslash`. A run of backslashes escapes the byte after it only if the
+    /// run has odd length, which is found with a carry-propagating add.
+    fn escaped(&mut self, backslash: u64) -> u64 {
+        if backslash == 0 {
+            let escaped = self.escaped;
+            self.escaped = 0;
+            return escaped;
+        }
+        let backslash = backslash & !self.escaped;
+        let follows_escape = (backslash << 1) | self.escaped;
+        let odd_starts = backslash & !EVEN_BITS & !follows_escape;
+        let (even_starts, overflow) = odd_starts.overflowing_add(backslash);
+        self.escaped = overflow as u64;
+        let invert = even_starts << 1;
+        (EVEN_BITS ^ invert) & follows_escape
+    }
+}
+
+/// Each bit set if an odd number of bits at or below it are set in `x`.
+fn prefix_xor(mut x: u64) -> u64 {
+    x ^= x << 1;
+    x ^= x << 2;
+    x ^= x << 4;
+    x ^= x << 8;
+    x ^= x << 16;
+    x ^= x << 32;
+    x
+}
+
+/// Offsets of the structural characters, opening quotes and scalar starts in
+/// `input`, using the backend chosen by [`Backend::detect`].
+pub fn index(input: &str) -> Result<Vec<u32>> {
+    index_with(Backend::detect(), input)
+}
+
+/// Like [`index`], with an explicit backend. Fails if the running CPU does
+/// not support `backend`.
+pub fn index_with(backend: Backend, input: &str) -> Result<Vec<u32>> {
+    let bytes = input.as_bytes();
+    ensure!(
+        u32::try_from(bytes.len()).is_ok(),
+        "input of {} bytes is too large to index",
+        bytes.len()
+    );
+    let classify = backend.classify()?;
+    let mut indexes = Vec::with_capacity(bytes.len() / 8);
+    let mut carry = Carry::default();
+
+    let mut blocks = bytes.chunks_exact(64);
+    let mut offset = 0u32;
+    for block in &mut blocks {
+        let block = block.try_into().expect("chunks are 64 bytes");
+        let bits = structurals(&mut carry, classify(block));
+        extract(&mut indexes, offset, bits);
+        offset += 64;
+    }
+    let rest = blocks.remainder();
+    if !rest.is_empty() {
+        // padding with spaces adds no structurals
+        let mut block = [b' '; 64];
+        block[..rest.len()].copy_from_slice(rest);
+        let bits = structurals(&mut carry, classify(&block));
+        extract(&mut indexes, offset, bits);
+    }
+
+    ensure!(carry.in_string == 0, "unterminated string");
+    Ok(indexes)
+}
+
+/// Bits of the structurals in one block.
+fn structurals(carry: &mut Carry, masks: Masks) -> u64 {
+    let escaped = carry.escaped(masks.backslash);
+    let quote = masks.quote & !escaped;
+    // set from an opening quote up to, not including, its closing quote
+    let in_string = prefix_xor(quote) ^ carry.in_string;
+    carry.in_string = 0u64.wrapping_sub(in_string >> 63);
+
+    let scalar = !(masks.structural | masks.whitespace | quote) & !in_string;
+    let scalar_starts = scalar & !((scalar << 1) | carry.scalar);
+    carry.scalar = scalar >> 63;
+
+    (masks.structural & !in_string) | (quote & in_string) | scalar_starts
+}
+
+fn extract(indexes: &mut Vec<u32>, offset: u32, mut bits: u64) {
+    while bits != 0 {
+        indexes.push(offset + bits.trailing_zeros());
+        bits &= bits - 1;
+    }
+}
+
+/// Parse `input` with stage 1 and stage 2.
+pub fn parse(input: &str) -> Result<Value> {
+    parse_with(Backend::detect(), input)
+}
+
+/// Like [`parse`], with an explicit backend for stage 1.
+pub fn parse_with(backend: Backend, input: &str) -> Result<Value> {
+    let indexes = index_with(backend, input)?;
+    stage2::parse(input, &indexes)
+}
@@ -0,0 +1,51 @@
+//! Classification of 64 bytes as two 32-byte AVX2 vectors.
+
+use std::arch::x86_64::{
+    __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8, _mm256_or_si256,
+    _mm256_set1_epi8,
+};
+
+use super::Masks;
+
+/// Only handed out by `Backend::classify` once the CPU is known to support
+/// AVX2.
+pub(super) fn classify(block: &[u8; 64]) -> Masks {
+    // SAFETY: `Backend::classify` checks that AVX2 is available before
+    // returning this function, and nothing else in the module calls it
+    unsafe { classify_avx2(block) }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn classify_avx2(block: &[u8; 64]) -> Masks {
+    let lo = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+    let hi = _mm256_loadu_si256(block.as_ptr().add(32) as *const __m256i);
+
+    Masks {
+        backslash: mask(lo, hi, b"\\"),
+        quote: mask(lo, hi, b"\""),
+        structural: mask(lo, hi, b"{}[]:,"),
+        whitespace: mask(lo, hi, b" \t\n\r"),
+    }
+}
+
+/// Bits of the bytes of `lo` followed by `hi` that equal any of `bytes`.
+#[target_feature(enable = "avx2")]
+unsafe fn mask(lo: __m256i, hi: __m256i, bytes: &[u8]) -> u64 {
+    let lo = movemask(eq_any(lo, bytes)) as u64;
+    let hi = movemask(eq_any(hi, bytes)) as u64;
+    lo | (hi << 32)
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn eq_any(v: __m256i, bytes: &[u8]) -> __m256i {
+    let mut acc = _mm256_cmpeq_epi8(v, _mm256_set1_epi8(bytes[0] as i8));
+    for &b in &bytes[1..] {
+        acc = _mm256_or_si256(acc, _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b as i8)));
+    }
+    acc
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn movemask(v: __m256i) -> u32 {
+    _mm256_movemask_epi8(v) as u32
+}
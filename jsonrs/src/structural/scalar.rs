@@ -0,0 +1,18 @@
+//! Byte-at-a-time classification, for CPUs without a SIMD backend.
+
+use super::Masks;
+
+pub(super) fn classify(block: &[u8; 64]) -> Masks {
+    let mut masks = Masks::default();
+    for (i, &b) in block.iter().enumerate() {
+        let bit = 1u64 << i;
+        match b {
+            b'\\' => masks.backslash |= bit,
+            b'"' => masks.quote |= bit,
+            b'{' | b'}' | b'[' | b']' | b':' | b',' => masks.structural |= bit,
+            b' ' | b'\t' | b'\n' | b'\r' => masks.whitespace |= bit,
+            _ => {}
+        }
+    }
+    masks
+}
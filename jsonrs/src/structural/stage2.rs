@@ -0,0 +1,151 @@
+//! Builds a [`Value`] by walking the offsets found by stage 1.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    parser::{is_number, string_contents, MAX_DEPTH},
+    value::{Number, Value},
+};
+
+pub(super) fn parse(input: &str, indexes: &[u32]) -> Result<Value> {
+    let mut p = Stage2 {
+        input,
+        indexes,
+        next: 0,
+        depth: 0,
+    };
+    let value = p.value().context("Could not parse document")?;
+    if let Some(&extra) = indexes.get(p.next) {
+        bail!("expected end of input at byte {}", extra);
+    }
+    Ok(value)
+}
+
+struct Stage2<'a> {
+    input: &'a str,
+    indexes: &'a [u32],
+    next: usize,
+    depth: usize,
+}
+
+impl Stage2<'_> {
+    /// Take the next structural, returning its offset and byte.
+    fn advance(&mut self) -> Result<(usize, u8)> {
+        let offset = *self
+            .indexes
+            .get(self.next)
+            .context("unexpected end of input")? as usize;
+        self.next += 1;
+        Ok((offset, self.input.as_bytes()[offset]))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        let offset = *self.indexes.get(self.next)? as usize;
+        Some(self.input.as_bytes()[offset])
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let (offset, byte) = self.advance()?;
+        match byte {
+            b'{' | b'[' => {
+                ensure!(
+                    self.depth < MAX_DEPTH,
+                    "nesting exceeds {} levels",
+                    MAX_DEPTH
+                );
+                self.depth += 1;
+                let value = if byte == b'{' {
+                    self.object()
+                } else {
+                    self.array()
+                };
+                self.depth -= 1;
+                value
+            }
+            b'"' => Ok(Value::String(self.string(offset)?)),
+            b'}' | b']' | b':' | b',' => {
+                bail!("unexpected `{}` at byte {}", byte as char, offset)
+            }
+            _ => self.scalar(offset),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        let mut object = BTreeMap::new();
+        if self.peek() == Some(b'}') {
+            self.next += 1;
+            return Ok(Value::Object(object));
+        }
+        loop {
+            let (offset, byte) = self.advance()?;
+            ensure!(byte == b'"', "expected JSON key at byte {}", offset);
+            let key = self.string(offset)?;
+
+            let (offset, byte) = self.advance()?;
+            ensure!(byte == b':', "expected `:` after key at byte {}", offset);
+            object.insert(key, self.value()?);
+
+            let (offset, byte) = self.advance()?;
+            match byte {
+                b',' => ensure!(
+                    self.peek() != Some(b'}'),
+                    "json objects should not end with a comma"
+                ),
+                b'}' => return Ok(Value::Object(object)),
+                _ => bail!("expected `,` or `}}` at byte {}", offset),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        let mut array = Vec::new();
+        if self.peek() == Some(b']') {
+            self.next += 1;
+            return Ok(Value::Array(array));
+        }
+        loop {
+            array.push(self.value()?);
+
+            let (offset, byte) = self.advance()?;
+            match byte {
+                b',' => ensure!(
+                    self.peek() != Some(b']'),
+                    "lists should not end with a comma"
+                ),
+                b']' => return Ok(Value::Array(array)),
+                _ => bail!("expected `,` or `]` at byte {}", offset),
+            }
+        }
+    }
+
+    /// Decode the string whose opening quote is at `offset`. Stage 1 has
+    /// already checked that every string is closed.
+    fn string(&self, offset: usize) -> Result<String> {
+        let bytes = self.input.as_bytes();
+        let mut end = offset + 1;
+        while bytes[end] != b'"' {
+            end += if bytes[end] == b'\\' { 2 } else { 1 };
+        }
+        string_contents(&self.input[offset + 1..end])
+            .context(format!("invalid string at byte {}", offset))
+    }
+
+    /// Parse the literal or number starting at `offset`, which runs up to the
+    /// next structural or whitespace.
+    fn scalar(&self, offset: usize) -> Result<Value> {
+        let end = self
+            .indexes
+            .get(self.next)
+            .map_or(self.input.len(), |&i| i as usize);
+        let text = self.input[offset..end].trim_end_matches([' ', '\t', '\n', '\r']);
+        match text {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "null" => Ok(Value::Null),
+            _ if is_number(text) => Ok(Value::Number(text.parse::<Number>()?)),
+            _ => bail!("unexpected {:?} at byte {}", text, offset),
+        }
+    }
+}
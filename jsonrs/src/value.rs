@@ -0,0 +1,359 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use anyhow::{Error, Result};
+
+use crate::{decimal::BigDecimal, lexer::Lexer, parser::Parser};
+
+/// An in-memory JSON document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+/// A JSON number, kept as an integer whenever it fits in an `i64`. Numbers
+/// are only kept as exact decimals when parsed with
+/// [`Parser::exact_numbers`].
+#[derive(Debug, Clone)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+    Decimal(BigDecimal),
+}
+
+impl Number {
+    /// Parse `s` without losing precision: integers that fit in an `i64`
+    /// become [`Number::Int`] and everything else a [`Number::Decimal`].
+    pub fn parse_exact(s: &str) -> Result<Self> {
+        match s.parse::<i64>() {
+            // keep the sign of negative zero
+            Ok(i) if s != "-0" => Ok(Number::Int(i)),
+            _ => Ok(Number::Decimal(s.parse()?)),
+        }
+    }
+
+    /// The nearest `f64`, which is infinite for decimals out of its range.
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Float(f) => *f,
+            Number::Decimal(d) => d.to_f64().unwrap_or(if d.is_negative() {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            }),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Number::Int(i) => Some(*i),
+            Number::Float(f)
+                if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < i64::MAX as f64 =>
+            {
+                Some(*f as i64)
+            }
+            Number::Float(_) => None,
+            Number::Decimal(d) => d.to_i64().ok(),
+        }
+    }
+
+    /// The exact value, or `None` for NaN and infinities.
+    pub fn as_decimal(&self) -> Option<BigDecimal> {
+        match self {
+            Number::Int(i) => Some((*i).into()),
+            Number::Float(f) => BigDecimal::try_from(*f).ok(),
+            Number::Decimal(d) => Some(d.clone()),
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.partial_cmp(b),
+            (Number::Decimal(_), _) | (_, Number::Decimal(_)) => {
+                self.as_decimal()?.partial_cmp(&other.as_decimal()?)
+            }
+            _ => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl FromStr for Number {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(i) = s.parse::<i64>() {
+            return Ok(Number::Int(i));
+        }
+        let f = s
+            .parse::<f64>()
+            .map_err(|_| anyhow::anyhow!("invalid number {:?}", s))?;
+        Ok(Number::Float(f))
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Number::Int(i) => write!(f, "{}", i),
+            // JSON has no representation for NaN or infinities
+            Number::Float(x) if !x.is_finite() => f.write_str("null"),
+            // `Debug` keeps the fractional part, so floats stay floats on re-parse
+            Number::Float(x) => write!(f, "{:?}", x),
+            Number::Decimal(ref d) => write!(f, "{}", d),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Number::Int(value)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Number::Float(value)
+    }
+}
+
+impl Value {
+    /// Name of the value's type, as reported in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// Look up a key on an object, returning `None` for other types.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object().and_then(|o| o.get(key))
+    }
+
+    /// Resolve an RFC 6901 JSON Pointer such as `/a/b/0`.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .try_fold(self, |target, token| match target {
+                Value::Object(o) => o.get(&token),
+                Value::Array(a) => parse_index(&token).and_then(|i| a.get(i)),
+                _ => None,
+            })
+    }
+
+    /// Serialize with two-space indentation.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        write_pretty(&mut out, self, 0).expect("writing to a String cannot fail");
+        out
+    }
+}
+
+fn parse_index(token: &str) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+impl FromStr for Value {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lexer = Lexer::new(s.into());
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse()
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write_escaped(f, s),
+            Value::Array(a) => {
+                f.write_char('[')?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                f.write_char(']')
+            }
+            Value::Object(o) => {
+                f.write_char('{')?;
+                for (i, (k, v)) in o.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_escaped(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_pretty(out: &mut String, value: &Value, indent: usize) -> fmt::Result {
+    let pad = "  ".repeat(indent + 1);
+    match value {
+        Value::Array(a) if !a.is_empty() => {
+            out.push_str("[\n");
+            for (i, v) in a.iter().enumerate() {
+                out.push_str(&pad);
+                write_pretty(out, v, indent + 1)?;
+                out.push_str(if i + 1 < a.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push(']');
+        }
+        Value::Object(o) if !o.is_empty() => {
+            out.push_str("{\n");
+            for (i, (k, v)) in o.iter().enumerate() {
+                out.push_str(&pad);
+                write_escaped(out, k)?;
+                out.push_str(": ");
+                write_pretty(out, v, indent + 1)?;
+                out.push_str(if i + 1 < o.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push('}');
+        }
+        _ => write!(out, "{}", value)?,
+    }
+    Ok(())
+}
+
+/// Write `s` as a quoted JSON string, escaping as required by RFC 8259.
+pub fn write_escaped<W: Write>(out: &mut W, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{08}' => out.write_str("\\b")?,
+            '\u{0c}' => out.write_str("\\f")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Number(Number::Int(value))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(Number::Float(value))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(value: BTreeMap<String, Value>) -> Self {
+        Value::Object(value)
+    }
+}
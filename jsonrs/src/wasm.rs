@@ -0,0 +1,41 @@
+//! WebAssembly bindings for JavaScript.
+//!
+//! The `wasm_bindgen` attributes only apply when building for `wasm32`, so the
+//! same code runs natively in the tests. Built with `wasm-pack` or
+//! `wasm-bindgen`, the module exports `validate(text)`.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::parser::parse_with_position;
+
+/// The result of [`validate`]. On success `line` and `col` are 0 and
+/// `message` is empty.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validation {
+    pub ok: bool,
+    pub line: u32,
+    pub col: u32,
+    pub message: String,
+}
+
+/// Check that `text` is a single JSON document, reporting the line and
+/// column where parsing stopped if it is not.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn validate(text: &str) -> Validation {
+    match parse_with_position(text) {
+        Ok(_) => Validation {
+            ok: true,
+            line: 0,
+            col: 0,
+            message: String::new(),
+        },
+        Err((e, position)) => Validation {
+            ok: false,
+            line: position.line.try_into().unwrap_or(u32::MAX),
+            col: position.column.try_into().unwrap_or(u32::MAX),
+            message: format!("{:#}", e),
+        },
+    }
+}
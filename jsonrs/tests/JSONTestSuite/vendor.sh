@@ -0,0 +1,26 @@
+#!/bin/sh
+# Replace test_parsing/ with the files of nst/JSONTestSuite at <commit>, byte
+# for byte, copy its LICENSE, record the commit in UPSTREAM and regenerate
+# CONFORMANCE.md against the new files.
+set -eu
+
+if [ $# -ne 1 ]; then
+  echo "usage: $0 <commit>" >&2
+  exit 2
+fi
+
+here=$(cd "$(dirname "$0")" && pwd)
+checkout=$(mktemp -d)
+trap 'rm -rf "$checkout"' EXIT
+
+git clone --quiet https://github.com/nst/JSONTestSuite.git "$checkout"
+git -C "$checkout" checkout --quiet "$1"
+
+rm -rf "$here/test_parsing"
+cp -R "$checkout/test_parsing" "$here/test_parsing"
+cp "$checkout/LICENSE" "$here/LICENSE"
+git -C "$checkout" rev-parse HEAD > "$here/UPSTREAM"
+echo "vendored JSONTestSuite $(cat "$here/UPSTREAM")"
+
+cd "$here/../.."
+UPDATE_CONFORMANCE=1 cargo test --quiet --test conformance
@@ -0,0 +1,180 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use jsonrs::batch::{self, Outcome, Summary};
+
+/// A fresh directory of fixtures for one test.
+fn fixtures(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("jsonrs-batch-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+fn relative(dir: &Path, paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|p| {
+            p.strip_prefix(dir)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect()
+}
+
+#[test]
+fn test_expand() {
+    let dir = fixtures(
+        "expand",
+        &[
+            ("b.json", "{}"),
+            ("a.json", "[]"),
+            ("notes.txt", "not json"),
+            ("sub/deeper/c.json", "1"),
+            ("sub/d.JSON", "2"),
+        ],
+    );
+    let root = dir.to_string_lossy().to_string();
+
+    let paths = batch::expand(std::slice::from_ref(&root), "json").unwrap();
+    assert_eq!(
+        relative(&dir, &paths),
+        ["a.json", "b.json", "sub/deeper/c.json"]
+    );
+
+    let pattern = format!("{}/**/*.json", root);
+    let explicit = dir.join("b.json").to_string_lossy().to_string();
+    let paths = batch::expand(&[explicit, pattern], "json").unwrap();
+    assert_eq!(
+        relative(&dir, &paths),
+        ["b.json", "a.json", "sub/deeper/c.json"]
+    );
+
+    let paths = batch::expand(std::slice::from_ref(&root), "txt").unwrap();
+    assert_eq!(relative(&dir, &paths), ["notes.txt"]);
+
+    let missing = format!("{}/*.yaml", root);
+    assert!(batch::expand(&[missing], "json").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_expand_skips_symlink_loops() {
+    let dir = fixtures("symlinks", &[("a.json", "{}"), ("sub/b.json", "[]")]);
+    std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+    std::os::unix::fs::symlink(dir.join("a.json"), dir.join("sub/link.json")).unwrap();
+    let root = dir.to_string_lossy().to_string();
+
+    let paths = batch::expand(std::slice::from_ref(&root), "json").unwrap();
+    assert_eq!(
+        relative(&dir, &paths),
+        ["a.json", "sub/b.json", "sub/link.json"]
+    );
+}
+
+#[test]
+fn test_validate_in_order() {
+    let files: Vec<(String, String)> = (0..40)
+        .map(|i| {
+            let contents = match i % 3 {
+                0 => format!("{{\"n\": {}}}", i),
+                1 => "[1, 2".to_string(),
+                _ => format!("[{}]", "0,".repeat(i * 100) + "0"),
+            };
+            (format!("{:02}.json", i), contents)
+        })
+        .collect();
+    let files: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(p, c)| (p.as_str(), c.as_str()))
+        .collect();
+    let dir = fixtures("order", &files);
+    let paths = batch::expand(&[dir.to_string_lossy().to_string()], "json").unwrap();
+
+    for jobs in [1, 3, 16] {
+        let mut streamed = Vec::new();
+        let outcomes = batch::validate(&paths, jobs, false, |o| streamed.push(o.path.clone()));
+        assert_eq!(streamed, paths);
+        let passed: Vec<bool> = outcomes.iter().map(Outcome::passed).collect();
+        let expected: Vec<bool> = (0..40).map(|i| i % 3 != 1).collect();
+        assert_eq!(passed, expected, "{} jobs", jobs);
+    }
+
+    let outcomes = batch::validate(&[dir.join("missing.json")], 4, false, |_| {});
+    assert!(outcomes[0]
+        .error
+        .as_ref()
+        .unwrap()
+        .contains("failed to open"));
+    assert!(batch::validate(&[], 4, false, |_| {}).is_empty());
+}
+
+fn outcome(path: &str, millis: u64, error: Option<&str>) -> Outcome {
+    Outcome {
+        path: path.into(),
+        duration: Duration::from_millis(millis),
+        error: error.map(Into::into),
+    }
+}
+
+#[test]
+fn test_summary() {
+    let outcomes = [
+        outcome("a.json", 3, None),
+        outcome("b.json", 7, Some("bad")),
+        outcome("c.json", 5, None),
+        outcome("d.json", 7, None),
+    ];
+    let summary = Summary::new(&outcomes, 2);
+    assert_eq!((summary.passed, summary.failed), (3, 1));
+    assert_eq!(summary.total, Duration::from_millis(22));
+    assert_eq!(
+        summary.slowest,
+        [
+            (PathBuf::from("b.json"), Duration::from_millis(7)),
+            (PathBuf::from("d.json"), Duration::from_millis(7)),
+        ]
+    );
+    assert_eq!(
+        summary.to_string(),
+        "3 passed, 1 failed in 0.022s\nslowest:\n       7.000ms  b.json\n       7.000ms  d.json\n"
+    );
+}
+
+#[test]
+fn test_reports() {
+    let outcomes = [
+        outcome("ok.json", 1, None),
+        outcome(
+            "bad.json",
+            2,
+            Some("expected `,` or `]`, got \"<eof>\" & more"),
+        ),
+    ];
+
+    let xml = batch::junit(&outcomes);
+    assert!(xml.starts_with(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"2\" failures=\"1\""
+    ));
+    assert!(xml.contains("<testcase classname=\"validate\" name=\"ok.json\" time=\"0.001000\"/>"));
+    assert!(xml.contains(
+        "<failure message=\"expected `,` or `]`, got &quot;&lt;eof&gt;&quot; &amp; more\">"
+    ));
+    assert!(xml.ends_with("  </testsuite>\n</testsuites>\n"));
+
+    let json = batch::json_report(&outcomes);
+    assert_eq!(json.pointer("/passed"), Some(&1i64.into()));
+    assert_eq!(json.pointer("/failed"), Some(&1i64.into()));
+    assert_eq!(json.pointer("/files/0/path"), Some(&"ok.json".into()));
+    assert_eq!(json.pointer("/files/0/error"), None);
+    assert_eq!(json.pointer("/files/1/ok"), Some(&false.into()));
+    assert_eq!(json.pointer("/files/1/seconds"), Some(&0.002.into()));
+}
@@ -0,0 +1,87 @@
+/* Exercises the C API through include/jsonrs.h; run by tests/ffi.rs. */
+#include <stdio.h>
+#include <string.h>
+
+#include "jsonrs.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                 \
+            failures++;                                                     \
+        }                                                                   \
+    } while (0)
+
+static void check_pointer(const JsonrsDocument *doc, const char *pointer,
+                          const char *expected) {
+    char *value = jsonrs_pointer(doc, pointer);
+    if (expected == NULL) {
+        CHECK(value == NULL);
+    } else {
+        CHECK(value != NULL && strcmp(value, expected) == 0);
+    }
+    jsonrs_string_free(value);
+}
+
+static void test_parse(void) {
+    const char *text = "{\"users\": [{\"name\": \"ada\", \"tags\": [\"a\", \"b\"]}],"
+                       " \"a/b\": 1.5, \"nul\": \"x\\u0000y\"}";
+    JsonrsError error = {0, 0, 0, NULL};
+    JsonrsDocument *doc = jsonrs_parse(text, strlen(text), &error);
+    CHECK(doc != NULL);
+    CHECK(error.message == NULL);
+
+    check_pointer(doc, "/users/0/name", "\"ada\"");
+    check_pointer(doc, "/users/0/tags", "[\"a\",\"b\"]");
+    check_pointer(doc, "/a~1b", "1.5");
+    check_pointer(doc, "/nul", "\"x\\u0000y\"");
+    check_pointer(doc, "/users/1", NULL);
+    check_pointer(doc, "missing slash", NULL);
+
+    char *all = jsonrs_to_string(doc);
+    CHECK(all != NULL && strncmp(all, "{\"a/b\":1.5,", 11) == 0);
+    jsonrs_string_free(all);
+    jsonrs_document_free(doc);
+}
+
+static void test_error(void) {
+    const char *text = "{\n  \"a\": [1, 2,\n  ]\n}";
+    JsonrsError error = {0, 0, 0, NULL};
+    JsonrsDocument *doc = jsonrs_parse(text, strlen(text), &error);
+    CHECK(doc == NULL);
+    CHECK(error.line == 3);
+    CHECK(error.column == 3);
+    CHECK(error.offset == 18);
+    CHECK(error.message != NULL &&
+          strstr(error.message, "lists should not end with a comma") != NULL);
+    jsonrs_error_free(&error);
+    CHECK(error.message == NULL);
+    jsonrs_error_free(&error);
+
+    /* invalid UTF-8 is reported where it starts */
+    const char bad[] = "[\"\xff\"]";
+    doc = jsonrs_parse(bad, sizeof bad - 1, &error);
+    CHECK(doc == NULL);
+    CHECK(error.offset == 2 && error.line == 1 && error.column == 3);
+    jsonrs_error_free(&error);
+
+    /* the error is optional, and embedded NUL bytes are just invalid input */
+    CHECK(jsonrs_parse("[1]\0", 4, NULL) == NULL);
+    CHECK(jsonrs_parse(NULL, 0, NULL) == NULL);
+    jsonrs_document_free(NULL);
+    jsonrs_string_free(NULL);
+}
+
+int main(void) {
+    test_parse();
+    test_error();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
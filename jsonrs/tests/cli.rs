@@ -0,0 +1,157 @@
+//! The `jsonrs` binary, run on files written to a temporary directory.
+
+use std::{fs, path::PathBuf, process::Command};
+
+const EXACT: &str = r#"{"n": 123456789012345678901234567890.1}"#;
+/// Valid JSON whose exponent is out of range for exact decimals.
+const HUGE_EXPONENT: &str = "[1e99999999999999999999]";
+
+/// Write `contents` to a fresh file named after the test.
+fn input(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("jsonrs-cli-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Run the binary with `args`, returning its stdout, or its stderr if it
+/// failed.
+fn run(args: &[&str]) -> Result<String, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_jsonrs"))
+        .args(args)
+        .output()
+        .unwrap();
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout).unwrap())
+    } else {
+        Err(String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+#[test]
+fn test_convert_exact_numbers() {
+    let path = input("convert.json", EXACT);
+    let path = path.to_str().unwrap();
+    let out = run(&["--exact-numbers", "convert", "--to", "yaml", path]).unwrap();
+    assert_eq!(out.trim(), "n: 123456789012345678901234567890.1");
+    let out = run(&["convert", "--to", "yaml", path]).unwrap();
+    assert_eq!(out.trim(), "n: 1.2345678901234568e29");
+
+    let yaml = input("convert.yaml", "n: 1.5\n");
+    let yaml = yaml.to_str().unwrap();
+    let err = run(&[
+        "--exact-numbers",
+        "convert",
+        "--from",
+        "yaml",
+        "--to",
+        "json",
+        yaml,
+    ])
+    .unwrap_err();
+    assert!(err.contains("only applies to JSON input"), "{}", err);
+}
+
+#[test]
+fn test_encode_exact_numbers() {
+    let source = input("encode.json", HUGE_EXPONENT);
+    let source = source.to_str().unwrap();
+    let destination = input("encode.cbor", "");
+    let destination = destination.to_str().unwrap();
+    run(&["encode", "--format", "cbor", source, destination]).unwrap();
+    let err = run(&[
+        "--exact-numbers",
+        "encode",
+        "--format",
+        "cbor",
+        source,
+        destination,
+    ])
+    .unwrap_err();
+    assert!(err.contains("exponent out of range"), "{}", err);
+
+    let source = input("encode-exact.json", EXACT);
+    let source = source.to_str().unwrap();
+    run(&[
+        "--exact-numbers",
+        "encode",
+        "--format",
+        "cbor",
+        source,
+        destination,
+    ])
+    .unwrap();
+    let out = run(&["decode", "--format", "cbor", destination]).unwrap();
+    assert!(out.contains("123456789012345678901234567890.1"), "{}", out);
+    let err = run(&[
+        "--exact-numbers",
+        "encode",
+        "--format",
+        "msgpack",
+        source,
+        destination,
+    ])
+    .unwrap_err();
+    assert!(err.contains("cannot be represented exactly"), "{}", err);
+}
+
+#[test]
+fn test_get_exact_numbers() {
+    let path = input("get.json", EXACT);
+    let path = path.to_str().unwrap();
+    let out = run(&["--exact-numbers", "get", path, "/n"]).unwrap();
+    assert_eq!(out, "123456789012345678901234567890.1\n");
+    assert_eq!(
+        run(&["get", path, "/n"]).unwrap(),
+        "1.2345678901234568e29\n"
+    );
+}
+
+#[test]
+fn test_validate_exact_numbers() {
+    let path = input("validate.json", HUGE_EXPONENT);
+    let path = path.to_str().unwrap();
+    run(&["validate", "--quiet", path]).unwrap();
+    let out = run(&["--exact-numbers", "validate", "--quiet", path]).unwrap_err();
+    assert!(out.contains("1 of 1 files failed"), "{}", out);
+}
+
+#[test]
+fn test_bare_file_exact_numbers() {
+    let path = input("bare.json", HUGE_EXPONENT);
+    let path = path.to_str().unwrap();
+    assert_eq!(run(&[path]).unwrap(), "Successfully parsed JSON.\n");
+    let err = run(&["--exact-numbers", path]).unwrap_err();
+    assert!(err.contains("exponent out of range"), "{}", err);
+    assert!(run(&[path, "jq", "."]).is_err());
+}
+
+#[test]
+fn test_redact_hash_key_from_environment() {
+    let rules = input(
+        "rules.json",
+        r#"{"rules": [{"key": "pin", "action": "hash"}]}"#,
+    );
+    let payload = input("payload.json", r#"{"pin": 1234}"#);
+    let redact = |key: Option<&str>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_jsonrs"));
+        command
+            .arg("redact")
+            .arg("--rules")
+            .arg(&rules)
+            .arg(&payload);
+        match key {
+            Some(key) => command.env("JSONRS_HASH_KEY", key),
+            None => command.env_remove("JSONRS_HASH_KEY"),
+        };
+        let output = command.output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    let (ok, out) = redact(Some("secret"));
+    assert!(ok && out.contains("hmac-sha256:"), "{}", out);
+    assert_ne!(redact(Some("other")).1, out);
+    assert!(!redact(None).0);
+}
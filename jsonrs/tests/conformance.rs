@@ -0,0 +1,103 @@
+//! Runs the parsing cases of JSONTestSuite through the decoder and parser.
+//!
+//! Cases starting with `y_` must be accepted and `n_` rejected. The outcome of
+//! the implementation-defined `i_` cases is compared against the checked-in
+//! report; set `UPDATE_CONFORMANCE=1` to rewrite it after a deliberate change.
+//! The cases are read from `tests/JSONTestSuite`, or from the upstream checkout
+//! in `JSONTESTSUITE_DIR` if set.
+
+use std::{fs, path::PathBuf};
+
+use jsonrs::{encoding, lexer::Lexer, parser::Parser};
+
+fn suite_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/JSONTestSuite")
+}
+
+/// Where the cases are, which is not where the report is kept when running
+/// against another checkout.
+fn cases_dir() -> PathBuf {
+    std::env::var_os("JSONTESTSUITE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(suite_dir)
+        .join("test_parsing")
+}
+
+/// All cases whose name starts with `prefix`, sorted by name.
+fn cases(prefix: &str) -> Vec<(String, Vec<u8>)> {
+    let mut cases: Vec<_> = fs::read_dir(cases_dir())
+        .expect("test suite should be present")
+        .map(|entry| entry.expect("test suite should be readable").path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            name.starts_with(prefix)
+                .then(|| (name, fs::read(&path).expect("case should be readable")))
+        })
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no {} cases found", prefix);
+    cases
+}
+
+fn accepts(input: &[u8]) -> bool {
+    let Ok(text) = encoding::decode(input) else {
+        return false;
+    };
+    let mut l = Lexer::new(text);
+    let mut p = Parser::new(&mut l);
+    p.parse().is_ok()
+}
+
+#[test]
+fn test_accepts_valid_documents() {
+    let failures: Vec<_> = cases("y_")
+        .into_iter()
+        .filter(|(_, input)| !accepts(input))
+        .map(|(name, _)| name)
+        .collect();
+    assert!(failures.is_empty(), "rejected: {:#?}", failures);
+}
+
+#[test]
+fn test_rejects_invalid_documents() {
+    let failures: Vec<_> = cases("n_")
+        .into_iter()
+        .filter(|(_, input)| accepts(input))
+        .map(|(name, _)| name)
+        .collect();
+    assert!(failures.is_empty(), "accepted: {:#?}", failures);
+}
+
+#[test]
+fn test_implementation_defined_report() {
+    // the commit `vendor.sh` copied the cases from, if they were vendored
+    let source = match fs::read_to_string(suite_dir().join("UPSTREAM")) {
+        Ok(commit) => format!("upstream commit `{}`", commit.trim()),
+        Err(_) => "cases transcribed by hand, not an upstream commit".to_string(),
+    };
+    let mut report = format!(
+        "# JSONTestSuite implementation-defined cases\n\n\
+         Generated by `tests/conformance.rs` from {}.\n\n\
+         | case | result |\n\
+         | --- | --- |\n",
+        source
+    );
+    for (name, input) in cases("i_") {
+        let result = if accepts(&input) {
+            "accepted"
+        } else {
+            "rejected"
+        };
+        report.push_str(&format!("| `{}` | {} |\n", name, result));
+    }
+
+    let path = suite_dir().join("CONFORMANCE.md");
+    if std::env::var_os("UPDATE_CONFORMANCE").is_some() {
+        fs::write(&path, &report).expect("report should be writable");
+    }
+    let expected = fs::read_to_string(&path).expect("report should be present");
+    assert_eq!(
+        report, expected,
+        "behavior on i_ cases changed; rerun with UPDATE_CONFORMANCE=1 if intended"
+    );
+}
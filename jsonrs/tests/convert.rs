@@ -0,0 +1,409 @@
+use jsonrs::{
+    convert::{self, cbor, csv, msgpack, toml, yaml, BinaryFormat, EncodeOptions, Format},
+    lexer::Lexer,
+    parser::Parser,
+    value::Value,
+};
+
+fn json(input: &str) -> Value {
+    input.parse().expect("test JSON should parse")
+}
+
+/// Parse `input` keeping numbers as exact decimals.
+fn exact(input: &str) -> Value {
+    let mut lexer = Lexer::new(input.into());
+    Parser::new(&mut lexer).exact_numbers(true).parse().unwrap()
+}
+
+#[test]
+fn test_yaml_block_and_flow() {
+    let input = r#"
+# service config
+name: api
+replicas: 3
+ratio: 0.5
+debug: false
+owner: ~
+tags: [web, "public facing"]
+limits: {cpu: 2, memory: 512Mi}
+ports:
+  - name: http
+    port: 80
+  - name: https
+    port: 443
+script: |
+  echo one
+  echo two
+"#;
+
+    let value = yaml::from_str(input).unwrap();
+
+    let expected = json(
+        r#"{
+  "name": "api", "replicas": 3, "ratio": 0.5, "debug": false, "owner": null,
+  "tags": ["web", "public facing"],
+  "limits": {"cpu": 2, "memory": "512Mi"},
+  "ports": [{"name": "http", "port": 80}, {"name": "https", "port": 443}],
+  "script": "echo one\necho two\n"
+}"#,
+    );
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn test_yaml_round_trip() {
+    let value = json(
+        r#"{"a": [1, [2, 3], {"b": "x: y", "c": []}], "d": {}, "e": "true", "f": "", "g": -1.5}"#,
+    );
+
+    let written = yaml::to_string(&value);
+
+    assert_eq!(yaml::from_str(&written).unwrap(), value, "{}", written);
+}
+
+#[test]
+fn test_yaml_comments() {
+    let input = r#"
+note: don't # the apostrophe opens no quote
+quoted: 'a # b' # comment
+escaped: 'it''s # still quoted'
+items: [it's, "x # y"] # comment
+list:
+  - "value" # comment
+"#;
+    let value = yaml::from_str(input).unwrap();
+    let expected = json(
+        r#"{
+  "note": "don't", "quoted": "a # b", "escaped": "it's # still quoted",
+  "items": ["it's", "x # y"], "list": ["value"]
+}"#,
+    );
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn test_yaml_non_finite_numbers() {
+    for (input, line) in [
+        ("a: 1\nb: .inf", 2),
+        ("a: -.Inf", 1),
+        ("a:\n  - .nan", 2),
+        ("a: 1e999", 1),
+    ] {
+        let err = convert::read(Format::Yaml, input).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains(&format!("line {}:", line)), "{}", message);
+    }
+    assert_eq!(
+        convert::read(Format::Yaml, "a: '.inf'").unwrap(),
+        json(r#"{"a": ".inf"}"#)
+    );
+}
+
+#[test]
+fn test_toml_tables() {
+    let input = r#"
+title = "example"
+port = 8_080
+[database]
+hosts = ["a", "b"]
+options = { timeout = 5, retry = true }
+[[users]]
+name = "ann"
+[[users]]
+name = "bob"
+roles.admin = true
+"#;
+
+    let value = toml::from_str(input).unwrap();
+
+    let expected = json(
+        r#"{
+  "title": "example", "port": 8080,
+  "database": {"hosts": ["a", "b"], "options": {"timeout": 5, "retry": true}},
+  "users": [{"name": "ann"}, {"name": "bob", "roles": {"admin": true}}]
+}"#,
+    );
+    assert_eq!(value, expected);
+    assert_eq!(
+        toml::from_str(&toml::to_string(&value).unwrap()).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn test_toml_unrepresentable() {
+    let err = toml::to_string(&json(r#"{"a": {"b": [1, null]}}"#)).unwrap_err();
+    assert!(err.to_string().contains(".a.b[1]"), "{}", err);
+
+    assert!(toml::to_string(&json("[1, 2]")).is_err());
+    assert!(toml::from_str("a = 1\na = 2").is_err());
+}
+
+#[test]
+fn test_toml_non_finite_numbers() {
+    for (input, line) in [
+        ("a = 1\nb = inf", 2),
+        ("a = -inf", 1),
+        ("a = [1, nan]", 1),
+        ("a = 1e999", 1),
+    ] {
+        let err = convert::read(Format::Toml, input).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains(&format!("line {}:", line)), "{}", message);
+    }
+}
+
+#[test]
+fn test_csv_round_trip() {
+    let input = "id,name,score\n1,\"Smith, J\",9.5\n2,\"say \"\"hi\"\"\",\n3,\"42\",-1\n";
+
+    let value = csv::from_str(input).unwrap();
+
+    let expected = json(
+        r#"[
+  {"id": 1, "name": "Smith, J", "score": 9.5},
+  {"id": 2, "name": "say \"hi\"", "score": null},
+  {"id": 3, "name": "42", "score": -1}
+]"#,
+    );
+    assert_eq!(value, expected);
+    assert_eq!(
+        csv::from_str(&csv::to_string(&value).unwrap()).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn test_csv_unrepresentable() {
+    let err = csv::to_string(&json(r#"[{"a": 1}, {"a": {"nested": true}}]"#)).unwrap_err();
+    assert!(err.to_string().contains(".[1].a"), "{}", err);
+
+    assert!(csv::to_string(&json(r#"{"a": 1}"#)).is_err());
+    assert!(csv::from_str("a,b\n1\n").is_err());
+}
+
+#[test]
+fn test_convert_between_formats() {
+    let toml_input = "name = \"x\"\n[[rows]]\nid = 1\n[[rows]]\nid = 2\n";
+
+    let value = convert::read(Format::Toml, toml_input).unwrap();
+    let yaml_output = convert::write(Format::Yaml, &value).unwrap();
+
+    assert_eq!(yaml_output, "name: x\nrows:\n  - id: 1\n  - id: 2\n");
+    assert_eq!(convert::read(Format::Yaml, &yaml_output).unwrap(), value);
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_cbor_rfc8949_vectors() {
+    let options = EncodeOptions::deterministic();
+
+    // examples from RFC 8949 appendix A
+    let test_cases = vec![
+        ("0", "00"),
+        ("23", "17"),
+        ("24", "1818"),
+        ("1000000", "1a000f4240"),
+        ("-1", "20"),
+        ("-1000", "3903e7"),
+        ("-9223372036854775808", "3b7fffffffffffffff"),
+        ("1.5", "f93e00"),
+        ("100000.0", "fa47c35000"),
+        ("1.1", "fb3ff199999999999a"),
+        ("-4.1", "fbc010666666666666"),
+        ("5.960464477539063e-8", "f90001"),
+        ("false", "f4"),
+        ("null", "f6"),
+        (r#""IETF""#, "6449455446"),
+        (r#""\u00fc""#, "62c3bc"),
+        ("[1, [2, 3], [4, 5]]", "8301820203820405"),
+        (r#"{"a": 1, "b": [2, 3]}"#, "a26161016162820203"),
+    ];
+
+    for (input, expected) in test_cases {
+        let value = json(input);
+        let encoded = cbor::to_vec(&value, &options);
+        assert_eq!(hex(&encoded), expected, "encoding {}", input);
+        assert_eq!(
+            cbor::from_slice(&encoded).unwrap(),
+            value,
+            "decoding {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_cbor_decoding() {
+    // indefinite-length containers and strings, and a tagged date string
+    let test_cases = vec![
+        ("9f018202039f0405ffff", "[1, [2, 3], [4, 5]]"),
+        ("bf61610161629f0203ffff", r#"{"a": 1, "b": [2, 3]}"#),
+        ("7f657374726561646d696e67ff", r#""streaming""#),
+        (
+            "c074323031332d30332d32315432303a30343a30305a",
+            r#""2013-03-21T20:04:00Z""#,
+        ),
+        ("f7", "null"),
+    ];
+
+    for (input, expected) in test_cases {
+        assert_eq!(cbor::from_slice(&unhex(input)).unwrap(), json(expected));
+    }
+
+    for invalid in [
+        "",
+        "18",
+        "4401020304",
+        "a10102",
+        "9b00000000ffffffff",
+        "0000",
+    ] {
+        assert!(cbor::from_slice(&unhex(invalid)).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_cbor_decimal_fractions() {
+    let options = EncodeOptions::default();
+    let test_cases = vec![
+        // RFC 8949 section 3.4.4
+        ("273.15", "c48221196ab3"),
+        ("-1.5", "c482202e"),
+        ("0.10", "c482210a"),
+        ("18446744073709551616", "c48200c249010000000000000000"),
+        ("-18446744073709551616", "c482003bffffffffffffffff"),
+        (
+            "-123456789012345678901234567890.1",
+            "c48220c34d0f951a9fa3a286c94f0e766c34",
+        ),
+    ];
+
+    for (input, expected) in test_cases {
+        let value = exact(input);
+        let encoded = cbor::to_vec(&value, &options);
+        assert_eq!(hex(&encoded), expected, "encoding {}", input);
+        let decoded = cbor::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, value, "decoding {}", input);
+        assert_eq!(decoded.to_string(), input);
+    }
+
+    for invalid in [
+        "c401",
+        "c48201",
+        "c4820101c24101",
+        "c4821bffffffffffffffff01",
+    ] {
+        assert!(cbor::from_slice(&unhex(invalid)).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_binary_non_finite_floats() {
+    for input in [
+        "f97c00",
+        "f9fc00",
+        "f97e00",
+        "fa7fc00000",
+        "fb7ff0000000000000",
+    ] {
+        let err = cbor::from_slice(&unhex(input)).unwrap_err();
+        assert!(err.to_string().contains("cannot be represented"), "{}", err);
+    }
+    for input in ["ca7f800000", "caff800000", "cb7ff8000000000000"] {
+        let err = msgpack::from_slice(&unhex(input)).unwrap_err();
+        assert!(err.to_string().contains("cannot be represented"), "{}", err);
+    }
+}
+
+#[test]
+fn test_msgpack_decimals() {
+    let options = EncodeOptions::default();
+    let value = exact(r#"{"a": [0.10, 1e400]}"#);
+    let err = msgpack::to_vec(&value, &options).unwrap_err();
+    assert!(err.to_string().contains(".a[1]"), "{}", err);
+
+    let value = exact(r#"{"a": 123456789012345678901234567890.1}"#);
+    assert!(msgpack::to_vec(&value, &options).is_err());
+
+    let value = exact("[0.10, 1.5]");
+    let encoded = msgpack::to_vec(&value, &options).unwrap();
+    assert_eq!(msgpack::from_slice(&encoded).unwrap(), value);
+}
+
+#[test]
+fn test_cbor_canonical_key_order() {
+    // deterministic encoding sorts shorter keys first, unlike string order
+    let value = json(r#"{"aa": 1, "b": 2}"#);
+
+    let sorted = cbor::to_vec(&value, &EncodeOptions::deterministic());
+    let plain = cbor::to_vec(&value, &EncodeOptions::default());
+
+    assert_eq!(hex(&sorted), "a261620262616101");
+    assert_eq!(hex(&plain), "a262616101616202");
+}
+
+#[test]
+fn test_msgpack_encoding() {
+    let options = EncodeOptions::deterministic();
+
+    let test_cases = vec![
+        ("0", "00"),
+        ("127", "7f"),
+        ("128", "cc80"),
+        ("65536", "ce00010000"),
+        ("-1", "ff"),
+        ("-33", "d0df"),
+        ("-129", "d1ff7f"),
+        ("1.5", "ca3fc00000"),
+        ("1.1", "cb3ff199999999999a"),
+        ("true", "c3"),
+        ("null", "c0"),
+        (r#""hi""#, "a26869"),
+        ("[1, [], {}]", "93019080"),
+        (r#"{"a": [1, 2]}"#, "81a161920102"),
+    ];
+
+    for (input, expected) in test_cases {
+        let value = json(input);
+        let encoded = msgpack::to_vec(&value, &options).unwrap();
+        assert_eq!(hex(&encoded), expected, "encoding {}", input);
+        assert_eq!(
+            msgpack::from_slice(&encoded).unwrap(),
+            value,
+            "decoding {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_binary_round_trip() {
+    let long = "x".repeat(300);
+    let many: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+    let value = json(&format!(
+        r#"{{"long": "{}", "many": [{}], "nested": {{"f": -0.0, "big": 9007199254740993, "neg": -4294967297}}}}"#,
+        long,
+        many.join(",")
+    ));
+
+    for format in [BinaryFormat::Cbor, BinaryFormat::Msgpack] {
+        for options in [EncodeOptions::default(), EncodeOptions::deterministic()] {
+            let encoded = convert::encode(format, &value, &options).unwrap();
+            assert_eq!(convert::decode(format, &encoded).unwrap(), value);
+        }
+    }
+
+    assert!(msgpack::from_slice(&unhex("c40100")).is_err());
+    assert!(msgpack::from_slice(&unhex("dc0005")).is_err());
+}
@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+
+use jsonrs::{
+    decimal::BigDecimal,
+    lexer::Lexer,
+    parser::Parser,
+    value::{Number, Value},
+};
+use proptest::prelude::*;
+
+fn dec(s: &str) -> BigDecimal {
+    s.parse().expect("test decimal should parse")
+}
+
+fn parse_exact(input: &str) -> Value {
+    let mut l = Lexer::new(input.into());
+    Parser::new(&mut l).exact_numbers(true).parse().unwrap()
+}
+
+#[test]
+fn test_display_keeps_digits() {
+    for (input, expected) in [
+        ("0", "0"),
+        ("-0", "-0"),
+        ("1.50", "1.50"),
+        ("0.00", "0.00"),
+        ("007.5", "7.5"),
+        ("+3", "3"),
+        (
+            "12345678901234567890.123456789",
+            "12345678901234567890.123456789",
+        ),
+        ("0.00001", "0.00001"),
+        ("1e-7", "0.0000001"),
+        ("1e-8", "1e-8"),
+        ("1.5E+3", "1500"),
+        ("1e20", "100000000000000000000"),
+        ("1e21", "1e21"),
+        ("0e5", "0"),
+        ("-2.5e-400", "-2.5e-400"),
+        (".5", "0.5"),
+        ("5.", "5"),
+    ] {
+        assert_eq!(dec(input).to_string(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_rejects_malformed() {
+    for input in [
+        "", "-", ".", "1e", "1e+", "1x", "1.2.3", "--1", "1e5.5", "0x10",
+    ] {
+        assert!(input.parse::<BigDecimal>().is_err(), "{:?}", input);
+    }
+    assert!("1e9999999999999999999".parse::<BigDecimal>().is_err());
+}
+
+#[test]
+fn test_compare() {
+    assert_eq!(dec("1.50"), dec("1.5"));
+    assert_eq!(dec("-0"), dec("0.000"));
+    assert_eq!(dec("1e3"), dec("1000"));
+    assert!(dec("0.1") < dec("0.11"));
+    assert!(dec("-0.11") < dec("-0.1"));
+    assert!(dec("-1") < dec("0"));
+    assert!(dec("99") < dec("1e2"));
+    assert!(dec("1e-400") > dec("0"));
+    assert!(dec("-1e400") < dec("-1e399"));
+    assert_eq!(
+        dec("12345678901234567890.000000000000000000001").cmp(&dec("12345678901234567890")),
+        Ordering::Greater
+    );
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(dec("1.500").normalize().to_string(), "1.5");
+    assert_eq!(dec("1500").normalize().to_string(), "1500");
+    assert_eq!(dec("-0.00").normalize().to_string(), "0");
+    assert!(dec("2.000").is_integer());
+    assert!(!dec("2.001").is_integer());
+}
+
+#[test]
+fn test_to_i64() {
+    assert_eq!(dec("1.2e3").to_i64().unwrap(), 1200);
+    assert_eq!(dec("-9223372036854775808").to_i64().unwrap(), i64::MIN);
+    assert_eq!(dec("9223372036854775807.000").to_i64().unwrap(), i64::MAX);
+
+    let err = dec("9223372036854775808").to_i64().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "9223372036854775808 is out of range for i64"
+    );
+    assert!(dec("1e19").to_i64().is_err());
+    assert_eq!(
+        dec("1.5").to_i64().unwrap_err().to_string(),
+        "1.5 has a fractional part"
+    );
+}
+
+#[test]
+fn test_to_f64() {
+    assert_eq!(dec("0.1").to_f64().unwrap(), 0.1);
+    assert_eq!(dec("-2.5e-3").to_f64().unwrap(), -0.0025);
+    assert_eq!(dec("1e-400").to_f64().unwrap(), 0.0);
+    assert_eq!(
+        dec("1e400").to_f64().unwrap_err().to_string(),
+        "1e400 is out of range for f64"
+    );
+}
+
+#[test]
+fn test_from_machine_numbers() {
+    assert_eq!(
+        BigDecimal::from(i64::MIN).to_string(),
+        "-9223372036854775808"
+    );
+    assert_eq!(BigDecimal::try_from(0.1).unwrap(), dec("0.1"));
+    assert_eq!(BigDecimal::try_from(1e300).unwrap(), dec("1e300"));
+    assert!(BigDecimal::try_from(f64::NAN).is_err());
+}
+
+#[test]
+fn test_exact_round_trip() {
+    let input = r#"{"amount":12345678901234567890.123456789,"fee":0.10,"n":[-0,1e-400,9223372036854775807]}"#;
+    let value = parse_exact(input);
+    assert_eq!(value.to_string(), input);
+    assert!(matches!(
+        value.pointer("/n/2"),
+        Some(Value::Number(Number::Int(i64::MAX)))
+    ));
+
+    // without the option the same document loses digits
+    let lossy: Value = input.parse().unwrap();
+    assert_ne!(lossy.to_string(), input);
+}
+
+#[test]
+fn test_mixed_comparisons() {
+    let decimal = |s| Number::Decimal(dec(s));
+    assert_eq!(decimal("2.0"), Number::Int(2));
+    assert_eq!(decimal("0.1"), Number::Float(0.1));
+    assert!(decimal("1e400") > Number::Float(f64::MAX));
+    assert!(decimal("-1e400") < Number::Int(i64::MIN));
+    assert_eq!(decimal("1").partial_cmp(&Number::Float(f64::NAN)), None);
+    assert_eq!(decimal("1e400").as_f64(), f64::INFINITY);
+    assert_eq!(decimal("-12.000").as_i64(), Some(-12));
+}
+
+/// `mantissa * 10^-scale`, written out as a decimal string.
+fn scaled(mantissa: i64, scale: u32) -> String {
+    format!("{}e-{}", mantissa, scale)
+}
+
+proptest! {
+    #[test]
+    fn ordering_matches_integers(a: i64, sa in 0u32..6, b: i64, sb in 0u32..6) {
+        let max = sa.max(sb);
+        let x = a as i128 * 10i128.pow(max - sa);
+        let y = b as i128 * 10i128.pow(max - sb);
+        prop_assert_eq!(dec(&scaled(a, sa)).cmp(&dec(&scaled(b, sb))), x.cmp(&y));
+    }
+
+    #[test]
+    fn display_round_trips(
+        input in r"-?(0|[1-9][0-9]{0,30})(\.[0-9]{1,30})?([eE][+-]?[0-9]{1,4})?"
+    ) {
+        let d = dec(&input);
+        let printed = d.to_string();
+        let reparsed = dec(&printed);
+        prop_assert_eq!(&reparsed, &d);
+        prop_assert_eq!(reparsed.to_string(), printed);
+    }
+}
@@ -0,0 +1,84 @@
+use jsonrs::{
+    encoding::{decode, detect, Encoding},
+    value::Value,
+};
+
+const TEXT: &str = r#"{"name": "Grüße 😀", "n": [1, 2]}"#;
+
+fn encode(text: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Utf8 => text.as_bytes().to_vec(),
+        Encoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        Encoding::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        Encoding::Utf32Le => text
+            .chars()
+            .flat_map(|c| (c as u32).to_le_bytes())
+            .collect(),
+        Encoding::Utf32Be => text
+            .chars()
+            .flat_map(|c| (c as u32).to_be_bytes())
+            .collect(),
+    }
+}
+
+const ENCODINGS: [Encoding; 5] = [
+    Encoding::Utf8,
+    Encoding::Utf16Le,
+    Encoding::Utf16Be,
+    Encoding::Utf32Le,
+    Encoding::Utf32Be,
+];
+
+#[test]
+fn test_detect_without_bom() {
+    for encoding in ENCODINGS {
+        assert_eq!(detect(&encode(TEXT, encoding)), (encoding, 0));
+        // a lone scalar is still recognised from its first two characters
+        assert_eq!(detect(&encode("7", encoding)).0, encoding, "{}", encoding);
+    }
+}
+
+#[test]
+fn test_decode_with_and_without_bom() {
+    for encoding in ENCODINGS {
+        let plain = encode(TEXT, encoding);
+        let with_bom = encode(&format!("\u{feff}{}", TEXT), encoding);
+        assert_eq!(detect(&with_bom).0, encoding);
+        assert_eq!(decode(&plain).unwrap(), TEXT, "{}", encoding);
+        assert_eq!(decode(&with_bom).unwrap(), TEXT, "{} with BOM", encoding);
+    }
+}
+
+#[test]
+fn test_decoded_text_parses() {
+    let value: Value = decode(&encode(TEXT, Encoding::Utf16Le))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(value.pointer("/name"), Some(&Value::from("Grüße 😀")));
+}
+
+#[test]
+fn test_invalid_input_reports_offset() {
+    let mut bytes = b"\xef\xbb\xbf{\"a\": \"".to_vec();
+    bytes.extend_from_slice(b"\xc3\x28\"}");
+    let err = decode(&bytes).unwrap_err();
+    assert_eq!(err.to_string(), "invalid UTF-8 at byte 10");
+
+    // a high surrogate followed by `"`
+    let mut bytes = encode("[\"", Encoding::Utf16Le);
+    bytes.extend_from_slice(&[0x3d, 0xd8, b'"', 0]);
+    let err = decode(&bytes).unwrap_err();
+    assert_eq!(err.to_string(), "unpaired surrogate in UTF-16LE at byte 4");
+
+    let err = decode(&encode("[1]", Encoding::Utf16Be)[..5]).unwrap_err();
+    assert!(err.to_string().contains("odd number of bytes"));
+
+    let mut bytes = encode("[", Encoding::Utf32Be);
+    bytes.extend_from_slice(&0x11_0000u32.to_be_bytes());
+    let err = decode(&bytes).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid code point 0x110000 in UTF-32BE at byte 4"
+    );
+}
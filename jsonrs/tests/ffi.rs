@@ -0,0 +1,154 @@
+//! The C API: Rust-side checks of `jsonrs::ffi`, a check that
+//! `include/jsonrs.h` is what cbindgen generates from it, and a C program
+//! compiled and linked against the static library. Set `UPDATE_HEADER=1` to
+//! regenerate the header after changing the bindings.
+
+use std::{
+    ffi::{CStr, CString},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    ptr,
+};
+
+use jsonrs::ffi::{self, JsonrsError};
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Parse through the C API, returning the document as compact JSON or the
+/// error's line, column and message.
+fn parse(text: &str) -> Result<String, (usize, usize, String)> {
+    let mut error = JsonrsError {
+        line: 0,
+        column: 0,
+        offset: 0,
+        message: ptr::null_mut(),
+    };
+    unsafe {
+        let doc = ffi::jsonrs_parse(text.as_ptr().cast(), text.len(), &mut error);
+        if doc.is_null() {
+            let message = CStr::from_ptr(error.message).to_str().unwrap().to_string();
+            ffi::jsonrs_error_free(&mut error);
+            return Err((error.line, error.column, message));
+        }
+        let json = ffi::jsonrs_to_string(doc);
+        let out = CStr::from_ptr(json).to_str().unwrap().to_string();
+        ffi::jsonrs_string_free(json);
+        ffi::jsonrs_document_free(doc);
+        Ok(out)
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(
+        parse(r#" {"b": [1, 2.5], "a": null} "#).unwrap(),
+        r#"{"a":null,"b":[1,2.5]}"#
+    );
+
+    let (line, column, message) = parse("[1,\n 2,\n x]").unwrap_err();
+    assert_eq!((line, column), (3, 2));
+    assert!(message.contains("ILLEGAL"), "{}", message);
+
+    // columns count characters, not bytes
+    assert_eq!(parse("{\"é\": 1 2}").unwrap_err().1, 9);
+    assert_eq!(parse("[1] [2]").unwrap_err().1, 5);
+    assert_eq!(parse("").unwrap_err().1, 1);
+}
+
+#[test]
+fn test_pointer() {
+    let text = r#"{"a": {"b~c": [true]}}"#;
+    unsafe {
+        let doc = ffi::jsonrs_parse(text.as_ptr().cast(), text.len(), ptr::null_mut());
+        let lookup = |pointer: &str| {
+            let pointer = CString::new(pointer).unwrap();
+            let value = ffi::jsonrs_pointer(doc, pointer.as_ptr());
+            if value.is_null() {
+                return None;
+            }
+            let out = CStr::from_ptr(value).to_str().unwrap().to_string();
+            ffi::jsonrs_string_free(value);
+            Some(out)
+        };
+        assert_eq!(lookup("/a/b~0c/0").as_deref(), Some("true"));
+        assert_eq!(lookup("").as_deref(), Some(r#"{"a":{"b~c":[true]}}"#));
+        assert_eq!(lookup("/a/missing"), None);
+        assert!(ffi::jsonrs_pointer(doc, ptr::null()).is_null());
+        ffi::jsonrs_document_free(doc);
+    }
+}
+
+/// The C declarations of `src/ffi.rs`, with their doc comments.
+fn header() -> String {
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        header: Some("/* Generated from src/ffi.rs by tests/ffi.rs; do not edit. */".into()),
+        include_guard: Some("JSONRS_H".into()),
+        no_includes: true,
+        sys_includes: vec!["stdbool.h".into(), "stddef.h".into(), "stdint.h".into()],
+        cpp_compat: true,
+        usize_is_size_t: true,
+        style: cbindgen::Style::Type,
+        tab_width: 4,
+        documentation_style: cbindgen::DocumentationStyle::C99,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(manifest_dir().join("src/ffi.rs"))
+        .generate()
+        .expect("cbindgen should understand src/ffi.rs")
+        .write(&mut out);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_header_is_current() {
+    let path = manifest_dir().join("include/jsonrs.h");
+    let header = header();
+    if std::env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&path, &header).expect("header should be writable");
+    }
+    let expected = fs::read_to_string(&path).expect("header should be present");
+    assert_eq!(
+        header, expected,
+        "src/ffi.rs changed; rerun with UPDATE_HEADER=1 to regenerate include/jsonrs.h"
+    );
+}
+
+/// `target/debug` or `target/release`, where the static library is built.
+fn library_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().and_then(Path::parent).unwrap().to_path_buf()
+}
+
+#[test]
+fn test_c_harness() {
+    let library = library_dir().join("libjsonrs.a");
+    assert!(library.exists(), "{:?} should be built", library);
+    let binary = std::env::temp_dir().join(format!("jsonrs-harness-{}", std::process::id()));
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+
+    let status = Command::new(&cc)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-pedantic", "-I"])
+        .arg(manifest_dir().join("include"))
+        .arg(manifest_dir().join("tests/c/harness.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&binary)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run `{}`: {}", cc, e));
+    assert!(status.success(), "compiling the C harness failed");
+
+    let output = Command::new(&binary).output().unwrap();
+    let _ = fs::remove_file(&binary);
+    assert!(
+        output.status.success(),
+        "C harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
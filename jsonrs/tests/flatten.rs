@@ -0,0 +1,179 @@
+use jsonrs::{
+    flatten::{flatten, unflatten, FlattenOptions, IndexStyle},
+    value::Value,
+};
+
+fn json(input: &str) -> Value {
+    input.parse().expect("test JSON should parse")
+}
+
+fn flat(value: &Value, options: &FlattenOptions) -> Value {
+    Value::Object(flatten(value, options))
+}
+
+const DOC: &str = r#"{"a": {"b": 1, "c": [true, null]}, "d": "x", "e": [], "f": {}}"#;
+
+#[test]
+fn test_flatten_dotted() {
+    let flat = flat(&json(DOC), &FlattenOptions::default());
+    assert_eq!(
+        flat,
+        json(r#"{"a.b": 1, "a.c.0": true, "a.c.1": null, "d": "x", "e": [], "f": {}}"#)
+    );
+}
+
+#[test]
+fn test_flatten_brackets_and_separator() {
+    let options = FlattenOptions {
+        separator: "/".into(),
+        index_style: IndexStyle::Brackets,
+        ..Default::default()
+    };
+    let flat = flat(&json(r#"{"items": [{"name": "a"}, [1]]}"#), &options);
+    assert_eq!(flat, json(r#"{"items[0]/name": "a", "items[1][0]": 1}"#));
+}
+
+#[test]
+fn test_flatten_pointer_escapes_keys() {
+    let options = FlattenOptions {
+        pointer: true,
+        ..Default::default()
+    };
+    let flat = flat(&json(r#"{"a/b": {"~c": [1]}, "": 2}"#), &options);
+    assert_eq!(flat, json(r#"{"/a~1b/~0c/0": 1, "/": 2}"#));
+}
+
+#[test]
+fn test_flatten_scalar_root() {
+    let flat = flat(&json("3"), &FlattenOptions::default());
+    assert_eq!(flat, json(r#"{"": 3}"#));
+}
+
+#[test]
+fn test_round_trip() {
+    let styles = [
+        FlattenOptions::default(),
+        FlattenOptions {
+            separator: "__".into(),
+            index_style: IndexStyle::Brackets,
+            pointer: false,
+        },
+        FlattenOptions {
+            pointer: true,
+            ..Default::default()
+        },
+    ];
+    for doc in [DOC, r#"[[1, {"a": []}], {"b": null}]"#, "{}", r#""s""#] {
+        let value = json(doc);
+        for options in &styles {
+            let restored = unflatten(&flatten(&value, options), options).unwrap();
+            assert_eq!(restored, value, "{} with {:?}", doc, options);
+        }
+    }
+}
+
+#[test]
+fn test_pointer_round_trip_numeric_keys() {
+    let options = FlattenOptions {
+        pointer: true,
+        ..Default::default()
+    };
+    for doc in [
+        r#"{"2024": {"a": 1}}"#,
+        r#"{"years": {"1999": [1, 2], "2024": {"7": true}}}"#,
+        r#"{"a": {"1": "x", "2": "y"}, "b": ["x", "y"]}"#,
+        r#"{"a": {"0": 1, "01": 2}}"#,
+        r#"[{"4000000000": null}]"#,
+    ] {
+        let value = json(doc);
+        let restored = unflatten(&flatten(&value, &options), &options).unwrap();
+        assert_eq!(restored, value, "{}", doc);
+    }
+
+    // keys that are exactly the indices of an array cannot be told apart
+    let value = json(r#"{"a": {"0": "x", "1": "y"}}"#);
+    let restored = unflatten(&flatten(&value, &options), &options).unwrap();
+    assert_eq!(restored, json(r#"{"a": ["x", "y"]}"#));
+}
+
+#[test]
+fn test_unflatten_fills_gaps_with_null() {
+    let Value::Object(flat) = json(r#"{"a.2": 1}"#) else {
+        unreachable!()
+    };
+    let value = unflatten(&flat, &FlattenOptions::default()).unwrap();
+    assert_eq!(value, json(r#"{"a": [null, null, 1]}"#));
+}
+
+#[test]
+fn test_unflatten_rejects_huge_indices() {
+    for input in [
+        r#"{"a.18446744073709551615": 1}"#,
+        r#"{"a.4000000000": 1}"#,
+        r#"{"a.0": 1, "b.2000": 2}"#,
+    ] {
+        let Value::Object(flat) = json(input) else {
+            unreachable!()
+        };
+        let err = unflatten(&flat, &FlattenOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}: {}", input, err);
+    }
+
+    // gaps up to the limit are still filled
+    let Value::Object(flat) = json(r#"{"a.1000": 1}"#) else {
+        unreachable!()
+    };
+    let value = unflatten(&flat, &FlattenOptions::default()).unwrap();
+    assert_eq!(value.pointer("/a/1000"), Some(&Value::from(1)));
+}
+
+#[test]
+fn test_unflatten_leading_zero_is_a_key() {
+    let Value::Object(flat) = json(r#"{"a.01": 1, "a.1": 2}"#) else {
+        unreachable!()
+    };
+    let err = unflatten(&flat, &FlattenOptions::default()).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("conflicts with an existing object"));
+}
+
+#[test]
+fn test_unflatten_conflicts() {
+    for (input, message) in [
+        (r#"{"a": 1, "a.b": 2}"#, "conflicts with an existing number"),
+        (
+            r#"{"a.0": 1, "a.b": 2}"#,
+            "conflicts with an existing array",
+        ),
+        (r#"{"a[0]": 1, "a[x]": 2}"#, "invalid array index"),
+        (r#"{"a[0": 1}"#, "unclosed"),
+    ] {
+        let Value::Object(flat) = json(input) else {
+            unreachable!()
+        };
+        let options = FlattenOptions {
+            index_style: IndexStyle::Brackets,
+            ..Default::default()
+        };
+        let options = if input.contains('[') {
+            options
+        } else {
+            FlattenOptions::default()
+        };
+        let err = unflatten(&flat, &options).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", input, err);
+    }
+}
+
+#[test]
+fn test_unflatten_pointer_requires_slash() {
+    let Value::Object(flat) = json(r#"{"a": 1}"#) else {
+        unreachable!()
+    };
+    let options = FlattenOptions {
+        pointer: true,
+        ..Default::default()
+    };
+    assert!(unflatten(&flat, &options).is_err());
+}
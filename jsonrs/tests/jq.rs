@@ -0,0 +1,111 @@
+use jsonrs::{jq::Filter, value::Value};
+
+fn run(filter: &str, input: &str) -> Vec<String> {
+    let filter: Filter = filter.parse().expect("filter should parse");
+    let input: Value = input.parse().expect("input should parse");
+    filter
+        .run(&input)
+        .expect("filter should run")
+        .iter()
+        .map(|v| v.to_string())
+        .collect()
+}
+
+#[test]
+fn test_paths() {
+    let input = r#"{"a": {"b": [10, 20, 30]}, "c d": true}"#;
+
+    let test_cases = vec![
+        (".", vec![r#"{"a":{"b":[10,20,30]},"c d":true}"#]),
+        (".a.b[0]", vec!["10"]),
+        (".a.b[-1]", vec!["30"]),
+        (".a.b[1:]", vec!["[20,30]"]),
+        (".a.b[]", vec!["10", "20", "30"]),
+        (".[\"c d\"]", vec!["true"]),
+        (".missing.deeper", vec!["null"]),
+    ];
+
+    for (filter, expected) in test_cases {
+        assert_eq!(run(filter, input), expected, "filter {:?}", filter);
+    }
+}
+
+#[test]
+fn test_pipes_and_builtins() {
+    let input = r#"{"items": [{"id": 1, "tags": ["x"]}, {"id": 2, "tags": []}, {"id": 3}]}"#;
+
+    let test_cases = vec![
+        (".items | length", vec!["3"]),
+        (".items[] | select(.id >= 2) | .id", vec!["2", "3"]),
+        (".items | map(.id * 10)", vec!["[10,20,30]"]),
+        (".items[0] | keys", vec![r#"["id","tags"]"#]),
+        (".items | map(.id) | add", vec!["6"]),
+        (".items[2].tags // \"none\"", vec![r#""none""#]),
+        (".items[] | .id | not", vec!["false", "false", "false"]),
+    ];
+
+    for (filter, expected) in test_cases {
+        assert_eq!(run(filter, input), expected, "filter {:?}", filter);
+    }
+}
+
+#[test]
+fn test_construction() {
+    let input = r#"{"name": "jsonrs", "version": 1}"#;
+
+    let test_cases = vec![
+        ("[.name, .version]", vec![r#"["jsonrs",1]"#]),
+        ("{name}", vec![r#"{"name":"jsonrs"}"#]),
+        (
+            "{(.name): .version + 1, v: .version | tostring}",
+            vec![r#"{"jsonrs":2,"v":"1"}"#],
+        ),
+        ("{a: (1, 2)}", vec![r#"{"a":1}"#, r#"{"a":2}"#]),
+    ];
+
+    for (filter, expected) in test_cases {
+        assert_eq!(run(filter, input), expected, "filter {:?}", filter);
+    }
+}
+
+#[test]
+fn test_arithmetic_and_comparison() {
+    let input = r#"{"a": 7, "b": 2, "s": "a,b"}"#;
+
+    let test_cases = vec![
+        (
+            ".a + .b, .a - .b, .a * .b, .a % .b",
+            vec!["9", "5", "14", "1"],
+        ),
+        (".a / .b", vec!["3.5"]),
+        (".s / \",\"", vec![r#"["a","b"]"#]),
+        (".a > .b, .a == 7, \"a\" < 1", vec!["true", "true", "false"]),
+        (".a > 5 and .b > 5, .a > 5 or .b > 5", vec!["false", "true"]),
+        (
+            "if .a > 5 then \"big\" else \"small\" end",
+            vec![r#""big""#],
+        ),
+    ];
+
+    for (filter, expected) in test_cases {
+        assert_eq!(run(filter, input), expected, "filter {:?}", filter);
+    }
+}
+
+#[test]
+fn test_errors() {
+    let input: Value = r#"{"a": [1, 2]}"#.parse().unwrap();
+
+    for filter in [".a.b", ".a + 1", "1 / 0", "nope"] {
+        let filter: Filter = filter.parse().unwrap();
+        assert!(filter.run(&input).is_err(), "{:?} should fail", filter);
+    }
+
+    for filter in [".[", "{a:}", "if . then 1", "\"unterminated"] {
+        assert!(
+            filter.parse::<Filter>().is_err(),
+            "{:?} should not parse",
+            filter
+        );
+    }
+}
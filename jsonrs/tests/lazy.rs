@@ -0,0 +1,90 @@
+use jsonrs::{lazy::get_lazy, value::Value};
+
+fn json(input: &str) -> Value {
+    input.parse().expect("test JSON should parse")
+}
+
+const DOC: &str = r#"{
+    "skip": {"deep": [1, {"x": "a]}\"b"}, [[]]], "s": "}"},
+    "users": [
+        {"name": "ada", "roles": ["admin"]},
+        {"name": "bob", "roles": []}
+    ],
+    "a/b": {"~c": 1},
+    "n": -1.5e3,
+    "": "empty key"
+}"#;
+
+#[test]
+fn test_matches_full_parse() {
+    let full = json(DOC);
+    let pointers = [
+        "",
+        "/users/1/name",
+        "/users/0/roles",
+        "/users/0/roles/0",
+        "/a~1b/~0c",
+        "/n",
+        "/",
+        "/skip/deep/1/x",
+        "/missing",
+        "/users/2",
+        "/users/01",
+        "/n/deeper",
+    ];
+    let lazy = get_lazy(DOC, &pointers).unwrap();
+    for (pointer, value) in pointers.iter().zip(lazy) {
+        assert_eq!(value.as_ref(), full.pointer(pointer), "{}", pointer);
+    }
+}
+
+#[test]
+fn test_nested_requests_share_one_value() {
+    let values = get_lazy(DOC, &["/users/0", "/users/0/name"]).unwrap();
+    assert_eq!(
+        values,
+        vec![
+            Some(json(r#"{"name": "ada", "roles": ["admin"]}"#)),
+            Some(json(r#""ada""#))
+        ]
+    );
+}
+
+#[test]
+fn test_duplicate_keys_agree_with_the_parser() {
+    let input = r#"{"a": 1, "b": {"c": 2}, "x": [{"a": 5, "a": 6}], "a": 3, "b": {"d": 4}}"#;
+    let full = json(input);
+    let pointers = ["/a", "/b", "/b/c", "/b/d", "/x/0/a"];
+    let lazy = get_lazy(input, &pointers).unwrap();
+    for (pointer, value) in pointers.iter().zip(lazy) {
+        assert_eq!(value.as_ref(), full.pointer(pointer), "{}", pointer);
+    }
+    assert_eq!(full.pointer("/a"), Some(&Value::from(3)));
+    assert_eq!(full.pointer("/b/c"), None);
+}
+
+#[test]
+fn test_skipped_subtrees_are_not_validated() {
+    let input = r#"{"a": [1 2 ,], "b": true, "c": {"d" ]}"#;
+    assert_eq!(
+        get_lazy(input, &["/b"]).unwrap(),
+        vec![Some(Value::Bool(true))]
+    );
+    // arrays cannot repeat an index, so the scan stops after `/1`
+    let input = r#"[[1 2 ,], true, nonsense"#;
+    assert_eq!(
+        get_lazy(input, &["/1"]).unwrap(),
+        vec![Some(Value::Bool(true))]
+    );
+}
+
+#[test]
+fn test_requested_values_are_validated() {
+    let err = get_lazy(r#"{"a": [1 2]}"#, &["/a"]).unwrap_err();
+    assert!(err.to_string().contains("invalid value at byte 6"));
+
+    for input in [r#"{"a": 1"#, r#"{"a" 1}"#, r#"{"a": "x"#, r#"{"a": [1"#] {
+        assert!(get_lazy(input, &["/b"]).is_err(), "{}", input);
+    }
+    assert!(get_lazy("{}", &["a"]).is_err());
+}
@@ -1,4 +1,4 @@
-use jsonrs::{lexer::Lexer, parser::Parser};
+use jsonrs::{lexer::Lexer, parser::Parser, value::Value};
 
 #[test]
 fn test_parse_empty_object() {
@@ -137,6 +137,47 @@ fn test_illegal_keywords() {
     assert!(result.is_ok(), "{:?}", result.err());
 }
 
+#[test]
+#[should_panic]
+fn test_missing_comma() {
+    let input = String::from(r#"{"key1": true "key2": false}"#);
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+
+    let result = p.parse();
+
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[test]
+fn test_rejects_malformed_values() {
+    for input in [
+        "[1,]",
+        "[012]",
+        "[1.]",
+        "[\"a\tb\"]",
+        "[\"unterminated]",
+        "[\u{0}]",
+        "\u{a0}[]",
+    ] {
+        let mut l = Lexer::new(input.into());
+        let mut p = Parser::new(&mut l);
+        assert!(p.parse().is_err(), "{:?} should be rejected", input);
+    }
+}
+
+#[test]
+fn test_nesting_limit() {
+    let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+
+    let mut l = Lexer::new(nested(jsonrs::parser::MAX_DEPTH));
+    assert!(Parser::new(&mut l).parse().is_ok());
+
+    let mut l = Lexer::new(nested(jsonrs::parser::MAX_DEPTH + 1));
+    let err = Parser::new(&mut l).parse().unwrap_err();
+    assert!(format!("{:#}", err).contains("nesting exceeds"));
+}
+
 #[test]
 fn test_single_list_value() {
     let input = String::from(
@@ -169,3 +210,45 @@ fn test_multiple_list_value() {
 
     assert!(result.is_ok(), "{:?}", result.err());
 }
+
+#[test]
+fn test_parse_value_tree() {
+    let input = String::from(r#"{"s": "a\"b", "n": -1.5e2, "i": 42, "l": [true, null], "o": {}}"#);
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+
+    let value = p.parse().expect("valid JSON should parse");
+
+    assert_eq!(value.get("s"), Some(&Value::from("a\"b")));
+    assert_eq!(value.get("n"), Some(&Value::from(-150.0)));
+    assert_eq!(value.get("i"), Some(&Value::from(42)));
+    assert_eq!(
+        value.get("l"),
+        Some(&Value::Array(vec![Value::Bool(true), Value::Null]))
+    );
+    assert_eq!(value.pointer("/l/0"), Some(&Value::Bool(true)));
+    assert_eq!(
+        value.to_string(),
+        r#"{"i":42,"l":[true,null],"n":-150.0,"o":{},"s":"a\"b"}"#
+    );
+}
+
+#[test]
+fn test_parse_top_level_values() {
+    for (input, expected) in [
+        ("[1, 2]", Value::Array(vec![Value::from(1), Value::from(2)])),
+        (r#""text""#, Value::from("text")),
+        ("42", Value::from(42)),
+        ("true", Value::Bool(true)),
+        ("null", Value::Null),
+    ] {
+        let mut l = Lexer::new(String::from(input));
+        let mut p = Parser::new(&mut l);
+
+        assert_eq!(p.parse().unwrap(), expected, "{}", input);
+    }
+
+    let mut l = Lexer::new(String::from("[1] [2]"));
+    let mut p = Parser::new(&mut l);
+    assert!(p.parse().is_err());
+}
@@ -0,0 +1,56 @@
+use jsonrs::{encoding, lexer::Lexer, parser::Parser, value::Value};
+use proptest::prelude::*;
+
+/// Random documents of up to six levels, with finite numbers only since
+/// non-finite ones serialize as `null`.
+fn document() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>()
+            .prop_filter("finite", |f| f.is_finite())
+            .prop_map(Value::from),
+        any::<String>().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(6, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            prop::collection::btree_map(any::<String>(), inner, 0..8).prop_map(Value::Object),
+        ]
+    })
+}
+
+fn parse(input: &str) -> anyhow::Result<Value> {
+    let mut l = Lexer::new(input.into());
+    Parser::new(&mut l).parse()
+}
+
+proptest! {
+    #[test]
+    fn compact_round_trip(value in document()) {
+        prop_assert_eq!(parse(&value.to_string()).unwrap(), value);
+    }
+
+    #[test]
+    fn pretty_round_trip(value in document()) {
+        prop_assert_eq!(parse(&value.to_pretty_string()).unwrap(), value);
+    }
+
+    #[test]
+    fn arbitrary_text_does_not_panic(input in any::<String>()) {
+        let _ = parse(&input);
+    }
+
+    #[test]
+    fn json_like_text_does_not_panic(input in r#"[\[\]{}:,"0-9a-z\\ .eE+-]{0,64}"#) {
+        let _ = parse(&input);
+    }
+
+    #[test]
+    fn arbitrary_bytes_do_not_panic(input in prop::collection::vec(any::<u8>(), 0..256)) {
+        if let Ok(text) = encoding::decode(&input) {
+            let _ = parse(&text);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use jsonrs::{
+    redact::{Action, Matcher, Redactor},
+    value::Value,
+};
+use regex::Regex;
+
+fn json(input: &str) -> Value {
+    input.parse().expect("test JSON should parse")
+}
+
+fn redact(rules: &str, input: &str) -> (Value, usize) {
+    let redactor: Redactor = rules.parse().expect("rules should parse");
+    let mut value = json(input);
+    let count = redactor.redact(&mut value).unwrap();
+    (value, count)
+}
+
+const PAYLOAD: &str = r#"{
+    "user": {"name": "ada", "email": "ada@example.com", "Password": "hunter2"},
+    "auth": {"token": "abc", "expires": 3600},
+    "cards": ["4111111111111111", 4000056655665556, "n/a"],
+    "a/b": {"~": 1}
+}"#;
+
+#[test]
+fn test_key_rules_mask_whole_values() {
+    let (value, count) = redact(
+        r#"{"rules": [{"key": "(?i)password"}, {"key": "^auth$"}]}"#,
+        PAYLOAD,
+    );
+    assert_eq!(count, 2);
+    assert_eq!(
+        value.pointer("/user/Password"),
+        Some(&json(r#""[REDACTED]""#))
+    );
+    // containers are replaced as a whole
+    assert_eq!(value.pointer("/auth"), Some(&json(r#""[REDACTED]""#)));
+    assert_eq!(value.pointer("/user/name"), Some(&json(r#""ada""#)));
+}
+
+#[test]
+fn test_pointer_rules() {
+    let (value, count) = redact(
+        r#"{"placeholder": "***", "rules": [{"pointer": "/user/email"}, {"pointer": "/a~1b/~0"}, {"pointer": "/cards/2"}]}"#,
+        PAYLOAD,
+    );
+    assert_eq!(count, 3);
+    assert_eq!(value.pointer("/user/email"), Some(&json(r#""***""#)));
+    assert_eq!(value.pointer("/a~1b/~0"), Some(&json(r#""***""#)));
+    assert_eq!(value.pointer("/cards/2"), Some(&json(r#""***""#)));
+    assert_eq!(
+        value.pointer("/cards/0"),
+        Some(&json(r#""4111111111111111""#))
+    );
+}
+
+#[test]
+fn test_value_rules_match_strings_and_numbers() {
+    let (value, count) = redact(r#"{"rules": [{"value": "^\\d{13,19}$"}]}"#, PAYLOAD);
+    assert_eq!(count, 2);
+    assert_eq!(
+        value.pointer("/cards"),
+        Some(&json(r#"["[REDACTED]", "[REDACTED]", "n/a"]"#))
+    );
+}
+
+#[test]
+fn test_hash_is_keyed_stable_and_first_rule_wins() {
+    let rules =
+        r#"{"hash_key": "k1", "rules": [{"key": "email", "action": "hash"}, {"value": "@"}]}"#;
+    let (first, _) = redact(rules, PAYLOAD);
+    let (second, _) = redact(rules, r#"{"email": "ada@example.com"}"#);
+
+    let hashed = first.pointer("/user/email").unwrap();
+    assert_eq!(Some(hashed), second.pointer("/email"));
+    // HMAC-SHA256 of "\"ada@example.com\"" under "k1"
+    assert_eq!(
+        hashed.as_str().unwrap(),
+        "hmac-sha256:fa650afda6826139eb3b741c06c919d3849018384f1449c6e35a5c7d7a5a95f2"
+    );
+
+    // another key gives unrelated hashes
+    let (other, _) = redact(&rules.replace("k1", "k2"), PAYLOAD);
+    assert_ne!(other.pointer("/user/email"), Some(hashed));
+}
+
+#[test]
+fn test_hash_requires_a_key() {
+    let redactor: Redactor = r#"{"rules": [{"key": "email", "action": "hash"}]}"#
+        .parse()
+        .unwrap();
+    let mut value = json(PAYLOAD);
+    let err = redactor.redact(&mut value).unwrap_err();
+    assert!(err.to_string().contains("hash_key"), "{}", err);
+    assert_eq!(value, json(PAYLOAD));
+
+    let mut value = json(r#"{"email": "ada@example.com"}"#);
+    assert_eq!(redactor.hash_key("k").redact(&mut value).unwrap(), 1);
+}
+
+#[test]
+fn test_builder() {
+    let redactor = Redactor::new("x")
+        .hash_key("k")
+        .rule(Matcher::pointer("").unwrap(), Action::Placeholder)
+        .rule(Matcher::Key(Regex::new("a").unwrap()), Action::Hash);
+    let mut value = json(r#"{"a": 1}"#);
+    assert_eq!(redactor.redact(&mut value).unwrap(), 1);
+    assert_eq!(value, json(r#""x""#));
+}
+
+#[test]
+fn test_invalid_rules() {
+    for (rules, message) in [
+        ("[]", "rules must be an object"),
+        (r#"{"rule": []}"#, "unknown field"),
+        (r#"{"rules": [{}]}"#, "expected one of"),
+        (r#"{"rules": [{"key": "a", "value": "b"}]}"#, "exactly one"),
+        (r#"{"rules": [{"key": "("}]}"#, "invalid regex"),
+        (r#"{"rules": [{"pointer": "a"}]}"#, "must start with"),
+        (r#"{"hash_key": "", "rules": []}"#, "must not be empty"),
+        (
+            r#"{"rules": [{"key": "a", "action": "drop"}]}"#,
+            "action must be",
+        ),
+    ] {
+        let err = rules.parse::<Redactor>().unwrap_err();
+        assert!(
+            format!("{:#}", err).contains(message),
+            "{}: {:#}",
+            rules,
+            err
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use jsonrs::{
+    stats::{Entry, Stats},
+    value::Value,
+};
+
+fn json(input: &str) -> Value {
+    input.parse().expect("test JSON should parse")
+}
+
+fn entry(document: usize, path: &str, len: usize) -> Entry {
+    Entry {
+        document,
+        path: path.into(),
+        len,
+    }
+}
+
+const DOC: &str = r#"{
+    "id": 7,
+    "tags": ["a", "bcd", "ef"],
+    "items": [{"id": 1, "name": "first"}, {"id": 2, "name": null}],
+    "meta": {"ok": true, "ratio": 0.5}
+}"#;
+
+#[test]
+fn test_counts_and_depth() {
+    let stats = Stats::of(&json(DOC), 10);
+    assert_eq!(stats.documents, 1);
+    assert_eq!(stats.max_depth, 3);
+    let counts: Vec<_> = stats.counts.iter().map(|(k, n)| (*k, *n)).collect();
+    assert_eq!(
+        counts,
+        vec![
+            ("array", 2),
+            ("boolean", 1),
+            ("null", 1),
+            ("number", 4),
+            ("object", 4),
+            ("string", 4),
+        ]
+    );
+}
+
+#[test]
+fn test_depth_of_scalars_and_empty_containers() {
+    for (input, depth) in [("1", 0), ("[]", 1), ("{}", 1), (r#"[[{"a": []}]]"#, 4)] {
+        assert_eq!(Stats::of(&json(input), 10).max_depth, depth, "{}", input);
+    }
+}
+
+#[test]
+fn test_rankings() {
+    let stats = Stats::of(&json(DOC), 2);
+    assert_eq!(
+        stats.largest_arrays,
+        vec![entry(0, ".tags", 3), entry(0, ".items", 2)]
+    );
+    assert_eq!(
+        stats.largest_objects,
+        vec![entry(0, ".", 4), entry(0, ".items[0]", 2)]
+    );
+    assert_eq!(
+        stats.longest_strings,
+        vec![entry(0, ".items[0].name", 5), entry(0, ".tags[1]", 3)]
+    );
+    assert_eq!(stats.top_keys(), vec![("id", 3), ("name", 2)]);
+}
+
+#[test]
+fn test_size_attribution() {
+    let value = json(DOC);
+    let stats = Stats::of(&value, 10);
+    assert_eq!(stats.total_size, value.to_string().len());
+    assert_eq!(stats.size_by_key["id"], r#""id":7"#.len());
+    assert_eq!(
+        stats.size_by_key["tags"],
+        r#""tags":["a","bcd","ef"]"#.len()
+    );
+    // members plus braces and separating commas make up the whole document
+    let members: usize = stats.size_by_key.values().sum();
+    assert_eq!(members + 2 + 3, stats.total_size);
+    assert_eq!(stats.sizes()[0].0, "items");
+}
+
+#[test]
+fn test_multiple_documents() {
+    let mut stats = Stats::new(10);
+    for line in [r#"{"a": [1, 2]}"#, "[1, 2, 3]", r#"{"a": "xyz"}"#] {
+        stats.add(&json(line));
+    }
+    assert_eq!(stats.documents, 3);
+    assert_eq!(
+        stats.largest_arrays,
+        vec![entry(1, ".", 3), entry(0, ".a", 2)]
+    );
+    assert_eq!(stats.key_frequency["a"], 2);
+    assert_eq!(
+        stats.size_by_key["a"],
+        r#""a":[1,2]"#.len() + r#""a":"xyz""#.len()
+    );
+    assert_eq!(stats.size_by_key["."], "[1,2,3]".len());
+
+    let report = stats.to_value();
+    assert_eq!(report.pointer("/documents"), Some(&json("3")));
+    assert_eq!(
+        report.pointer("/largest_arrays/0"),
+        Some(&json(r#"{"document": 1, "path": ".", "length": 3}"#))
+    );
+    assert!(stats.to_string().contains("document 2 ."));
+}
@@ -0,0 +1,148 @@
+#![cfg(feature = "async")]
+
+use std::fs;
+
+use jsonrs::{
+    lexer::Lexer,
+    parser::Parser,
+    stream::{AsyncParser, Event},
+    value::Value,
+};
+use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+fn json(input: &str) -> Value {
+    input.parse().expect("test JSON should parse")
+}
+
+/// A parser reading from a peer that writes `input` in `chunk`-byte pieces
+/// and then closes the connection.
+fn peer(input: &[u8], chunk: usize) -> AsyncParser<DuplexStream> {
+    let (mut client, server) = duplex(64);
+    let input = input.to_vec();
+    tokio::spawn(async move {
+        for piece in input.chunks(chunk) {
+            client.write_all(piece).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+    });
+    AsyncParser::new(server)
+}
+
+/// Parse exactly one document from the stream.
+async fn parse_one(mut parser: AsyncParser<DuplexStream>) -> anyhow::Result<Value> {
+    let value = parser.next_value().await?;
+    anyhow::ensure!(parser.next_event().await?.is_none(), "trailing data");
+    value.ok_or_else(|| anyhow::anyhow!("empty input"))
+}
+
+#[tokio::test]
+async fn test_events() {
+    let mut parser = peer(br#" {"a": [1, "x\"y", {}], "b": null} "#, 1);
+    let mut events = Vec::new();
+    while let Some(event) = parser.next_event().await.unwrap() {
+        events.push(event);
+    }
+    assert_eq!(
+        events,
+        [
+            Event::StartObject,
+            Event::Key("a".into()),
+            Event::StartArray,
+            Event::Value(1i64.into()),
+            Event::Value("x\"y".into()),
+            Event::StartObject,
+            Event::EndObject,
+            Event::EndArray,
+            Event::Key("b".into()),
+            Event::Value(Value::Null),
+            Event::EndObject,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_document_sequence() {
+    let input = "{\"id\": 1, \"tags\": [\"ü\", \"\\u00e9\"]}\n[true,false]\"s\" -12.5e3 null\n{}";
+    for chunk in 1..8 {
+        let mut parser = peer(input.as_bytes(), chunk);
+        let mut values = Vec::new();
+        while let Some(value) = parser.next_value().await.unwrap() {
+            values.push(value);
+        }
+        assert_eq!(
+            values,
+            [
+                json(r#"{"id": 1, "tags": ["ü", "é"]}"#),
+                json("[true, false]"),
+                json(r#""s""#),
+                json("-12.5e3"),
+                Value::Null,
+                json("{}"),
+            ],
+            "chunks of {}",
+            chunk
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_yields_before_end_of_stream() {
+    let (mut client, server) = duplex(64);
+    let mut parser = AsyncParser::new(server);
+
+    client.write_all(br#"{"first": 1}"#).await.unwrap();
+    assert_eq!(
+        parser.next_value().await.unwrap(),
+        Some(json(r#"{"first": 1}"#))
+    );
+    client.write_all(b" [2]").await.unwrap();
+    assert_eq!(parser.next_value().await.unwrap(), Some(json("[2]")));
+    drop(client);
+    assert_eq!(parser.next_value().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_errors() {
+    for (input, message) in [
+        ("[1, 2", "unexpected end of input at byte 5"),
+        (r#"{"a" 1}"#, "expected `:` after key at byte 5"),
+        (
+            "[1 2]",
+            "expected `,` or the end of the container at byte 3",
+        ),
+        ("[1,]", "lists should not end with a comma"),
+        (r#"{"a":1,}"#, "json objects should not end with a comma"),
+        ("[1}", "mismatched `}` at byte 2"),
+        ("]", "expected a value at byte 0"),
+        (r#"["abc"#, "unterminated string starting at byte 1"),
+        ("[01]", "unexpected \"01\" at byte 1"),
+    ] {
+        let err = parse_one(peer(input.as_bytes(), 2)).await.unwrap_err();
+        assert_eq!(err.to_string(), message, "{}", input);
+    }
+    let deep = "[".repeat(1000);
+    let err = parse_one(peer(deep.as_bytes(), 64)).await.unwrap_err();
+    assert_eq!(err.to_string(), "nesting exceeds 512 levels");
+}
+
+#[tokio::test]
+async fn test_conformance() {
+    let dir = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/JSONTestSuite/test_parsing"
+    );
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if name.starts_with("i_") {
+            continue;
+        }
+        let bytes = fs::read(&path).unwrap();
+        let expected = std::str::from_utf8(&bytes).ok().and_then(|text| {
+            let mut l = Lexer::new(text.into());
+            Parser::new(&mut l).parse().ok()
+        });
+        let actual = parse_one(peer(&bytes, 3)).await.ok();
+        assert_eq!(actual, expected, "{}", name);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+
+use jsonrs::{
+    encoding,
+    lexer::Lexer,
+    parser::Parser,
+    structural::{self, Backend},
+    value::Value,
+};
+use proptest::prelude::*;
+
+/// Stage 1 written as a plain state machine over bytes. As in stage 1, a
+/// backslash escapes the next byte even outside strings, which only matters
+/// for input that is invalid anyway.
+fn reference_index(input: &str) -> Option<Vec<u32>> {
+    let mut out = Vec::new();
+    let (mut in_string, mut escaped, mut in_scalar) = (false, false, false);
+    for (i, &b) in input.as_bytes().iter().enumerate() {
+        let was_escaped = escaped;
+        escaped = b == b'\\' && !was_escaped;
+        if in_string {
+            if b == b'"' && !was_escaped {
+                in_string = false;
+            }
+            continue;
+        }
+        match b {
+            b'"' if !was_escaped => {
+                out.push(i as u32);
+                in_string = true;
+                in_scalar = false;
+            }
+            b'{' | b'}' | b'[' | b']' | b':' | b',' => {
+                out.push(i as u32);
+                in_scalar = false;
+            }
+            b' ' | b'\t' | b'\n' | b'\r' => in_scalar = false,
+            _ => {
+                if !in_scalar {
+                    out.push(i as u32);
+                }
+                in_scalar = true;
+            }
+        }
+    }
+    (!in_string).then_some(out)
+}
+
+fn parse(input: &str) -> anyhow::Result<Value> {
+    let mut l = Lexer::new(input.into());
+    Parser::new(&mut l).parse()
+}
+
+fn assert_matches_reference(input: &str) {
+    for backend in Backend::available() {
+        assert_eq!(
+            structural::index_with(backend, input).ok(),
+            reference_index(input),
+            "{:?} on {:?}",
+            backend,
+            input
+        );
+    }
+}
+
+#[test]
+fn test_index_simple() {
+    let input = r#"{"a": [1, true, "x\"y"], "b":null}"#;
+    assert_eq!(
+        structural::index(input).unwrap(),
+        vec![0, 1, 4, 6, 7, 8, 10, 14, 16, 22, 23, 25, 28, 29, 33]
+    );
+}
+
+#[test]
+fn test_backslash_runs_across_blocks() {
+    // place runs of backslashes so that they end exactly at, just before and
+    // just after a 64-byte boundary
+    for run in 1..6 {
+        for pad in 56..66 {
+            let input = format!(
+                r#"["{}{}"]"#,
+                "x".repeat(pad),
+                "\\".repeat(run) + if run % 2 == 1 { "\"" } else { "" }
+            );
+            assert_matches_reference(&input);
+        }
+    }
+}
+
+#[test]
+fn test_unterminated_string() {
+    for backend in Backend::available() {
+        assert!(structural::index_with(backend, r#"["abc\"]"#).is_err());
+    }
+}
+
+#[test]
+fn test_unsupported_backend_is_an_error() {
+    let all = [
+        Backend::Scalar,
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2,
+    ];
+    for backend in all {
+        assert_eq!(
+            structural::index_with(backend, "[1]").is_ok(),
+            backend.is_supported(),
+            "{:?}",
+            backend
+        );
+        assert_eq!(
+            Backend::available().contains(&backend),
+            backend.is_supported()
+        );
+    }
+}
+
+#[test]
+fn test_stage2_conformance() {
+    let dir = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/JSONTestSuite/test_parsing"
+    );
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let Ok(text) = encoding::decode(&fs::read(&path).unwrap()) else {
+            continue;
+        };
+        let expected = parse(&text).ok();
+        for backend in Backend::available() {
+            let actual = structural::parse_with(backend, &text).ok();
+            assert_eq!(actual, expected, "{:?} on {}", backend, name);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn index_matches_reference(input in r#"[\[\]{}:," \\a1\n]{0,200}"#) {
+        assert_matches_reference(&input);
+    }
+
+    #[test]
+    fn stage2_agrees_with_parser(input in r#"[\[\]{}:,"\\ a0-2.e\-]{0,80}|[\[\]{}:,"a1 ]{0,40}"#) {
+        for backend in Backend::available() {
+            prop_assert_eq!(structural::parse_with(backend, &input).ok(), parse(&input).ok());
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use jsonrs::wasm::{validate, Validation};
+
+#[test]
+fn test_valid() {
+    assert_eq!(
+        validate("{\"a\": [1, 2]}"),
+        Validation {
+            ok: true,
+            line: 0,
+            col: 0,
+            message: String::new(),
+        }
+    );
+}
+
+#[test]
+fn test_invalid() {
+    let result = validate("{\n  \"name\": \"ü\",\n  \"n\": 01\n}");
+    assert!(!result.ok);
+    assert_eq!((result.line, result.col), (3, 8));
+    assert_eq!(
+        result.message,
+        "Could not parse document: invalid number \"01\""
+    );
+
+    let result = validate("[1, 2");
+    assert_eq!((result.line, result.col), (1, 6));
+
+    let result = validate("\u{feff}[]");
+    assert_eq!((result.ok, result.line, result.col), (false, 1, 1));
+}